@@ -8,9 +8,10 @@ use crate::{
         circuit::CircuitBuilderStage,
         flex_gate::{
             threads::{GateStatistics, MultiPhaseCoreManager, SinglePhaseCoreManager},
-            MultiPhaseThreadBreakPoints, MAX_PHASE,
+            GateChip, MultiPhaseThreadBreakPoints, MAX_PHASE,
         },
         range::RangeConfig,
+        shuffle::{ShuffleChip, ShuffleConfig, ShuffleQuery},
         RangeChip,
     },
     halo2_proofs::{
@@ -26,7 +27,7 @@ use crate::{
     AssignedValue, Context,
 };
 
-use super::BaseCircuitParams;
+use super::{BaseCircuitParams, BaseConfig};
 
 /// Keeping the naming `RangeCircuitBuilder` for backwards compatibility.
 pub type RangeCircuitBuilder<F> = BaseCircuitBuilder<F>;
@@ -35,7 +36,8 @@ pub type RangeCircuitBuilder<F> = BaseCircuitBuilder<F>;
 /// regions into a single physical circuit.
 ///
 /// [BaseCircuitBuilder] is a circuit builder to create a circuit where the columns correspond to [super::BaseConfig].
-/// This builder can hold multiple threads, but the `Circuit` implementation only evaluates the first phase.
+/// This builder can hold multiple threads, but the `Circuit` implementation only evaluates the first phase,
+/// plus the shuffles queued with [BaseCircuitBuilder::assert_shuffle] in the second phase.
 /// The user will have to implement a separate `Circuit` with multi-phase witness generation logic.
 ///
 /// This is used to manage the virtual region corresponding to [super::FlexGateConfig] and (optionally) [RangeConfig].
@@ -54,6 +56,9 @@ pub struct BaseCircuitBuilder<F: ScalarField> {
     pub config_params: BaseCircuitParams,
     /// The assigned instances to expose publicly at the end of circuit synthesis
     pub assigned_instances: Vec<Vec<AssignedValue<F>>>,
+    /// The shuffles to prove in phase 1 at circuit synthesis
+    #[getset(get = "pub")]
+    pub(super) shuffles: Vec<ShuffleQuery<F>>,
 }
 
 impl<F: ScalarField> Default for BaseCircuitBuilder<F> {
@@ -82,7 +87,13 @@ impl<F: ScalarField> BaseCircuitBuilder<F> {
         let core = MultiPhaseCoreManager::new(witness_gen_only);
         let lookup_manager = [(); MAX_PHASE]
             .map(|_| LookupAnyManager::new(witness_gen_only, core.copy_manager.clone()));
-        Self { core, lookup_manager, config_params: Default::default(), assigned_instances: vec![] }
+        Self {
+            core,
+            lookup_manager,
+            config_params: Default::default(),
+            assigned_instances: vec![],
+            shuffles: vec![],
+        }
     }
 
     /// Creates a new [MultiPhaseCoreManager] depending on the stage of circuit building. If the stage is [CircuitBuilderStage::Prover], the [MultiPhaseCoreManager] is used for witness generation only.
@@ -222,6 +233,7 @@ impl<F: ScalarField> BaseCircuitBuilder<F> {
             lm.clear();
         }
         self.assigned_instances.iter_mut().for_each(|c| c.clear());
+        self.shuffles.clear();
     }
 
     /// Returns a mutable reference to the [Context] of a gate thread. Spawns a new thread for the given phase, if none exists.
@@ -263,6 +275,17 @@ impl<F: ScalarField> BaseCircuitBuilder<F> {
         assert_ne!(k, 0, "k must be set");
         let max_rows = (1 << k) - minimum_rows.unwrap_or(0);
         let gate_params = self.core.calculate_params(k, minimum_rows);
+        let mut num_advice_per_phase = gate_params.num_advice_per_phase;
+        let mut num_fixed = gate_params.num_fixed;
+        let use_shuffle = !self.shuffles.is_empty();
+        if use_shuffle {
+            let (total_shuffle_advice, total_fixed) = self.shuffle_statistics();
+            if num_advice_per_phase.len() < 2 {
+                num_advice_per_phase.resize(2, 0);
+            }
+            num_advice_per_phase[1] = (total_shuffle_advice + max_rows - 1) / max_rows;
+            num_fixed = (total_fixed + (1 << k) - 1) >> k;
+        }
         let total_lookup_advice_per_phase = self.total_lookup_advice_per_phase();
         let num_lookup_advice_per_phase = total_lookup_advice_per_phase
             .iter()
@@ -271,11 +294,12 @@ impl<F: ScalarField> BaseCircuitBuilder<F> {
 
         let params = BaseCircuitParams {
             k: gate_params.k,
-            num_advice_per_phase: gate_params.num_advice_per_phase,
-            num_fixed: gate_params.num_fixed,
+            num_advice_per_phase,
+            num_fixed,
             num_lookup_advice_per_phase,
             lookup_bits: self.lookup_bits(),
             num_instance_columns: ni,
+            use_shuffle,
         };
         self.config_params = params.clone();
         #[cfg(feature = "display")]
@@ -286,6 +310,111 @@ impl<F: ScalarField> BaseCircuitBuilder<F> {
         params
     }
 
+    /// Queues a proof that `shuffled` is a permutation of `original`, where both are lists of tuples of the same length.
+    ///
+    /// The proof is only constrained during `synthesize`, in phase 1 once the shuffle challenges are known,
+    /// so the circuit must be configured with `use_shuffle` (e.g. by [BaseCircuitBuilder::calculate_params]).
+    pub fn assert_shuffle(
+        &mut self,
+        original: Vec<Vec<AssignedValue<F>>>,
+        shuffled: Vec<Vec<AssignedValue<F>>>,
+    ) {
+        // phase 1 holds the break points of the shuffle threads
+        self.core.touch(1);
+        self.shuffles.push(ShuffleQuery::new(original, shuffled));
+    }
+
+    /// Proves all queued shuffles in the phase 1 context `ctx` with the given challenge values.
+    ///
+    /// Returns the [ShuffleChip] whose challenge cells must be pinned with [ShuffleConfig::assign_challenges].
+    pub fn assign_shuffles(&self, ctx: &mut Context<F>, theta: F, gamma: F) -> ShuffleChip<F> {
+        let chip = ShuffleChip::load_challenges(ctx, GateChip::default(), theta, gamma);
+        for query in &self.shuffles {
+            chip.assert_shuffle(ctx, &query.original, &query.shuffled);
+        }
+        chip
+    }
+
+    /// Simulates the phase 1 witness generation of the queued shuffles with dummy challenges.
+    /// Returns the number of phase 1 advice cells and the total number of distinct constants of the circuit.
+    fn shuffle_statistics(&self) -> (usize, usize) {
+        let mut phase_manager = SinglePhaseCoreManager::new(false, Default::default()).in_phase(1);
+        self.assign_shuffles(phase_manager.main(), F::ZERO, F::ZERO);
+        let total_advice = phase_manager.total_advice();
+        let total_fixed = {
+            let copy_manager = self.core.copy_manager.lock().unwrap();
+            let shuffle_copy_manager = phase_manager.copy_manager.lock().unwrap();
+            copy_manager
+                .constant_equalities
+                .iter()
+                .chain(shuffle_copy_manager.constant_equalities.iter())
+                .map(|(c, _)| *c)
+                .sorted()
+                .dedup()
+                .count()
+        };
+        // prevent drop warnings
+        phase_manager.clear();
+        (total_advice, total_fixed)
+    }
+
+    /// Assigns the phase 1 region proving all queued shuffles and then imposes all equality constraints.
+    /// Should only be called in `synthesize`, after the phase 0 region has been assigned.
+    pub fn synthesize_shuffles(
+        &self,
+        shuffle: &ShuffleConfig<F>,
+        config: &BaseConfig<F>,
+        layouter: &mut impl Layouter<F>,
+    ) {
+        let basic_gates = config
+            .gate()
+            .basic_gates
+            .get(1)
+            .filter(|gates| !gates.is_empty())
+            .expect("shuffles need phase 1 advice columns");
+        let usable_rows = config.gate().max_rows;
+        let witness_gen_only = self.witness_gen_only();
+        let copy_manager = &self.core.copy_manager;
+        let break_points =
+            &self.core.phase_manager.get(1).expect("no shuffle has been queued").break_points;
+
+        // The phase 1 threads depend on the challenges, so they are generated anew on every call.
+        // Their copy constraints are rolled back after assignment so that calling `synthesize` again does not duplicate them.
+        let (advice_equalities, constant_equalities) = {
+            let copy_manager = copy_manager.lock().unwrap();
+            (copy_manager.advice_equalities.clone(), copy_manager.constant_equalities.clone())
+        };
+        let (theta, gamma) = shuffle.challenge_values(layouter);
+        let mut phase_manager = SinglePhaseCoreManager::new(witness_gen_only, copy_manager.clone())
+            .in_phase(1)
+            .unknown(self.core.use_unknown());
+        *phase_manager.break_points.borrow_mut() = break_points.borrow().clone();
+        let chip = self.assign_shuffles(phase_manager.main(), theta, gamma);
+
+        layouter
+            .assign_region(
+                || "BaseCircuitBuilder shuffles",
+                |mut region| {
+                    phase_manager.assign_raw(&(basic_gates.clone(), usable_rows), &mut region);
+                    if witness_gen_only {
+                        shuffle.assign_challenges(&mut region, &chip, None);
+                    } else {
+                        let mut guard = copy_manager.lock().unwrap();
+                        shuffle.assign_challenges(&mut region, &chip, Some(&mut guard));
+                        drop(guard);
+                        copy_manager.assign_raw(config.constants(), &mut region);
+                    }
+                    Ok(())
+                },
+            )
+            .unwrap();
+
+        *break_points.borrow_mut() = phase_manager.break_points.take();
+        let mut copy_manager = copy_manager.lock().unwrap();
+        copy_manager.advice_equalities = advice_equalities;
+        copy_manager.constant_equalities = constant_equalities;
+    }
+
     /// Copies `assigned_instances` to the instance columns. Should only be called at the very end of
     /// `synthesize` after virtual `assigned_instances` have been assigned to physical circuit.
     pub fn assign_instances(
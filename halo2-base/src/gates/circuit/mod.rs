@@ -14,7 +14,7 @@ use self::builder::BaseCircuitBuilder;
 
 use super::flex_gate::{FlexGateConfig, FlexGateConfigParams};
 use super::range::RangeConfig;
-// use super::shuffle::ShuffleConfig;
+use super::shuffle::ShuffleConfig;
 
 /// Module that helps auto-build circuits
 pub mod builder;
@@ -38,6 +38,10 @@ pub struct BaseCircuitParams {
     /// Number of public instance columns
     #[serde(default)]
     pub num_instance_columns: usize,
+    /// Whether the circuit proves shuffles queued with [BaseCircuitBuilder::assert_shuffle] in phase 1.
+    /// This adds the shuffle challenges and requires phase 1 advice columns.
+    #[serde(default)]
+    pub use_shuffle: bool,
 }
 
 impl BaseCircuitParams {
@@ -57,6 +61,8 @@ pub struct BaseConfig<F: ScalarField> {
     pub base: MaybeRangeConfig<F>,
     /// The public instance column
     pub instance: Vec<Column<Instance>>,
+    /// The shuffle challenges, if using
+    pub shuffle: Option<ShuffleConfig<F>>,
 }
 
 /// Smart Halo2 circuit config that has different variants depending on whether you need range checks or not.
@@ -69,15 +75,6 @@ pub enum MaybeRangeConfig<F: ScalarField> {
     WithRange(RangeConfig<F>),
 }
 
-
-// #[derive(Clone, Debug)]
-// pub enum MaybeShuffleConfig<F: ScalarField> {
-//     /// Config for a circuit that does not use range checks
-//     WithoutShuffle(FlexGateConfig<F>),
-//     /// Config for a circuit that does use range checks
-//     WithShuffle(ShuffleConfig<F>),
-// }
-
 impl<F: ScalarField> BaseConfig<F> {
     /// Generates a new `BaseConfig` depending on `params`.
     /// - It will generate a `RangeConfig` is `params` has `lookup_bits` not None **and** `num_lookup_advice_per_phase` are not all empty or zero (i.e., if `params` indicates that the circuit actually requires a lookup table).
@@ -102,7 +99,8 @@ impl<F: ScalarField> BaseConfig<F> {
                 inst
             })
             .collect();
-        Self { base, instance }
+        let shuffle = params.use_shuffle.then(|| ShuffleConfig::configure(meta));
+        Self { base, instance, shuffle }
     }
 
     /// Returns the inner [`FlexGateConfig`]
@@ -181,10 +179,7 @@ impl<F: ScalarField> Circuit<F> for BaseCircuitBuilder<F> {
         if let MaybeRangeConfig::WithRange(config) = &config.base {
             config.load_lookup_table(&mut layouter).expect("load lookup table should not fail");
         }
-        // if let MaybeShuffleConfig::WithShuffle(config) = &config.base {
-        //     config.load_shuffle(&mut layouter).expect("load shuffle should not fail");
-        // }
-        // Only FirstPhase (phase 0)
+        // FirstPhase (phase 0)
         layouter
             .assign_region(
                 || "BaseCircuitBuilder generated circuit",
@@ -198,14 +193,18 @@ impl<F: ScalarField> Circuit<F> for BaseCircuitBuilder<F> {
                     if let MaybeRangeConfig::WithRange(config) = &config.base {
                         self.assign_lookups_in_phase(config, &mut region, 0);
                     }
-                    // Impose equality constraints
-                    if !self.core.witness_gen_only() {
+                    // Impose equality constraints, unless there are phase 1 cells left to assign
+                    if !self.core.witness_gen_only() && config.shuffle.is_none() {
                         self.core.copy_manager.assign_raw(config.constants(), &mut region);
                     }
                     Ok(())
                 },
             )
             .unwrap();
+        // SecondPhase (phase 1), only used by the shuffle argument
+        if let Some(shuffle) = &config.shuffle {
+            self.synthesize_shuffles(shuffle, &config, &mut layouter);
+        }
 
         self.assign_instances(&config.instance, layouter.namespace(|| "expose"));
         Ok(())
@@ -4,6 +4,8 @@ pub mod circuit;
 pub mod flex_gate;
 /// Module using a single lookup table for range checks
 pub mod range;
+/// Module implementing a shuffle (permutation) argument over virtual cells
+pub mod shuffle;
 
 /// Tests
 #[cfg(test)]
@@ -11,3 +13,4 @@ pub mod tests;
 
 pub use flex_gate::{GateChip, GateInstructions};
 pub use range::{RangeChip, RangeInstructions};
+pub use shuffle::ShuffleChip;
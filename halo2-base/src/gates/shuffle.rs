@@ -0,0 +1,215 @@
+use std::marker::PhantomData;
+
+use itertools::Itertools;
+
+use crate::{
+    gates::{flex_gate::GateChip, GateInstructions},
+    halo2_proofs::{
+        circuit::{Layouter, Region, Value},
+        plonk::{Advice, Challenge, Column, ConstraintSystem, FirstPhase, SecondPhase, Selector},
+        poly::Rotation,
+    },
+    utils::{
+        halo2::{constrain_virtual_equals_external, raw_assign_advice},
+        ScalarField,
+    },
+    virtual_region::copy_constraints::CopyConstraintManager,
+    AssignedValue, Context,
+    QuantumCell::Constant,
+};
+
+/// Physical columns for the challenges of the shuffle argument.
+///
+/// The challenges `theta` and `gamma` are only available after [FirstPhase], so they are assigned to a
+/// [SecondPhase] advice column and pinned there by a custom gate. The pinned cells are then copied into
+/// virtual phase 1 cells, which [ShuffleChip] uses like any other witness.
+#[derive(Clone, Copy, Debug)]
+pub struct ShuffleConfig<F: ScalarField> {
+    /// Challenge used to compress a tuple into a single field element
+    pub theta: Challenge,
+    /// Challenge added to every compressed tuple in the grand product
+    pub gamma: Challenge,
+    /// Advice column holding `theta` at offset 0 and `gamma` at offset 1
+    pub challenges: Column<Advice>,
+    /// Selector enabling the gate that pins the challenges to `challenges`
+    pub q_challenges: Selector,
+    _marker: PhantomData<F>,
+}
+
+impl<F: ScalarField> ShuffleConfig<F> {
+    /// Creates the challenges, the challenge column and the gate pinning them together.
+    pub fn configure(meta: &mut ConstraintSystem<F>) -> Self {
+        let theta = meta.challenge_usable_after(FirstPhase);
+        let gamma = meta.challenge_usable_after(FirstPhase);
+        let challenges = meta.advice_column_in(SecondPhase);
+        meta.enable_equality(challenges);
+        let q_challenges = meta.selector();
+
+        meta.create_gate("shuffle challenges", |meta| {
+            let q = meta.query_selector(q_challenges);
+            let theta_cell = meta.query_advice(challenges, Rotation::cur());
+            let gamma_cell = meta.query_advice(challenges, Rotation::next());
+            let theta = meta.query_challenge(theta);
+            let gamma = meta.query_challenge(gamma);
+            vec![q.clone() * (theta_cell - theta), q * (gamma_cell - gamma)]
+        });
+
+        Self { theta, gamma, challenges, q_challenges, _marker: PhantomData }
+    }
+
+    /// Returns the values of `theta` and `gamma`.
+    ///
+    /// These are zero until the prover has committed to [FirstPhase], e.g. during key generation.
+    pub fn challenge_values(&self, layouter: &impl Layouter<F>) -> (F, F) {
+        let [theta, gamma] = [self.theta, self.gamma].map(|challenge| {
+            let mut value = F::ZERO;
+            layouter.get_challenge(challenge).map(|v| value = v);
+            value
+        });
+        (theta, gamma)
+    }
+
+    /// Assigns the challenges to the challenge column and, unless `copy_manager` is `None` (witness generation only),
+    /// constrains them to equal the virtual cells `chip.theta` and `chip.gamma`.
+    ///
+    /// The virtual cells must have already been assigned to the physical circuit.
+    pub fn assign_challenges(
+        &self,
+        region: &mut Region<F>,
+        chip: &ShuffleChip<F>,
+        copy_manager: Option<&mut CopyConstraintManager<F>>,
+    ) {
+        let cells = [chip.theta, chip.gamma]
+            .into_iter()
+            .enumerate()
+            .map(|(offset, challenge)| {
+                raw_assign_advice(region, self.challenges, offset, Value::known(*challenge.value()))
+                    .cell()
+            })
+            .collect_vec();
+        if let Some(copy_manager) = copy_manager {
+            self.q_challenges.enable(region, 0).unwrap();
+            for (cell, challenge) in cells.into_iter().zip([chip.theta, chip.gamma]) {
+                constrain_virtual_equals_external(region, challenge, cell, copy_manager);
+            }
+        }
+    }
+}
+
+/// Two lists of tuples whose permutation relation is proven once the shuffle challenges are known.
+///
+/// Every tuple, in both lists, must have the same length.
+#[derive(Clone, Debug)]
+pub struct ShuffleQuery<F: ScalarField> {
+    /// The original list of tuples
+    pub original: Vec<Vec<AssignedValue<F>>>,
+    /// The list of tuples claimed to be a permutation of `original`
+    pub shuffled: Vec<Vec<AssignedValue<F>>>,
+}
+
+impl<F: ScalarField> ShuffleQuery<F> {
+    /// Creates a new [ShuffleQuery] after checking that both lists have the same shape.
+    pub fn new(original: Vec<Vec<AssignedValue<F>>>, shuffled: Vec<Vec<AssignedValue<F>>>) -> Self {
+        assert_eq!(original.len(), shuffled.len(), "lists to shuffle must have the same length");
+        let width = original.first().map_or(0, |tuple| tuple.len());
+        assert!(
+            original.iter().chain(shuffled.iter()).all(|tuple| tuple.len() == width),
+            "tuples to shuffle must all have the same length"
+        );
+        assert!(width != 0 || original.is_empty(), "tuples to shuffle must be non-empty");
+        Self { original, shuffled }
+    }
+}
+
+/// Chip proving that two lists of tuples are permutations of each other with a grand product argument.
+///
+/// Each tuple `(a_0, ..., a_{w-1})` is compressed to `a_0 * theta^{w-1} + ... + a_{w-1}` and the lists are a
+/// permutation of each other (with overwhelming probability over `theta` and `gamma`) iff
+/// `prod (original_i + gamma) = prod (shuffled_i + gamma)`.
+/// This is checked with the running column `z_0 = 1`, `z_{i+1} * (shuffled_i + gamma) = z_i * (original_i + gamma)`
+/// ending with `z_n = 1`.
+///
+/// All methods must be called on a [Context] in phase 1 (or later), since they use the challenges.
+#[derive(Clone, Debug)]
+pub struct ShuffleChip<F: ScalarField> {
+    /// Chip for basic arithmetic operations
+    pub gate: GateChip<F>,
+    /// Virtual cell holding the compression challenge
+    pub theta: AssignedValue<F>,
+    /// Virtual cell holding the grand product challenge
+    pub gamma: AssignedValue<F>,
+}
+
+impl<F: ScalarField> ShuffleChip<F> {
+    /// Creates a new [ShuffleChip] from the virtual cells holding the challenges.
+    pub fn new(gate: GateChip<F>, theta: AssignedValue<F>, gamma: AssignedValue<F>) -> Self {
+        Self { gate, theta, gamma }
+    }
+
+    /// Loads the challenge values as witnesses in `ctx` and returns the corresponding [ShuffleChip].
+    ///
+    /// The loaded cells are unconstrained: they must be pinned to the challenges with [ShuffleConfig::assign_challenges].
+    pub fn load_challenges(ctx: &mut Context<F>, gate: GateChip<F>, theta: F, gamma: F) -> Self {
+        assert_ne!(ctx.phase(), 0, "shuffle challenges are not available in phase 0");
+        let theta = ctx.load_witness(theta);
+        let gamma = ctx.load_witness(gamma);
+        Self::new(gate, theta, gamma)
+    }
+
+    /// Compresses `tuple` into a single field element using `theta`.
+    pub fn compress(&self, ctx: &mut Context<F>, tuple: &[AssignedValue<F>]) -> AssignedValue<F> {
+        let (first, rest) = tuple.split_first().expect("tuple must be non-empty");
+        rest.iter().fold(*first, |acc, a| self.gate.mul_add(ctx, acc, self.theta, *a))
+    }
+
+    /// Constrains the running column of the grand product and returns its last value `z_n`,
+    /// which is 1 iff `shuffled` is a permutation of `original` (with overwhelming probability).
+    pub fn grand_product(
+        &self,
+        ctx: &mut Context<F>,
+        original: &[Vec<AssignedValue<F>>],
+        shuffled: &[Vec<AssignedValue<F>>],
+    ) -> AssignedValue<F> {
+        assert_eq!(original.len(), shuffled.len(), "lists to shuffle must have the same length");
+        let mut z: Option<AssignedValue<F>> = None;
+        for (a, b) in original.iter().zip(shuffled) {
+            let a = self.compress(ctx, a);
+            let b = self.compress(ctx, b);
+            let a = self.gate.add(ctx, a, self.gamma);
+            let b = self.gate.add(ctx, b, self.gamma);
+            // z_i * (a + gamma), skipping the multiplication by z_0 = 1
+            let lhs = match z {
+                Some(z) => self.gate.mul(ctx, z, a),
+                None => a,
+            };
+            // b + gamma is zero with negligible probability, in which case z_{i+1} is left unconstrained
+            let z_next = ctx.load_witness(*lhs.value() * b.value().invert().unwrap_or(F::ZERO));
+            let rhs = self.gate.mul(ctx, z_next, b);
+            ctx.constrain_equal(&lhs, &rhs);
+            z = Some(z_next);
+        }
+        z.unwrap_or_else(|| ctx.load_constant(F::ONE))
+    }
+
+    /// Returns 1 if `shuffled` is a permutation of `original`, 0 otherwise (with overwhelming probability).
+    pub fn is_shuffle(
+        &self,
+        ctx: &mut Context<F>,
+        original: &[Vec<AssignedValue<F>>],
+        shuffled: &[Vec<AssignedValue<F>>],
+    ) -> AssignedValue<F> {
+        let z = self.grand_product(ctx, original, shuffled);
+        self.gate.is_equal(ctx, z, Constant(F::ONE))
+    }
+
+    /// Constrains `shuffled` to be a permutation of `original`.
+    pub fn assert_shuffle(
+        &self,
+        ctx: &mut Context<F>,
+        original: &[Vec<AssignedValue<F>>],
+        shuffled: &[Vec<AssignedValue<F>>],
+    ) {
+        let z = self.grand_product(ctx, original, shuffled);
+        self.gate.assert_is_const(ctx, &z, &F::ONE);
+    }
+}
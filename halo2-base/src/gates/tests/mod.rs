@@ -6,4 +6,5 @@ mod idx_to_indicator;
 mod neg_prop;
 mod pos_prop;
 mod range;
+mod shuffle;
 mod utils;
@@ -0,0 +1,89 @@
+use crate::gates::circuit::{builder::BaseCircuitBuilder, CircuitBuilderStage};
+use crate::halo2_proofs::{
+    dev::MockProver,
+    plonk::{keygen_pk, keygen_vk},
+};
+use crate::utils::{
+    fs::gen_srs,
+    testing::{check_proof, gen_proof},
+};
+use crate::{AssignedValue, Context};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use test_log::test;
+
+use super::*;
+
+const K: usize = 8;
+
+fn load_rows(ctx: &mut Context<Fr>, rows: &[[u64; 2]]) -> Vec<Vec<AssignedValue<Fr>>> {
+    rows.iter().map(|row| ctx.assign_witnesses(row.map(Fr::from))).collect()
+}
+
+fn shuffle_circuit(
+    stage: CircuitBuilderStage,
+    original: &[[u64; 2]],
+    shuffled: &[[u64; 2]],
+) -> BaseCircuitBuilder<Fr> {
+    let mut builder = BaseCircuitBuilder::from_stage(stage).use_k(K);
+    let ctx = builder.main(0);
+    let original = load_rows(ctx, original);
+    let shuffled = load_rows(ctx, shuffled);
+    builder.assert_shuffle(original, shuffled);
+    builder
+}
+
+fn random_rows() -> (Vec<[u64; 2]>, Vec<[u64; 2]>) {
+    let mut rng = StdRng::seed_from_u64(0);
+    // few distinct keys so that the tuples, and not only their entries, matter
+    let original = (0..20).map(|_| [rng.gen_range(0..4), rng.gen()]).collect::<Vec<_>>();
+    let mut shuffled = original.clone();
+    shuffled.shuffle(&mut rng);
+    (original, shuffled)
+}
+
+fn mock_shuffle(original: &[[u64; 2]], shuffled: &[[u64; 2]]) -> bool {
+    let mut builder = shuffle_circuit(CircuitBuilderStage::Mock, original, shuffled);
+    let params = builder.calculate_params(Some(9));
+    assert!(params.use_shuffle);
+    MockProver::run(K as u32, &builder, vec![]).unwrap().verify().is_ok()
+}
+
+#[test]
+fn test_shuffle_tuples() {
+    let (original, shuffled) = random_rows();
+    assert!(mock_shuffle(&original, &shuffled));
+}
+
+#[test]
+fn test_shuffle_not_permutation() {
+    let (original, mut shuffled) = random_rows();
+    shuffled[3][1] += 1;
+    assert!(!mock_shuffle(&original, &shuffled));
+}
+
+#[test]
+fn test_shuffle_tuple_entries_mixed() {
+    let (original, mut shuffled) = random_rows();
+    // same multiset of entries per column, but not of tuples
+    let (a, b) = (shuffled[0][1], shuffled[1][1]);
+    shuffled[0][1] = b;
+    shuffled[1][1] = a;
+    assert!(!mock_shuffle(&original, &shuffled));
+}
+
+#[test]
+fn test_shuffle_real_prover() {
+    let (original, shuffled) = random_rows();
+    let mut builder = shuffle_circuit(CircuitBuilderStage::Keygen, &original, &original);
+    let config_params = builder.calculate_params(Some(9));
+    let params = gen_srs(K as u32);
+    let vk = keygen_vk(&params, &builder).unwrap();
+    let pk = keygen_pk(&params, vk, &builder).unwrap();
+    let break_points = builder.break_points();
+
+    let builder = shuffle_circuit(CircuitBuilderStage::Prover, &original, &shuffled)
+        .use_params(config_params)
+        .use_break_points(break_points);
+    let proof = gen_proof(&params, &pk, builder);
+    check_proof(&params, pk.get_vk(), &proof, true);
+}
@@ -0,0 +1,362 @@
+#![allow(non_snake_case)]
+
+use super::merkle_tree::{g1_limbs, g2_limbs};
+use super::msp::truncate_challenge;
+use super::pairing::PairingChip;
+use super::{Fp12Chip, Fp2Chip, FpChip, FpPoint, FqPoint};
+use crate::ecc::{EcPoint, EccChip};
+use crate::fields::FieldChip;
+use crate::halo2_proofs::halo2curves::bn256::Fq12;
+use crate::halo2_proofs::halo2curves::bn256::{G1Affine, G2Affine};
+use halo2_base::gates::{GateChip, GateInstructions};
+use halo2_base::poseidon::hasher::PoseidonHasher;
+use halo2_base::utils::BigPrimeField;
+use halo2_base::{AssignedValue, Context};
+
+// Bit length of the batching coefficients used by `BlsBatchChip::new`
+pub const COEFF_BITS: usize = 128;
+
+// Batch verification of k independent BLS signatures (pubkey_i, m_i, signature_i) with a random linear combination.
+// The coefficients r_i are derived by Fiat-Shamir from Poseidon over the CRT limbs of every assigned input, and
+// for keys in G1 the k checks e(g1, signature_i) = e(pubkey_i, H(m_i)) are combined into
+//   prod_i e(r_i * g1, -signature_i) * e(r_i * pubkey_i, H(m_i)) === 1
+// with a single multi Miller loop over 2k pairs and a single final exponentiation, instead of k of each.
+// All scalar multiplications are in G1. A batch with an invalid signature passes with probability about 2^-coeff_bits.
+pub struct BlsBatchChip<'chip, F: BigPrimeField> {
+    pub fp_chip: &'chip FpChip<'chip, F>,
+    pub pairing_chip: &'chip PairingChip<'chip, F>,
+    pub poseidon_chip: &'chip PoseidonHasher<F, 3, 2>,
+    pub coeff_bits: usize,
+}
+
+impl<'chip, F: BigPrimeField> BlsBatchChip<'chip, F> {
+    pub fn new(
+        fp_chip: &'chip FpChip<F>,
+        pairing_chip: &'chip PairingChip<F>,
+        poseidon_chip: &'chip PoseidonHasher<F, 3, 2>,
+    ) -> Self {
+        Self::new_with_coeff_bits(fp_chip, pairing_chip, poseidon_chip, COEFF_BITS)
+    }
+
+    // Same as `new`, with coefficients r_i truncated to `coeff_bits` bits
+    pub fn new_with_coeff_bits(
+        fp_chip: &'chip FpChip<F>,
+        pairing_chip: &'chip PairingChip<F>,
+        poseidon_chip: &'chip PoseidonHasher<F, 3, 2>,
+        coeff_bits: usize,
+    ) -> Self {
+        assert!(coeff_bits != 0, "coefficients must have at least one bit");
+        Self { fp_chip, pairing_chip, poseidon_chip, coeff_bits }
+    }
+
+    // Returns 1 iff every signature_i is a valid signature of H(m_i) under pubkey_i, with keys in G1
+    pub fn bls_batch_verify(
+        &self,
+        ctx: &mut Context<F>,
+        g1: G1Affine,
+        signatures: &[G2Affine],
+        pubkeys: &[G1Affine],
+        msghashes: &[G2Affine],
+    ) -> AssignedValue<F> {
+        let (signatures, pubkeys, hash_ms) = self.load_batch(ctx, signatures, pubkeys, msghashes);
+        self.bls_batch_verify_points(ctx, g1, &signatures, &pubkeys, &hash_ms)
+    }
+
+    // Same as `bls_batch_verify`, but constrains every signature to be valid
+    pub fn assert_bls_batch_verify(
+        &self,
+        ctx: &mut Context<F>,
+        g1: G1Affine,
+        signatures: &[G2Affine],
+        pubkeys: &[G1Affine],
+        msghashes: &[G2Affine],
+    ) {
+        let (signatures, pubkeys, hash_ms) = self.load_batch(ctx, signatures, pubkeys, msghashes);
+        let result = self.batch_pairing(ctx, g1, &signatures, &pubkeys, &hash_ms);
+        let fp12_chip = Fp12Chip::<F>::new(self.fp_chip);
+        let fp12_one = fp12_chip.load_constant(ctx, Fq12::one());
+        fp12_chip.assert_equal(ctx, result, fp12_one);
+    }
+
+    // Batch verification with all of the signatures, pubkeys and H(m_i) already assigned
+    pub fn bls_batch_verify_points(
+        &self,
+        ctx: &mut Context<F>,
+        g1: G1Affine,
+        signatures: &[EcPoint<F, FqPoint<F>>],
+        pubkeys: &[EcPoint<F, FpPoint<F>>],
+        hash_ms: &[EcPoint<F, FqPoint<F>>],
+    ) -> AssignedValue<F> {
+        let result = self.batch_pairing(ctx, g1, signatures, pubkeys, hash_ms);
+        let fp12_chip = Fp12Chip::<F>::new(self.fp_chip);
+        let fp12_one = fp12_chip.load_constant(ctx, Fq12::one());
+        fp12_chip.is_equal(ctx, result, fp12_one)
+    }
+
+    // Returns 1 iff every signature_i is a valid signature of H(m_i) under pubkey_i, with keys in G2:
+    // the k checks e(signature_i, g2) = e(H(m_i), pubkey_i) are combined into
+    //   prod_i e(r_i * signature_i, -g2) * e(r_i * H(m_i), pubkey_i) === 1
+    pub fn bls_batch_verify_2(
+        &self,
+        ctx: &mut Context<F>,
+        g2: G2Affine,
+        signatures: &[G1Affine],
+        pubkeys: &[G2Affine],
+        msghashes: &[G1Affine],
+    ) -> AssignedValue<F> {
+        let (signatures, pubkeys, hash_ms) = self.load_batch_2(ctx, signatures, pubkeys, msghashes);
+        self.bls_batch_verify_2_points(ctx, g2, &signatures, &pubkeys, &hash_ms)
+    }
+
+    // Same as `bls_batch_verify_2`, but constrains every signature to be valid
+    pub fn assert_bls_batch_verify_2(
+        &self,
+        ctx: &mut Context<F>,
+        g2: G2Affine,
+        signatures: &[G1Affine],
+        pubkeys: &[G2Affine],
+        msghashes: &[G1Affine],
+    ) {
+        let (signatures, pubkeys, hash_ms) = self.load_batch_2(ctx, signatures, pubkeys, msghashes);
+        let result = self.batch_pairing_2(ctx, g2, &signatures, &pubkeys, &hash_ms);
+        let fp12_chip = Fp12Chip::<F>::new(self.fp_chip);
+        let fp12_one = fp12_chip.load_constant(ctx, Fq12::one());
+        fp12_chip.assert_equal(ctx, result, fp12_one);
+    }
+
+    // Batch verification with all of the signatures, pubkeys and H(m_i) already assigned, with keys in G2
+    pub fn bls_batch_verify_2_points(
+        &self,
+        ctx: &mut Context<F>,
+        g2: G2Affine,
+        signatures: &[EcPoint<F, FpPoint<F>>],
+        pubkeys: &[EcPoint<F, FqPoint<F>>],
+        hash_ms: &[EcPoint<F, FpPoint<F>>],
+    ) -> AssignedValue<F> {
+        let result = self.batch_pairing_2(ctx, g2, signatures, pubkeys, hash_ms);
+        let fp12_chip = Fp12Chip::<F>::new(self.fp_chip);
+        let fp12_one = fp12_chip.load_constant(ctx, Fq12::one());
+        fp12_chip.is_equal(ctx, result, fp12_one)
+    }
+
+    // r_i = Poseidon([i, seed]) truncated to `coeff_bits` bits, where seed is Poseidon over `transcript`,
+    // see `native::coefficients`
+    pub fn coefficients(
+        &self,
+        ctx: &mut Context<F>,
+        transcript: &[AssignedValue<F>],
+        n: usize,
+    ) -> Vec<AssignedValue<F>> {
+        let gate_chip = GateChip::<F>::default();
+        let seed = self.poseidon_chip.hash_fix_len_array(ctx, &gate_chip, transcript);
+        (0..n)
+            .map(|i| {
+                let i_assigned = ctx.load_constant(F::from(i as u64));
+                let r_i =
+                    self.poseidon_chip.hash_fix_len_array(ctx, &gate_chip, &[i_assigned, seed]);
+                truncate_challenge(self.fp_chip.range(), ctx, r_i, self.coeff_bits)
+            })
+            .collect()
+    }
+
+    fn coeff_max_bits(&self) -> usize {
+        self.coeff_bits.min(F::NUM_BITS as usize)
+    }
+
+    fn load_batch(
+        &self,
+        ctx: &mut Context<F>,
+        signatures: &[G2Affine],
+        pubkeys: &[G1Affine],
+        msghashes: &[G2Affine],
+    ) -> (Vec<EcPoint<F, FqPoint<F>>>, Vec<EcPoint<F, FpPoint<F>>>, Vec<EcPoint<F, FqPoint<F>>>)
+    {
+        let signatures = signatures
+            .iter()
+            .map(|pt| self.pairing_chip.load_private_g2_checked(ctx, *pt))
+            .collect();
+        let pubkeys =
+            pubkeys.iter().map(|pt| self.pairing_chip.load_private_g1(ctx, *pt)).collect();
+        let hash_ms = msghashes
+            .iter()
+            .map(|pt| self.pairing_chip.load_private_g2_checked(ctx, *pt))
+            .collect();
+        (signatures, pubkeys, hash_ms)
+    }
+
+    fn load_batch_2(
+        &self,
+        ctx: &mut Context<F>,
+        signatures: &[G1Affine],
+        pubkeys: &[G2Affine],
+        msghashes: &[G1Affine],
+    ) -> (Vec<EcPoint<F, FpPoint<F>>>, Vec<EcPoint<F, FqPoint<F>>>, Vec<EcPoint<F, FpPoint<F>>>)
+    {
+        let signatures =
+            signatures.iter().map(|pt| self.pairing_chip.load_private_g1(ctx, *pt)).collect();
+        let pubkeys =
+            pubkeys.iter().map(|pt| self.pairing_chip.load_private_g2_checked(ctx, *pt)).collect();
+        let hash_ms =
+            msghashes.iter().map(|pt| self.pairing_chip.load_private_g1(ctx, *pt)).collect();
+        (signatures, pubkeys, hash_ms)
+    }
+
+    // prod_i e(r_i * g1, -signature_i) * e(r_i * pubkey_i, H(m_i)) after the final exponentiation,
+    // with the transcript made of the limbs of all the pubkeys, then all the H(m_i), then all the signatures
+    fn batch_pairing(
+        &self,
+        ctx: &mut Context<F>,
+        g1: G1Affine,
+        signatures: &[EcPoint<F, FqPoint<F>>],
+        pubkeys: &[EcPoint<F, FpPoint<F>>],
+        hash_ms: &[EcPoint<F, FqPoint<F>>],
+    ) -> FqPoint<F> {
+        assert!(
+            signatures.len() == pubkeys.len() && hash_ms.len() == pubkeys.len(),
+            "signatures, pubkeys and messages must be the same length"
+        );
+        assert!(!pubkeys.is_empty(), "pubkeys must not be empty");
+
+        let mut transcript = vec![];
+        for pubkey in pubkeys.iter() {
+            transcript.extend(g1_limbs(self.fp_chip, ctx, pubkey));
+        }
+        for hash_m in hash_ms.iter() {
+            transcript.extend(g2_limbs(self.fp_chip, ctx, hash_m));
+        }
+        for signature in signatures.iter() {
+            transcript.extend(g2_limbs(self.fp_chip, ctx, signature));
+        }
+        let r_is = self.coefficients(ctx, &transcript, pubkeys.len());
+
+        let g1_chip = EccChip::new(self.fp_chip);
+        let fp2_chip = Fp2Chip::<F>::new(self.fp_chip);
+        let g2_chip = EccChip::new(&fp2_chip);
+        let max_bits = self.coeff_max_bits();
+        let mut g1_points = vec![];
+        let mut g2_points = vec![];
+        for ((signature, pubkey), (hash_m, &r_i)) in
+            signatures.iter().zip(pubkeys.iter()).zip(hash_ms.iter().zip(r_is.iter()))
+        {
+            g1_points.push(g1_chip.fixed_base_scalar_mult(ctx, &g1, vec![r_i], max_bits, 4));
+            g2_points.push(g2_chip.negate(ctx, signature.clone()));
+            g1_points.push(g1_chip.scalar_mult::<G1Affine>(
+                ctx,
+                pubkey.clone(),
+                vec![r_i],
+                max_bits,
+                4,
+            ));
+            g2_points.push(hash_m.clone());
+        }
+
+        let multi_paired = self
+            .pairing_chip
+            .multi_miller_loop(ctx, g1_points.iter().zip(g2_points.iter()).collect());
+        let fp12_chip = Fp12Chip::<F>::new(self.fp_chip);
+        fp12_chip.final_exp(ctx, multi_paired)
+    }
+
+    // prod_i e(r_i * signature_i, -g2) * e(r_i * H(m_i), pubkey_i) after the final exponentiation,
+    // with the transcript made of the limbs of all the pubkeys, then all the H(m_i), then all the signatures
+    fn batch_pairing_2(
+        &self,
+        ctx: &mut Context<F>,
+        g2: G2Affine,
+        signatures: &[EcPoint<F, FpPoint<F>>],
+        pubkeys: &[EcPoint<F, FqPoint<F>>],
+        hash_ms: &[EcPoint<F, FpPoint<F>>],
+    ) -> FqPoint<F> {
+        assert!(
+            signatures.len() == pubkeys.len() && hash_ms.len() == pubkeys.len(),
+            "signatures, pubkeys and messages must be the same length"
+        );
+        assert!(!pubkeys.is_empty(), "pubkeys must not be empty");
+
+        let mut transcript = vec![];
+        for pubkey in pubkeys.iter() {
+            transcript.extend(g2_limbs(self.fp_chip, ctx, pubkey));
+        }
+        for hash_m in hash_ms.iter() {
+            transcript.extend(g1_limbs(self.fp_chip, ctx, hash_m));
+        }
+        for signature in signatures.iter() {
+            transcript.extend(g1_limbs(self.fp_chip, ctx, signature));
+        }
+        let r_is = self.coefficients(ctx, &transcript, pubkeys.len());
+
+        let g1_chip = EccChip::new(self.fp_chip);
+        // -g2 is a constant, so its line functions are precomputed and shared by the k signature pairs
+        let neg_g2_lines = self.pairing_chip.load_fixed_g2_lines(ctx, -g2);
+        let max_bits = self.coeff_max_bits();
+        let mut signature_points = vec![];
+        let mut hash_m_points = vec![];
+        for (signature, (hash_m, &r_i)) in signatures.iter().zip(hash_ms.iter().zip(r_is.iter())) {
+            signature_points.push(g1_chip.scalar_mult::<G1Affine>(
+                ctx,
+                signature.clone(),
+                vec![r_i],
+                max_bits,
+                4,
+            ));
+            hash_m_points.push(g1_chip.scalar_mult::<G1Affine>(
+                ctx,
+                hash_m.clone(),
+                vec![r_i],
+                max_bits,
+                4,
+            ));
+        }
+
+        let multi_paired = self.pairing_chip.multi_miller_loop_mixed(
+            ctx,
+            signature_points.iter().map(|pt| (pt, &neg_g2_lines[..])).collect(),
+            hash_m_points.iter().zip(pubkeys.iter()).collect(),
+        );
+        let fp12_chip = Fp12Chip::<F>::new(self.fp_chip);
+        fp12_chip.final_exp(ctx, multi_paired)
+    }
+}
+
+// Native counterparts of the batching coefficients
+pub mod native {
+    use crate::bn254::merkle_tree::native::{g1_limbs, g2_limbs, poseidon_hash};
+    use crate::bn254::msp::native::challenges;
+    use crate::halo2_proofs::halo2curves::bn256::{Fr, G1Affine, G2Affine};
+
+    // Coefficients r_i of `BlsBatchChip::bls_batch_verify`
+    pub fn coefficients(
+        signatures: &[G2Affine],
+        pubkeys: &[G1Affine],
+        msghashes: &[G2Affine],
+        limb_bits: usize,
+        num_limbs: usize,
+        coeff_bits: usize,
+    ) -> Vec<Fr> {
+        let transcript = pubkeys
+            .iter()
+            .flat_map(|pt| g1_limbs(pt, limb_bits, num_limbs))
+            .chain(msghashes.iter().flat_map(|pt| g2_limbs(pt, limb_bits, num_limbs)))
+            .chain(signatures.iter().flat_map(|pt| g2_limbs(pt, limb_bits, num_limbs)))
+            .collect::<Vec<_>>();
+        challenges(poseidon_hash(&transcript), pubkeys.len(), coeff_bits)
+    }
+
+    // Coefficients r_i of `BlsBatchChip::bls_batch_verify_2`
+    pub fn coefficients_2(
+        signatures: &[G1Affine],
+        pubkeys: &[G2Affine],
+        msghashes: &[G1Affine],
+        limb_bits: usize,
+        num_limbs: usize,
+        coeff_bits: usize,
+    ) -> Vec<Fr> {
+        let transcript = pubkeys
+            .iter()
+            .flat_map(|pt| g2_limbs(pt, limb_bits, num_limbs))
+            .chain(msghashes.iter().flat_map(|pt| g1_limbs(pt, limb_bits, num_limbs)))
+            .chain(signatures.iter().flat_map(|pt| g1_limbs(pt, limb_bits, num_limbs)))
+            .collect::<Vec<_>>();
+        challenges(poseidon_hash(&transcript), pubkeys.len(), coeff_bits)
+    }
+}
@@ -1,11 +1,13 @@
 #![allow(non_snake_case)]
 
+use super::hash_to_g1::HashToG1Chip;
 use super::pairing::PairingChip;
-use super::{Fp12Chip, Fp2Chip, FpChip};
-use crate::ecc::EccChip;
+use super::{Fp12Chip, Fp2Chip, FpChip, FpPoint, FqPoint};
+use crate::ecc::{EcPoint, EccChip};
 use crate::fields::FieldChip;
 use crate::halo2_proofs::halo2curves::bn256::Fq12;
 use crate::halo2_proofs::halo2curves::bn256::{G1Affine, G2Affine};
+use halo2_base::gates::GateInstructions;
 use halo2_base::utils::BigPrimeField;
 use halo2_base::{AssignedValue, Context};
 
@@ -23,7 +25,6 @@ impl<'chip, F: BigPrimeField> BlsSignatureChip2<'chip, F> {
     // Verifies that e(signature, g2) = e(H(m), pubkey) by checking e(signature, g2)*e(H(m), -pubkey) === 1
     // where e(,) is optimal Ate pairing
     // G1: {signature, message}, G2: {g2, pubkey}
-    // TODO add support for aggregating signatures over different messages
     pub fn bls_signature_verify(
         &self,
         ctx: &mut Context<F>,
@@ -39,13 +40,77 @@ impl<'chip, F: BigPrimeField> BlsSignatureChip2<'chip, F> {
         assert!(!signatures.is_empty(), "signatures must not be empty");
         assert!(!pubkeys.is_empty(), "pubkeys must not be empty");
 
+        let hash_m_assigned = self.pairing_chip.load_private_g1(ctx, msghash);
+        self.bls_signature_verify_assigned(ctx, g2, signatures, pubkeys, hash_m_assigned)
+    }
+
+    // Same as `bls_signature_verify` but with the message hash already assigned, e.g. computed in circuit by `HashToG1Chip`
+    pub fn bls_signature_verify_assigned(
+        &self,
+        ctx: &mut Context<F>,
+        g2: G2Affine,
+        signatures: &[G1Affine],
+        pubkeys: &[G2Affine],
+        hash_m_assigned: EcPoint<F, FpPoint<F>>,
+    ) -> AssignedValue<F> {
+        let result = self.signature_pairing(ctx, g2, signatures, pubkeys, hash_m_assigned);
+
+        // Check signatures are verified
+        let fp12_chip = Fp12Chip::<F>::new(self.fp_chip);
+        let fp12_one = fp12_chip.load_constant(ctx, Fq12::one());
+        fp12_chip.is_equal(ctx, result, fp12_one)
+    }
+
+    // Same as `bls_signature_verify`, but constrains the signature to be valid instead of returning a boolean
+    pub fn assert_bls_signature_verify(
+        &self,
+        ctx: &mut Context<F>,
+        g2: G2Affine,
+        signatures: &[G1Affine],
+        pubkeys: &[G2Affine],
+        msghash: G1Affine,
+    ) {
+        let hash_m_assigned = self.pairing_chip.load_private_g1(ctx, msghash);
+        self.assert_bls_signature_verify_assigned(ctx, g2, signatures, pubkeys, hash_m_assigned)
+    }
+
+    // Same as `bls_signature_verify_assigned`, but constrains the signature to be valid.
+    // This is cheaper than constraining the returned boolean since it skips the `is_equal` over Fp12.
+    pub fn assert_bls_signature_verify_assigned(
+        &self,
+        ctx: &mut Context<F>,
+        g2: G2Affine,
+        signatures: &[G1Affine],
+        pubkeys: &[G2Affine],
+        hash_m_assigned: EcPoint<F, FpPoint<F>>,
+    ) {
+        let result = self.signature_pairing(ctx, g2, signatures, pubkeys, hash_m_assigned);
+        let fp12_chip = Fp12Chip::<F>::new(self.fp_chip);
+        let fp12_one = fp12_chip.load_constant(ctx, Fq12::one());
+        fp12_chip.assert_equal(ctx, result, fp12_one);
+    }
+
+    // e(signature, g2) * e(H(m), -pubkey) after the final exponentiation, which is 1 iff the signature is valid
+    fn signature_pairing(
+        &self,
+        ctx: &mut Context<F>,
+        g2: G2Affine,
+        signatures: &[G1Affine],
+        pubkeys: &[G2Affine],
+        hash_m_assigned: EcPoint<F, FpPoint<F>>,
+    ) -> FqPoint<F> {
+        assert!(
+            signatures.len() == pubkeys.len(),
+            "signatures and pubkeys must be the same length"
+        );
+        assert!(!signatures.is_empty(), "signatures must not be empty");
+
         let g1_chip = EccChip::new(self.fp_chip);
         let fp2_chip = Fp2Chip::<F>::new(self.fp_chip);
         let g2_chip = EccChip::new(&fp2_chip);
 
-        let g2_assigned = self.pairing_chip.load_private_g2(ctx, g2);
-
-        let hash_m_assigned = self.pairing_chip.load_private_g1(ctx, msghash);
+        // g2 is a constant, so its line functions are precomputed natively
+        let g2_lines = self.pairing_chip.load_fixed_g2_lines(ctx, g2);
 
         let signature_points = signatures
             .iter()
@@ -55,7 +120,7 @@ impl<'chip, F: BigPrimeField> BlsSignatureChip2<'chip, F> {
 
         let pubkey_points = pubkeys
             .iter()
-            .map(|pt| g2_chip.load_private::<G2Affine>(ctx, (pt.x, pt.y)))
+            .map(|pt| self.pairing_chip.load_private_g2_checked(ctx, *pt))
             .collect::<Vec<_>>();
         let pubkey_agg_assigned = g2_chip.sum::<G2Affine>(ctx, pubkey_points);
 
@@ -63,17 +128,148 @@ impl<'chip, F: BigPrimeField> BlsSignatureChip2<'chip, F> {
         let g12_chip = EccChip::new(&fp12_chip);
         let neg_pubkey_assigned_g12 = g12_chip.negate(ctx, &pubkey_agg_assigned);
 
-        let multi_paired = self.pairing_chip.multi_miller_loop(
+        let multi_paired = self.pairing_chip.multi_miller_loop_mixed(
             ctx,
-            vec![
-                (&signature_agg_assigned, &g2_assigned),
-                (&hash_m_assigned, &neg_pubkey_assigned_g12),
-            ],
+            vec![(&signature_agg_assigned, &g2_lines[..])],
+            vec![(&hash_m_assigned, &neg_pubkey_assigned_g12)],
         );
-        let result = fp12_chip.final_exp(ctx, multi_paired);
+        fp12_chip.final_exp(ctx, multi_paired)
+    }
+
+    // Verifies a signature on `msg`, hashing the message to G1 in circuit with Poseidon
+    pub fn bls_signature_verify_msg(
+        &self,
+        ctx: &mut Context<F>,
+        g2: G2Affine,
+        signatures: &[G1Affine],
+        pubkeys: &[G2Affine],
+        msg: &[AssignedValue<F>],
+        hash_to_g1_chip: &HashToG1Chip<F>,
+    ) -> AssignedValue<F> {
+        let hash_m_assigned = hash_to_g1_chip.hash_to_g1(ctx, msg);
+        self.bls_signature_verify_assigned(ctx, g2, signatures, pubkeys, hash_m_assigned)
+    }
+
+    // Aggregate verification over distinct messages: checks e(signature, g2) = prod_i e(H(m_i), pubkey_i)
+    // by checking e(-signature, g2) * prod_i e(H(m_i), pubkey_i) === 1 with one multi Miller loop and one final exponentiation
+    // where signature is the sum of the signatures of each signer on its own message
+    pub fn bls_aggregate_verify(
+        &self,
+        ctx: &mut Context<F>,
+        g2: G2Affine,
+        signature: G1Affine,
+        pubkeys: &[G2Affine],
+        msghashes: &[G1Affine],
+    ) -> AssignedValue<F> {
+        let hash_ms_assigned =
+            msghashes.iter().map(|h| self.pairing_chip.load_private_g1(ctx, *h)).collect();
+        self.bls_aggregate_verify_assigned(ctx, g2, signature, pubkeys, hash_ms_assigned)
+    }
+
+    // Same as `bls_aggregate_verify`, but each H(m_i) is computed in circuit with `HashToG1Chip`
+    pub fn bls_aggregate_verify_msgs(
+        &self,
+        ctx: &mut Context<F>,
+        g2: G2Affine,
+        signature: G1Affine,
+        pubkeys: &[G2Affine],
+        msgs: &[Vec<AssignedValue<F>>],
+        hash_to_g1_chip: &HashToG1Chip<F>,
+    ) -> AssignedValue<F> {
+        let hash_ms_assigned =
+            msgs.iter().map(|msg| hash_to_g1_chip.hash_to_g1(ctx, msg)).collect();
+        self.bls_aggregate_verify_assigned(ctx, g2, signature, pubkeys, hash_ms_assigned)
+    }
+
+    // Aggregate verification against already assigned H(m_i)
+    pub fn bls_aggregate_verify_assigned(
+        &self,
+        ctx: &mut Context<F>,
+        g2: G2Affine,
+        signature: G1Affine,
+        pubkeys: &[G2Affine],
+        hash_ms_assigned: Vec<EcPoint<F, FpPoint<F>>>,
+    ) -> AssignedValue<F> {
+        let g1_chip = EccChip::new(self.fp_chip);
+
+        let signature_assigned = g1_chip.load_private::<G1Affine>(ctx, (signature.x, signature.y));
+        let pubkeys_assigned = pubkeys
+            .iter()
+            .map(|pt| self.pairing_chip.load_private_g2_checked(ctx, *pt))
+            .collect::<Vec<_>>();
+        self.bls_aggregate_verify_points(
+            ctx,
+            g2,
+            signature_assigned,
+            &pubkeys_assigned,
+            &hash_ms_assigned,
+        )
+    }
+
+    // Aggregate verification with all of the signature, pubkeys and H(m_i) already assigned.
+    // Aggregation over different messages is only secure if the messages are distinct,
+    // so this constrains the H(m_i) to be pairwise distinct, which holds iff the messages are (up to hash collisions).
+    pub fn bls_aggregate_verify_points(
+        &self,
+        ctx: &mut Context<F>,
+        g2: G2Affine,
+        signature: EcPoint<F, FpPoint<F>>,
+        pubkeys: &[EcPoint<F, FqPoint<F>>],
+        hash_ms: &[EcPoint<F, FpPoint<F>>],
+    ) -> AssignedValue<F> {
+        let result = self.aggregate_pairing(ctx, g2, signature, pubkeys, hash_ms);
 
         // Check signatures are verified
+        let fp12_chip = Fp12Chip::<F>::new(self.fp_chip);
         let fp12_one = fp12_chip.load_constant(ctx, Fq12::one());
         fp12_chip.is_equal(ctx, result, fp12_one)
     }
+
+    // Same as `bls_aggregate_verify_points`, but constrains the signature to be valid instead of returning a boolean
+    pub fn assert_bls_aggregate_verify_points(
+        &self,
+        ctx: &mut Context<F>,
+        g2: G2Affine,
+        signature: EcPoint<F, FpPoint<F>>,
+        pubkeys: &[EcPoint<F, FqPoint<F>>],
+        hash_ms: &[EcPoint<F, FpPoint<F>>],
+    ) {
+        let result = self.aggregate_pairing(ctx, g2, signature, pubkeys, hash_ms);
+        let fp12_chip = Fp12Chip::<F>::new(self.fp_chip);
+        let fp12_one = fp12_chip.load_constant(ctx, Fq12::one());
+        fp12_chip.assert_equal(ctx, result, fp12_one);
+    }
+
+    // Product of the pairings of `bls_aggregate_verify_points` after the final exponentiation, which is 1 iff the signature is valid
+    fn aggregate_pairing(
+        &self,
+        ctx: &mut Context<F>,
+        g2: G2Affine,
+        signature: EcPoint<F, FpPoint<F>>,
+        pubkeys: &[EcPoint<F, FqPoint<F>>],
+        hash_ms: &[EcPoint<F, FpPoint<F>>],
+    ) -> FqPoint<F> {
+        assert!(hash_ms.len() == pubkeys.len(), "pubkeys and messages must be the same length");
+        assert!(!pubkeys.is_empty(), "pubkeys must not be empty");
+
+        let g1_chip = EccChip::new(self.fp_chip);
+
+        for (i, hash_i) in hash_ms.iter().enumerate() {
+            for hash_j in hash_ms.iter().skip(i + 1) {
+                let is_equal = g1_chip.is_equal(ctx, hash_i.clone(), hash_j.clone());
+                self.fp_chip.gate().assert_is_const(ctx, &is_equal, &F::ZERO);
+            }
+        }
+
+        let g2_lines = self.pairing_chip.load_fixed_g2_lines(ctx, g2);
+        let neg_signature_assigned = g1_chip.negate(ctx, signature);
+
+        let multi_paired = self.pairing_chip.multi_miller_loop_mixed(
+            ctx,
+            vec![(&neg_signature_assigned, &g2_lines[..])],
+            hash_ms.iter().zip(pubkeys.iter()).collect(),
+        );
+        let fp12_chip = Fp12Chip::<F>::new(self.fp_chip);
+        fp12_chip.final_exp(ctx, multi_paired)
+    }
 }
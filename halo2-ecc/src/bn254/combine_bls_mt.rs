@@ -14,15 +14,21 @@
 
 use halo2_base::utils::BigPrimeField;
 use halo2_base::gates::GateInstructions;
+use halo2_base::safe_types::FixLenBytes;
 // use super::FpChip;
 use halo2_base::{ AssignedValue, Context};
 
 use crate::bn254::bls_signature::BlsSignatureChip;
+use crate::bn254::bls_signature_inverse::BlsSignatureChip2;
+use crate::bn254::hash_to_curve::{HashToCurveChip, DST_G2};
+use crate::bn254::hash_to_g1::{HashToG1Chip, DST_G1};
 use crate::bn254::merkle_tree::MerkleTreeChip;
+use crate::ecc::{EcPoint, EccChip};
 use crate::halo2curves::bn256::G1Affine;
-use super::MerkleInfo;
+use super::instances::PublicStatement;
+use super::merkle_tree::{g1_limbs, g2_limbs, load_native};
+use super::{Fp2Chip, FpPoint, FqPoint, MerkleInfo};
 use halo2_base::halo2_proofs::halo2curves::bn256::G2Affine;
-use crate::halo2curves::bn256::Fr;
 // pub struct MerkleTreeChip<'chip, F: BigPrimeField, const T: usize, const RATE: usize> {
 //     pub fp_chip: &'chip FpChip<'chip, F>,
 //     pub poseidon_chip: &'chip PoseidonHasher<F, T, RATE>,
@@ -31,6 +37,8 @@ use crate::halo2curves::bn256::Fr;
 pub struct CombineBlsMtChip<'chip, F: BigPrimeField, const T: usize, const RATE: usize> {
     pub bls_chip: &'chip BlsSignatureChip<'chip, F>,
     pub merkle_chip: MerkleTreeChip<'chip, F, T, RATE>,
+    // hash to G2 under DST_G2, its SHA-256 promises must be bound to the SHA-256 component by the caller
+    pub hash_to_curve_chip: HashToCurveChip<'chip, F>,
 }
 
 
@@ -38,9 +46,39 @@ pub struct CombineBlsMtChip<'chip, F: BigPrimeField, const T: usize, const RATE:
 impl<'chip, F: BigPrimeField, const T: usize, const RATE: usize> CombineBlsMtChip<'chip, F, T, RATE> {
     
         pub fn new(bls_chip: &'chip BlsSignatureChip<'chip, F>, merkle_chip: MerkleTreeChip<'chip, F, T, RATE>) -> Self {
-            Self { bls_chip, merkle_chip }
+            let hash_to_curve_chip = HashToCurveChip::new(bls_chip.fp_chip, DST_G2);
+            Self { bls_chip, merkle_chip, hash_to_curve_chip }
         }
-    
+
+        // The public digest of the message is Poseidon([message, 0])
+        fn message_digest(&self, ctx: &mut Context<F>, message: AssignedValue<F>) -> AssignedValue<F> {
+            let zero = ctx.load_constant(F::ZERO);
+            self.merkle_chip.poseidon_chip.hash_fix_len_array(ctx, &self.merkle_chip.gate_chip, &[message,zero])
+        }
+
+        // Checks that every assigned leaf hashes up to `root` along its authentication path
+        fn verify_leaves(
+            &self,
+            ctx: &mut Context<F>,
+            root: AssignedValue<F>,
+            leaves: &[AssignedValue<F>],
+            merkle_infos: &[MerkleInfo<F>],
+        ) -> AssignedValue<F> {
+            let mut result = ctx.load_constant(F::ONE);
+            for (leaf, merkle_info) in leaves.iter().zip(merkle_infos.iter()) {
+                let computed_root = self.merkle_chip.merkle_root_from_leaf(ctx, *leaf, &merkle_info.path, &merkle_info.index);
+                let _result = self.merkle_chip.gate_chip.is_equal(ctx, computed_root, root);
+                result = self.merkle_chip.gate_chip.and(ctx, result, _result);
+            }
+            result
+        }
+}
+
+// The hash to G1 is defined over Poseidon<F, 3, 2>, so verification needs the Merkle tree over the same Poseidon
+impl<'chip, F: BigPrimeField> CombineBlsMtChip<'chip, F, 3, 2> {
+        // Verifies the aggregate signature of `pubkeys` (in G1) and that every pubkey is a leaf of the tree with `root`.
+        // Each pubkey is loaded once and the same assigned point is both hashed into its leaf
+        // (see `MerkleTreeChip::g1_leaf`) and aggregated into the pairing, so `merkle_infos[i].leaf` is not used.
         pub fn combine_bls_mt_verify(
             &self,
             ctx: &mut Context<F>,
@@ -51,45 +89,138 @@ impl<'chip, F: BigPrimeField, const T: usize, const RATE: usize> CombineBlsMtChi
             pubkeys: &[G1Affine],
             message: F,
         ) -> AssignedValue<F> {
-            // let x = F::from(0);
+            self.combine_bls_mt_verify_public(ctx, root, merkle_infos, g1, signatures, pubkeys, message).result
+        }
+
+        // Same as `combine_bls_mt_verify`, returning the result with the assigned root, the message digest
+        // Poseidon([message, 0]) and the limbs of the aggregate pubkey as a public statement
+        pub fn combine_bls_mt_verify_public(
+            &self,
+            ctx: &mut Context<F>,
+            root: F,
+            merkle_infos: &[MerkleInfo<F>],
+            g1: G1Affine,
+            signatures: &[G2Affine],
+            pubkeys: &[G1Affine],
+            message: F,
+        ) -> PublicStatement<F> {
             assert!(merkle_infos.len() == pubkeys.len(), "merkle_info and pubkeys must be the same length");
+            assert!(signatures.len() == pubkeys.len(), "signatures and pubkeys must be the same length");
+            assert!(!pubkeys.is_empty(), "pubkeys must not be empty");
 
-            // let msg_to_f = F::from_bytes_le(&message.as_bytes());
-            // let assigned_msg = ctx.load_witness(msg_to_f);
-            // let zero = ctx.load_witness(F::ZERO);
-            // let msghash = self.merkle_chip.poseidon_chip.hash_fix_len_array(ctx, &self.merkle_chip.gate_chip, &[assigned_msg,zero]);
-            // let msghash_byte:[u8;32] = msghash.value().to_bytes_le().to_vec().try_into().expect("Invalid Fr bytes");
-            // let msghash = G2Affine::from(G2Affine::generator() * Fr::from_bytes(&msghash_byte).unwrap());
-            // let msghash = G2Affine::from(G2Affine::generator() * Fr::from(123456));
-
-            let assigned_msg = ctx.load_witness(message);
-            let zero = ctx.load_witness(F::ZERO);
-            let msghash = self.merkle_chip.poseidon_chip.hash_fix_len_array(ctx, &self.merkle_chip.gate_chip, &[assigned_msg,zero]);
-            let msghash_byte:[u8;32] = msghash.value().to_bytes_le().to_vec().try_into().expect("Invalid Fr bytes");
-            let msghash = G2Affine::from(G2Affine::generator() * Fr::from_bytes(&msghash_byte).unwrap());
-            // Verify BLS signature
-            let result_bls = self.bls_chip.bls_signature_verify(ctx, g1, signatures, pubkeys, msghash);
+            let fp_chip = self.bls_chip.fp_chip;
+            let pairing_chip = self.bls_chip.pairing_chip;
+            let g1_chip = EccChip::new(fp_chip);
+            let fp2_chip = Fp2Chip::<F>::new(fp_chip);
+            let g2_chip = EccChip::new(&fp2_chip);
+
+            // H(m) and the public digest are both computed in circuit from the same assigned message
+            let message = ctx.load_witness(message);
+            let msg_digest = self.message_digest(ctx, message);
+            let hash_m = self.message_hash(ctx, message);
+
+            let pubkeys_assigned = pubkeys.iter().map(|pk| pairing_chip.load_private_g1(ctx, *pk)).collect::<Vec<_>>();
+            let leaves = pubkeys_assigned.iter().map(|pk| self.merkle_chip.g1_leaf(ctx, fp_chip, pk)).collect::<Vec<_>>();
             // Verify Merkle tree
-            let result_mt = self.merkle_chip.merkle_tree_verify_batch(ctx, root, merkle_infos);
-            
+            let root = ctx.load_witness(root);
+            let result_mt = self.verify_leaves(ctx, root, &leaves, merkle_infos);
+
+            // Verify BLS signature
+            let signatures_assigned = signatures.iter().map(|sig| pairing_chip.load_private_g2_checked(ctx, *sig)).collect::<Vec<_>>();
+            let signature_agg = g2_chip.sum::<G2Affine>(ctx, signatures_assigned);
+            let pubkey_agg = g1_chip.sum::<G1Affine>(ctx, pubkeys_assigned);
+            let agg_key = g1_limbs(fp_chip, ctx, &pubkey_agg);
+            let result_bls = self.bls_chip.bls_aggregate_verify_points(ctx, g1, signature_agg, &[pubkey_agg], &[hash_m]);
+
             // Combine the results
+            let result = self.merkle_chip.gate_chip.and(ctx, result_bls, result_mt);
+            PublicStatement { result, root: Some(root), msg_digest: Some(msg_digest), agg_key }
+        }
+
+        // Same as `combine_bls_mt_verify` with pubkeys in G2 and signatures in G1, leaves are `MerkleTreeChip::g2_leaf`
+        pub fn combine_bls_mt_verify_2(
+            &self,
+            ctx: &mut Context<F>,
+            root: F,
+            merkle_infos: &[MerkleInfo<F>],
+            g2: G2Affine,
+            signatures: &[G1Affine],
+            pubkeys: &[G2Affine],
+            message: F,
+        ) -> AssignedValue<F> {
+            self.combine_bls_mt_verify_2_public(ctx, root, merkle_infos, g2, signatures, pubkeys, message).result
+        }
+
+        // Same as `combine_bls_mt_verify_2`, returning the public statement as in `combine_bls_mt_verify_public`
+        pub fn combine_bls_mt_verify_2_public(
+            &self,
+            ctx: &mut Context<F>,
+            root: F,
+            merkle_infos: &[MerkleInfo<F>],
+            g2: G2Affine,
+            signatures: &[G1Affine],
+            pubkeys: &[G2Affine],
+            message: F,
+        ) -> PublicStatement<F> {
+            assert!(merkle_infos.len() == pubkeys.len(), "merkle_info and pubkeys must be the same length");
+            assert!(signatures.len() == pubkeys.len(), "signatures and pubkeys must be the same length");
+            assert!(!pubkeys.is_empty(), "pubkeys must not be empty");
 
-            let mut result = self.merkle_chip.gate_chip.and(ctx, result_bls, result_mt);
+            let fp_chip = self.bls_chip.fp_chip;
+            let pairing_chip = self.bls_chip.pairing_chip;
+            let bls_chip_2 = BlsSignatureChip2::new(fp_chip, pairing_chip);
+            let g1_chip = EccChip::new(fp_chip);
+            let fp2_chip = Fp2Chip::<F>::new(fp_chip);
+            let g2_chip = EccChip::new(&fp2_chip);
 
+            let message = ctx.load_witness(message);
+            let msg_digest = self.message_digest(ctx, message);
+            let hash_m = self.message_hash_2(ctx, message);
 
+            let pubkeys_assigned = pubkeys.iter().map(|pk| pairing_chip.load_private_g2_checked(ctx, *pk)).collect::<Vec<_>>();
+            let leaves = pubkeys_assigned.iter().map(|pk| self.merkle_chip.g2_leaf(ctx, fp_chip, pk)).collect::<Vec<_>>();
+            let root = ctx.load_witness(root);
+            let result_mt = self.verify_leaves(ctx, root, &leaves, merkle_infos);
 
-            // 要求pubkeys的x和merkle_info的leaf相等
-            for i in 0..pubkeys.len() {
-                let leaf = merkle_infos[i].leaf;
-                let pk_x = F::from_bytes_le(&pubkeys[i].x.to_bytes());
-                
-                let leaf = ctx.load_witness(leaf);
-                let pk_x = ctx.load_witness(pk_x);
+            let signatures_assigned = signatures.iter().map(|sig| pairing_chip.load_private_g1(ctx, *sig)).collect::<Vec<_>>();
+            let signature_agg = g1_chip.sum::<G1Affine>(ctx, signatures_assigned);
+            let pubkey_agg = g2_chip.sum::<G2Affine>(ctx, pubkeys_assigned);
+            let agg_key = g2_limbs(fp_chip, ctx, &pubkey_agg);
+            let result_bls = bls_chip_2.bls_aggregate_verify_points(ctx, g2, signature_agg, &[pubkey_agg], &[hash_m]);
 
-                let _result = self.merkle_chip.gate_chip.is_equal(ctx,pk_x,leaf);
-                result = self.merkle_chip.gate_chip.and(ctx, result, _result);
-            }
-            result
+            let result = self.merkle_chip.gate_chip.and(ctx, result_bls, result_mt);
+            PublicStatement { result, root: Some(root), msg_digest: Some(msg_digest), agg_key }
         }
 
+        // H(m) for signatures in G2: hash to G2 under DST_G2 of the 32 byte big-endian encoding of the message,
+        // see `native::message_hash`. The message is loaded into Fp below r so that its encoding is unique.
+        pub fn message_hash(&self, ctx: &mut Context<F>, message: AssignedValue<F>) -> EcPoint<F, FqPoint<F>> {
+            let message_fp = load_native(self.bls_chip.fp_chip, ctx, message);
+            let bytes = self.hash_to_curve_chip.fp_to_bytes_be(ctx, message_fp);
+            let msg = FixLenBytes::<F, 32>::new(bytes.try_into().unwrap());
+            self.hash_to_curve_chip.hash_to_g2_fix_len(ctx, &msg)
+        }
+
+        // H(m) for signatures in G1: Poseidon hash to G1 of the message under DST_G1, see `native::message_hash_2`
+        pub fn message_hash_2(&self, ctx: &mut Context<F>, message: AssignedValue<F>) -> EcPoint<F, FpPoint<F>> {
+            let hash_to_g1_chip = HashToG1Chip::new(self.bls_chip.fp_chip, self.merkle_chip.poseidon_chip, DST_G1);
+            hash_to_g1_chip.hash_to_g1(ctx, &[message])
+        }
+}
+
+// Native counterparts of the message hashes, for signers
+pub mod native {
+    use crate::bn254::hash_to_curve::{native::{fp_to_bytes_be, hash_to_g2}, DST_G2};
+    use crate::bn254::hash_to_g1::{native::hash_to_g1, DST_G1};
+    use crate::halo2_proofs::halo2curves::bn256::{Fq, Fr, G1Affine, G2Affine};
+    use halo2_base::utils::{biguint_to_fe, fe_to_biguint};
+
+    pub fn message_hash(message: Fr) -> G2Affine {
+        let message = biguint_to_fe::<Fq>(&fe_to_biguint(&message));
+        hash_to_g2(&fp_to_bytes_be(&message), DST_G2)
+    }
+
+    pub fn message_hash_2(message: Fr) -> G1Affine {
+        hash_to_g1(&[message], DST_G1)
+    }
 }
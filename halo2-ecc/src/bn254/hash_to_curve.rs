@@ -0,0 +1,682 @@
+#![allow(non_snake_case)]
+// Hash to curve for BN254 G2 following RFC 9380:
+// expand_message_xmd with SHA-256, the Shallue-van de Woestijne (SVDW) map on the twist E'(Fp2),
+// and cofactor clearing with the psi endomorphism.
+//
+// SHA-256 is not computed in this circuit: every digest is witnessed and recorded as a `Sha256Promise`,
+// to be bound to the SHA-256 component circuit of the zkevm hashes crate by the app circuit, see `Sha256Promise`.
+use std::cell::RefCell;
+
+use super::pairing::{twisted_frobenius, BN_X};
+use super::{Fp2Chip, FpChip, FpPoint, Fq, FqPoint};
+use crate::bigint::{CRTInteger, OverflowInteger};
+use crate::ecc::{EcPoint, EccChip};
+use crate::ff::Field;
+use crate::fields::{vector::FieldVector, FieldChip, Selectable};
+use crate::halo2_proofs::halo2curves::bn256::{Fq2, G2Affine, FROBENIUS_COEFF_FQ12_C1};
+use halo2_base::gates::{GateChip, GateInstructions};
+use halo2_base::safe_types::{
+    FixLenBytes, FixLenBytesVec, SafeByte, SafeTypeChip, VarLenBytes, VarLenBytesVec,
+};
+use halo2_base::utils::{
+    biguint_to_fe, bit_length, decompose_biguint, fe_to_biguint, modulus, BigPrimeField,
+    ScalarField,
+};
+use halo2_base::{
+    AssignedValue, Context,
+    QuantumCell::{Constant, Existing},
+};
+use itertools::Itertools;
+use num_bigint::BigUint;
+use sha2::{Digest, Sha256};
+
+// Default domain separation tag for BLS signatures with signatures in G2
+pub const DST_G2: &[u8] = b"BLS_SIG_BN254G2_XMD:SHA-256_SVDW_RO_NUL_";
+
+// L = ceil((ceil(log2(p)) + k) / 8) = ceil((254 + 128) / 8) with security parameter k = 128
+pub const HASH_TO_FIELD_L: usize = 48;
+
+// hash_to_field for Fp2 with count = 2
+pub const HASH_TO_G2_LEN_IN_BYTES: usize = 2 * 2 * HASH_TO_FIELD_L;
+
+// Number of bytes of a SHA-256 digest
+pub const SHA256_DIGEST_LEN: usize = 32;
+
+// Input of a SHA-256 call of `HashToCurveChip`
+#[derive(Clone, Debug)]
+pub enum Sha256Input<F: BigPrimeField> {
+    Fix(FixLenBytesVec<F>),
+    Var(VarLenBytesVec<F>),
+}
+
+// A SHA-256 digest witnessed by `HashToCurveChip`. The digest is not constrained by `HashToCurveChip`:
+// the app circuit must pass every promise of `HashToCurveChip::sha256_promises` to
+// `Sha256PromiseChip::promise_fix_len` / `promise_var_len` of the zkevm hashes crate and expose
+// `Sha256PromiseChip::promise_commit`, which the verifier checks against the commit of the SHA-256 component circuit.
+#[derive(Clone, Debug)]
+pub struct Sha256Promise<F: BigPrimeField> {
+    pub input: Sha256Input<F>,
+    pub digest: FixLenBytes<F, SHA256_DIGEST_LEN>,
+}
+
+// Constants of the SVDW map for y^2 = x^3 + b on the twist, see RFC 9380 Section 6.6.1
+#[derive(Clone, Copy, Debug)]
+pub struct SvdwConstants {
+    pub b: Fq2,
+    pub z: Fq2,
+    pub c1: Fq2,
+    pub c2: Fq2,
+    pub c3: Fq2,
+    pub c4: Fq2,
+    // a fixed non-square, used to prove non-squareness in circuit
+    pub non_residue: Fq2,
+}
+
+impl SvdwConstants {
+    pub fn new() -> Self {
+        let fq2 = |c0: u64, c1: u64| Fq2 { c0: Fq::from(c0), c1: Fq::from(c1) };
+        // xi = 9 + u is neither a square nor a cube in Fp2, and b = 3 / xi
+        let non_residue = fq2(9, 1);
+        let b = fq2(3, 0) * non_residue.invert().unwrap();
+        // Z = 1 is the output of find_z_svdw
+        let z = Fq2::one();
+        let gz = z.square() * z + b;
+        let three_z2 = fq2(3, 0) * z.square();
+        let c1 = gz;
+        let c2 = -(z * fq2(2, 0).invert().unwrap());
+        let mut c3 = (-(gz * three_z2)).sqrt().unwrap();
+        if native::sgn0(&c3) {
+            c3 = -c3;
+        }
+        let c4 = -(fq2(4, 0) * gz) * three_z2.invert().unwrap();
+        Self { b, z, c1, c2, c3, c4, non_residue }
+    }
+}
+
+impl Default for SvdwConstants {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Constants for psi(x, y) = (conj(x) * xi^{(p-1)/3}, conj(y) * xi^{(p-1)/2}), the untwist-Frobenius-twist endomorphism
+fn psi_coeffs() -> (Fq2, Fq2) {
+    let c = FROBENIUS_COEFF_FQ12_C1[1];
+    (c.square(), c.square() * c)
+}
+
+// To avoid issues with mutably borrowing twice (not allowed in Rust), we only store fp_chip and construct fp2_chip and g2_chip in scope when needed for temporary mutable borrows
+pub struct HashToCurveChip<'chip, F: BigPrimeField> {
+    pub fp_chip: &'chip FpChip<'chip, F>,
+    pub dst: Vec<u8>,
+    pub constants: SvdwConstants,
+    sha256_promises: RefCell<Vec<Sha256Promise<F>>>,
+}
+
+impl<'chip, F: BigPrimeField> HashToCurveChip<'chip, F> {
+    pub fn new(fp_chip: &'chip FpChip<F>, dst: &[u8]) -> Self {
+        assert!(dst.len() <= 255, "domain separation tag must be at most 255 bytes");
+        Self {
+            fp_chip,
+            dst: dst.to_vec(),
+            constants: SvdwConstants::new(),
+            sha256_promises: RefCell::new(vec![]),
+        }
+    }
+
+    pub fn gate(&self) -> &GateChip<F> {
+        self.fp_chip.gate()
+    }
+
+    // The SHA-256 calls made so far, in order
+    pub fn sha256_promises(&self) -> Vec<Sha256Promise<F>> {
+        self.sha256_promises.borrow().clone()
+    }
+
+    // Witnesses the SHA-256 digest of `input` and records it as a promise
+    fn sha256(
+        &self,
+        ctx: &mut Context<F>,
+        input: Sha256Input<F>,
+    ) -> FixLenBytes<F, SHA256_DIGEST_LEN> {
+        let (bytes, len) = match &input {
+            Sha256Input::Fix(bytes) => (bytes.bytes(), bytes.len()),
+            Sha256Input::Var(bytes) => (bytes.bytes(), bytes.len().value().get_lower_32() as usize),
+        };
+        let bytes =
+            bytes[..len].iter().map(|b| b.as_ref().value().get_lower_32() as u8).collect_vec();
+        let digest = Sha256::digest(bytes)
+            .iter()
+            .map(|b| ctx.load_witness(F::from(*b as u64)))
+            .collect_vec();
+        let digest = SafeTypeChip::new(self.fp_chip.range())
+            .raw_to_fix_len_bytes(ctx, digest.try_into().unwrap());
+        self.sha256_promises.borrow_mut().push(Sha256Promise { input, digest: digest.clone() });
+        digest
+    }
+
+    // Bytes following msg in msg_prime: I2OSP(len_in_bytes, 2) || I2OSP(0, 1) || DST_prime
+    fn msg_suffix(&self, len_in_bytes: usize) -> Vec<u8> {
+        [
+            &(len_in_bytes as u16).to_be_bytes()[..],
+            &[0u8][..],
+            &self.dst[..],
+            &[self.dst.len() as u8][..],
+        ]
+        .concat()
+    }
+
+    fn load_bytes(ctx: &mut Context<F>, bytes: &[u8]) -> Vec<SafeByte<F>> {
+        bytes
+            .iter()
+            .map(|b| SafeTypeChip::unsafe_to_byte(ctx.load_constant(F::from(*b as u64))))
+            .collect()
+    }
+
+    // strxor(a, b), bit by bit
+    fn xor_bytes(
+        &self,
+        ctx: &mut Context<F>,
+        a: &[SafeByte<F>],
+        b: &[SafeByte<F>],
+    ) -> Vec<SafeByte<F>> {
+        let gate = self.gate();
+        a.iter()
+            .zip_eq(b.iter())
+            .map(|(a, b)| {
+                let a_bits = gate.num_to_bits(ctx, *a.as_ref(), 8);
+                let b_bits = gate.num_to_bits(ctx, *b.as_ref(), 8);
+                let bits =
+                    a_bits.into_iter().zip(b_bits).map(|(a, b)| gate.xor(ctx, a, b)).collect_vec();
+                SafeTypeChip::unsafe_to_byte(gate.bits_to_num(ctx, &bits))
+            })
+            .collect()
+    }
+
+    // b_0 = H(Z_pad || msg || I2OSP(len_in_bytes, 2) || I2OSP(0, 1) || DST_prime) for fixed length msg
+    fn b_0_fix_len(
+        &self,
+        ctx: &mut Context<F>,
+        msg: &[SafeByte<F>],
+        len_in_bytes: usize,
+    ) -> FixLenBytes<F, SHA256_DIGEST_LEN> {
+        let z_pad = Self::load_bytes(ctx, &[0u8; 64]);
+        let suffix = Self::load_bytes(ctx, &self.msg_suffix(len_in_bytes));
+        let bytes = [&z_pad[..], msg, &suffix[..]].concat();
+        let len = bytes.len();
+        self.sha256(ctx, Sha256Input::Fix(FixLenBytesVec::new(bytes, len)))
+    }
+
+    // Same as `b_0_fix_len` for `msg[..len]`, assuming `msg` is 0 padded
+    fn b_0_var_len(
+        &self,
+        ctx: &mut Context<F>,
+        msg: &[SafeByte<F>],
+        len: AssignedValue<F>,
+        len_in_bytes: usize,
+    ) -> FixLenBytes<F, SHA256_DIGEST_LEN> {
+        let gate = self.gate();
+        let suffix = self.msg_suffix(len_in_bytes);
+        let max_len = msg.len();
+        let total_max_len = max_len + suffix.len();
+
+        // Barrel shift the suffix to the right by `len` so it lands right after `msg[..len]`
+        let mut tail =
+            Self::load_bytes(ctx, &suffix).into_iter().map(|b| *b.as_ref()).collect_vec();
+        tail.resize(total_max_len, ctx.load_zero());
+        let shift_bits = gate.num_to_bits(ctx, len, bit_length(max_len as u64));
+        for (i, shift_bit) in shift_bits.into_iter().enumerate() {
+            tail = (0..total_max_len)
+                .map(|j| {
+                    let shifted = if j >= (1 << i) {
+                        Existing(tail[j - (1 << i)])
+                    } else {
+                        Constant(F::ZERO)
+                    };
+                    gate.select(ctx, shifted, tail[j], shift_bit)
+                })
+                .collect_vec();
+        }
+        // `msg` is 0 from `len` on and the shifted suffix is 0 before `len`, so every sum is a byte
+        let bytes = tail
+            .into_iter()
+            .enumerate()
+            .map(|(j, byte)| if j < max_len { gate.add(ctx, *msg[j].as_ref(), byte) } else { byte })
+            .map(SafeTypeChip::unsafe_to_byte)
+            .collect_vec();
+
+        let z_pad = Self::load_bytes(ctx, &[0u8; 64]);
+        let bytes = [z_pad, bytes].concat();
+        let total_len = gate.add(ctx, len, Constant(F::from((64 + suffix.len()) as u64)));
+        let total_max_len = bytes.len();
+        self.sha256(ctx, Sha256Input::Var(VarLenBytesVec::new(bytes, total_len, total_max_len)))
+    }
+
+    // b_1 || ... || b_ell truncated to `len_in_bytes`, given b_0
+    fn expand_from_b_0(
+        &self,
+        ctx: &mut Context<F>,
+        b_0: FixLenBytes<F, SHA256_DIGEST_LEN>,
+        len_in_bytes: usize,
+    ) -> Vec<SafeByte<F>> {
+        let ell = (len_in_bytes + 31) / 32;
+        assert!(ell <= 255, "len_in_bytes is too large");
+        let dst_prime = [&self.dst[..], &[self.dst.len() as u8][..]].concat();
+
+        // b_i = H(strxor(b_0, b_{i-1}) || I2OSP(i, 1) || DST_prime), with b_1 = H(b_0 || I2OSP(1, 1) || DST_prime)
+        let mut uniform_bytes = Vec::with_capacity(32 * ell);
+        let mut b_prev: Option<FixLenBytes<F, SHA256_DIGEST_LEN>> = None;
+        for i in 1..=ell {
+            let mut bytes = match &b_prev {
+                None => b_0.bytes().to_vec(),
+                Some(b_prev) => self.xor_bytes(ctx, b_0.bytes(), b_prev.bytes()),
+            };
+            bytes.extend(Self::load_bytes(ctx, &[&[i as u8][..], &dst_prime[..]].concat()));
+            let len = bytes.len();
+            let b_i = self.sha256(ctx, Sha256Input::Fix(FixLenBytesVec::new(bytes, len)));
+            uniform_bytes.extend_from_slice(b_i.bytes());
+            b_prev = Some(b_i);
+        }
+        uniform_bytes.truncate(len_in_bytes);
+        uniform_bytes
+    }
+
+    // expand_message_xmd(msg, DST, len_in_bytes) with H = SHA-256
+    pub fn expand_message_xmd_fix_len<const LEN: usize>(
+        &self,
+        ctx: &mut Context<F>,
+        msg: &FixLenBytes<F, LEN>,
+        len_in_bytes: usize,
+    ) -> Vec<SafeByte<F>> {
+        let b_0 = self.b_0_fix_len(ctx, msg.bytes(), len_in_bytes);
+        self.expand_from_b_0(ctx, b_0, len_in_bytes)
+    }
+
+    pub fn expand_message_xmd_var_len<const MAX_LEN: usize>(
+        &self,
+        ctx: &mut Context<F>,
+        msg: &VarLenBytes<F, MAX_LEN>,
+        len_in_bytes: usize,
+    ) -> Vec<SafeByte<F>> {
+        let msg = msg.ensure_0_padding(ctx, self.gate());
+        let b_0 = self.b_0_var_len(ctx, msg.bytes(), *msg.len(), len_in_bytes);
+        self.expand_from_b_0(ctx, b_0, len_in_bytes)
+    }
+
+    // OS2IP(bytes) mod p
+    // The bytes are combined with the limbs of 256^i mod p as constants, then reduced with a single carry_mod
+    pub fn bytes_to_fp(&self, ctx: &mut Context<F>, bytes: &[SafeByte<F>]) -> FpPoint<F> {
+        let fp_chip = self.fp_chip;
+        let gate = self.gate();
+        let p = modulus::<Fq>();
+        let native_modulus = modulus::<F>();
+        let coeffs = (0..bytes.len())
+            .map(|i| (BigUint::from(1u64) << (8 * (bytes.len() - 1 - i))) % &p)
+            .collect_vec();
+        let coeff_limbs = coeffs
+            .iter()
+            .map(|c| decompose_biguint::<F>(c, fp_chip.num_limbs, fp_chip.limb_bits))
+            .collect_vec();
+
+        let limbs = (0..fp_chip.num_limbs)
+            .map(|j| {
+                gate.inner_product(
+                    ctx,
+                    bytes.iter().map(|b| *b.as_ref()),
+                    coeff_limbs.iter().map(|c| Constant(c[j])),
+                )
+            })
+            .collect_vec();
+        let native = gate.inner_product(
+            ctx,
+            bytes.iter().map(|b| *b.as_ref()),
+            coeffs.iter().map(|c| Constant(biguint_to_fe(&(c % &native_modulus)))),
+        );
+        let value = bytes
+            .iter()
+            .zip(coeffs.iter())
+            .fold(BigUint::from(0u64), |acc, (b, c)| acc + fe_to_biguint(b.as_ref().value()) * c);
+        // each limb is a sum of bytes.len() terms, each less than 2^{8 + limb_bits}
+        let max_limb_bits = fp_chip.limb_bits + 8 + bit_length(bytes.len() as u64);
+        let crt = CRTInteger::new(OverflowInteger::new(limbs, max_limb_bits), native, value.into());
+        fp_chip.carry_mod(ctx, crt)
+    }
+
+    // I2OSP(a, 32), the canonical 32 byte big-endian encoding of `a`, constraining `a` to be reduced
+    pub fn fp_to_bytes_be(&self, ctx: &mut Context<F>, a: FpPoint<F>) -> Vec<SafeByte<F>> {
+        let fp_chip = self.fp_chip;
+        let gate = self.gate();
+        fp_chip.enforce_less_than_p(ctx, a.clone());
+        // limbs are range checked to limb_bits, and bits above 256 are zero since a < p
+        let bits = a
+            .limbs()
+            .iter()
+            .flat_map(|limb| gate.num_to_bits(ctx, *limb, fp_chip.limb_bits))
+            .take(256)
+            .collect_vec();
+        bits.chunks(8)
+            .rev()
+            .map(|byte_bits| SafeTypeChip::unsafe_to_byte(gate.bits_to_num(ctx, byte_bits)))
+            .collect()
+    }
+
+    // hash_to_field for Fp2 with count = 2 on the uniform bytes output by expand_message_xmd
+    pub fn hash_to_field(
+        &self,
+        ctx: &mut Context<F>,
+        uniform_bytes: &[SafeByte<F>],
+    ) -> [FqPoint<F>; 2] {
+        assert_eq!(uniform_bytes.len(), HASH_TO_G2_LEN_IN_BYTES);
+        let mut elements = uniform_bytes
+            .chunks(HASH_TO_FIELD_L)
+            .map(|chunk| self.bytes_to_fp(ctx, chunk))
+            .tuples()
+            .map(|(c0, c1)| FieldVector(vec![c0, c1]));
+        [elements.next().unwrap(), elements.next().unwrap()]
+    }
+
+    // sgn0(a) = sgn0(a.c0) OR (a.c0 == 0 AND sgn0(a.c1)), constraining a.c0 and a.c1 to be reduced
+    pub fn sgn0(&self, ctx: &mut Context<F>, a: &FqPoint<F>) -> AssignedValue<F> {
+        let gate = self.gate();
+        let c0_is_even = self.fp_chip.is_even(ctx, a[0].clone());
+        let c0_is_zero = self.fp_chip.is_zero(ctx, a[0].clone());
+        let c1_is_even = self.fp_chip.is_even(ctx, a[1].clone());
+        let c0_sign = gate.not(ctx, c0_is_even);
+        let c1_sign = gate.not(ctx, c1_is_even);
+        // c0_sign and c0_is_zero are never both 1
+        gate.mul_add(ctx, c0_is_zero, c1_sign, c0_sign)
+    }
+
+    // Returns 1 iff `a` is a square in Fp2.
+    // Witnesses `root` with `root^2 = a` if `a` is a square and `root^2 = a * non_residue` otherwise.
+    pub fn is_square(&self, ctx: &mut Context<F>, a: FqPoint<F>) -> AssignedValue<F> {
+        let fp2_chip = Fp2Chip::<F>::new(self.fp_chip);
+        let a_val = fp2_chip.get_assigned_value(&a.clone().into());
+        let is_square = native::is_square(&a_val);
+        let root_val = if is_square {
+            a_val.sqrt().unwrap()
+        } else {
+            (a_val * self.constants.non_residue).sqrt().unwrap()
+        };
+
+        let is_square = ctx.load_witness(F::from(is_square));
+        self.gate().assert_bit(ctx, is_square);
+        let root = fp2_chip.load_private(ctx, root_val);
+        let non_residue = fp2_chip.load_constant(ctx, self.constants.non_residue);
+        let a_non_residue = fp2_chip.mul(ctx, a.clone(), non_residue);
+        let square = fp2_chip.select(ctx, a, a_non_residue, is_square);
+        let root_sq = fp2_chip.mul_no_carry(ctx, root.clone(), root);
+        let diff = fp2_chip.sub_no_carry(ctx, root_sq, square);
+        fp2_chip.check_carry_mod_to_zero(ctx, diff);
+        is_square
+    }
+
+    // x^3 + b
+    fn curve_rhs(&self, ctx: &mut Context<F>, x: FqPoint<F>) -> FqPoint<F> {
+        let fp2_chip = Fp2Chip::<F>::new(self.fp_chip);
+        let x2 = fp2_chip.mul(ctx, x.clone(), x.clone());
+        let x3 = fp2_chip.mul_no_carry(ctx, x2, x);
+        let rhs = fp2_chip.add_constant_no_carry(ctx, x3, self.constants.b);
+        fp2_chip.carry_mod(ctx, rhs)
+    }
+
+    // map_to_curve_svdw from RFC 9380 Appendix F.1, onto the twist E'(Fp2).
+    // inv0 is computed with `divide_unsafe`, so `u` with u^4 * c1^2 = 1 is not supported; such `u` are hit with negligible probability.
+    pub fn map_to_curve_svdw(&self, ctx: &mut Context<F>, u: FqPoint<F>) -> EcPoint<F, FqPoint<F>> {
+        let fp2_chip = Fp2Chip::<F>::new(self.fp_chip);
+        let gate = self.gate();
+        let SvdwConstants { c1, c2, c3, c4, z, .. } = self.constants;
+
+        let c1 = fp2_chip.load_constant(ctx, c1);
+        let c3 = fp2_chip.load_constant(ctx, c3);
+        let c4 = fp2_chip.load_constant(ctx, c4);
+        let one = fp2_chip.load_constant(ctx, Fq2::one());
+
+        let tv1 = fp2_chip.mul(ctx, u.clone(), u.clone());
+        let tv1 = fp2_chip.mul(ctx, tv1, c1);
+        let tv2 = fp2_chip.add_constant_no_carry(ctx, tv1.clone(), Fq2::one());
+        let tv2 = fp2_chip.carry_mod(ctx, tv2);
+        let neg_tv1 = fp2_chip.negate(ctx, tv1);
+        let tv1 = fp2_chip.add_constant_no_carry(ctx, neg_tv1, Fq2::one());
+        let tv1 = fp2_chip.carry_mod(ctx, tv1);
+        let tv3 = fp2_chip.mul(ctx, tv1.clone(), tv2.clone());
+        let tv3 = fp2_chip.divide_unsafe(ctx, one, tv3);
+        let tv4 = fp2_chip.mul(ctx, u.clone(), tv1);
+        let tv4 = fp2_chip.mul(ctx, tv4, tv3.clone());
+        let tv4 = fp2_chip.mul(ctx, tv4, c3);
+
+        let neg_tv4 = fp2_chip.negate(ctx, tv4.clone());
+        let x1 = fp2_chip.add_constant_no_carry(ctx, neg_tv4, c2);
+        let x1 = fp2_chip.carry_mod(ctx, x1);
+        let gx1 = self.curve_rhs(ctx, x1.clone());
+        let e1 = self.is_square(ctx, gx1);
+
+        let x2 = fp2_chip.add_constant_no_carry(ctx, tv4, c2);
+        let x2 = fp2_chip.carry_mod(ctx, x2);
+        let gx2 = self.curve_rhs(ctx, x2.clone());
+        let gx2_is_square = self.is_square(ctx, gx2);
+        let not_e1 = gate.not(ctx, e1);
+        let e2 = gate.and(ctx, gx2_is_square, not_e1);
+
+        let x3 = fp2_chip.mul(ctx, tv2.clone(), tv2);
+        let x3 = fp2_chip.mul(ctx, x3, tv3);
+        let x3 = fp2_chip.mul(ctx, x3.clone(), x3);
+        let x3 = fp2_chip.mul(ctx, x3, c4);
+        let x3 = fp2_chip.add_constant_no_carry(ctx, x3, z);
+        let x3 = fp2_chip.carry_mod(ctx, x3);
+
+        let x = fp2_chip.select(ctx, x1, x3, e1);
+        let x = fp2_chip.select(ctx, x2, x, e2);
+        let gx = self.curve_rhs(ctx, x.clone());
+
+        // gx is a square by choice of x; witness the root whose sign matches u
+        let u_val = fp2_chip.get_assigned_value(&u.clone().into());
+        let gx_val = fp2_chip.get_assigned_value(&gx.clone().into());
+        let mut y_val = gx_val.sqrt().unwrap();
+        if native::sgn0(&u_val) != native::sgn0(&y_val) {
+            y_val = -y_val;
+        }
+        let y = fp2_chip.load_private(ctx, y_val);
+        let y_sq = fp2_chip.mul_no_carry(ctx, y.clone(), y.clone());
+        let diff = fp2_chip.sub_no_carry(ctx, y_sq, gx);
+        fp2_chip.check_carry_mod_to_zero(ctx, diff);
+
+        let u_sign = self.sgn0(ctx, &u);
+        let y_sign = self.sgn0(ctx, &y);
+        ctx.constrain_equal(&u_sign, &y_sign);
+
+        EcPoint::new(x, y)
+    }
+
+    // psi(P) = (conj(x) * c2, conj(y) * c3)
+    pub fn psi(
+        &self,
+        ctx: &mut Context<F>,
+        P: impl Into<EcPoint<F, FqPoint<F>>>,
+    ) -> EcPoint<F, FqPoint<F>> {
+        let fp2_chip = Fp2Chip::<F>::new(self.fp_chip);
+        let g2_chip = EccChip::new(&fp2_chip);
+        let (c2, c3) = psi_coeffs();
+        let c2 = fp2_chip.load_constant(ctx, c2);
+        let c3 = fp2_chip.load_constant(ctx, c3);
+        twisted_frobenius(&g2_chip, ctx, P, c2, c3)
+    }
+
+    // clear_cofactor(P) = [x]P + psi([3x]P) + psi^2([x]P) + psi^3(P), following Fuentes-Castaneda, Knapp and Rodriguez-Henriquez
+    // Assumes `P` does not have small order, which holds with overwhelming probability for outputs of the SVDW map.
+    pub fn clear_cofactor(
+        &self,
+        ctx: &mut Context<F>,
+        P: EcPoint<F, FqPoint<F>>,
+    ) -> EcPoint<F, FqPoint<F>> {
+        let fp2_chip = Fp2Chip::<F>::new(self.fp_chip);
+        let g2_chip = EccChip::new(&fp2_chip);
+
+        let x = ctx.load_constant(F::from(BN_X));
+        let xP = g2_chip.scalar_mult::<G2Affine>(ctx, P.clone(), vec![x], 63, 4);
+        let x2P = g2_chip.double(ctx, &xP);
+        let x3P = g2_chip.add_unequal(ctx, &x2P, &xP, true);
+
+        let psi_x3P = self.psi(ctx, x3P);
+        let psi_xP = self.psi(ctx, &xP);
+        let psi2_xP = self.psi(ctx, psi_xP);
+        let psi_P = self.psi(ctx, P);
+        let psi2_P = self.psi(ctx, psi_P);
+        let psi3_P = self.psi(ctx, psi2_P);
+
+        g2_chip.sum::<G2Affine>(ctx, [xP, psi_x3P, psi2_xP, psi3_P])
+    }
+
+    // hash_to_curve from uniform bytes: clear_cofactor(map_to_curve(u0) + map_to_curve(u1))
+    fn hash_uniform_to_g2(
+        &self,
+        ctx: &mut Context<F>,
+        uniform_bytes: &[SafeByte<F>],
+    ) -> EcPoint<F, FqPoint<F>> {
+        let fp2_chip = Fp2Chip::<F>::new(self.fp_chip);
+        let g2_chip = EccChip::new(&fp2_chip);
+        let [u0, u1] = self.hash_to_field(ctx, uniform_bytes);
+        let Q0 = self.map_to_curve_svdw(ctx, u0);
+        let Q1 = self.map_to_curve_svdw(ctx, u1);
+        let R = g2_chip.add_unequal(ctx, &Q0, &Q1, true);
+        self.clear_cofactor(ctx, R)
+    }
+
+    // Hashes a fixed length message to G2; the output matches `native::hash_to_g2`
+    pub fn hash_to_g2_fix_len<const LEN: usize>(
+        &self,
+        ctx: &mut Context<F>,
+        msg: &FixLenBytes<F, LEN>,
+    ) -> EcPoint<F, FqPoint<F>> {
+        let uniform_bytes = self.expand_message_xmd_fix_len(ctx, msg, HASH_TO_G2_LEN_IN_BYTES);
+        self.hash_uniform_to_g2(ctx, &uniform_bytes)
+    }
+
+    // Hashes a variable length message to G2; the output matches `native::hash_to_g2`
+    pub fn hash_to_g2_var_len<const MAX_LEN: usize>(
+        &self,
+        ctx: &mut Context<F>,
+        msg: &VarLenBytes<F, MAX_LEN>,
+    ) -> EcPoint<F, FqPoint<F>> {
+        let uniform_bytes = self.expand_message_xmd_var_len(ctx, msg, HASH_TO_G2_LEN_IN_BYTES);
+        self.hash_uniform_to_g2(ctx, &uniform_bytes)
+    }
+}
+
+// Native reference implementation, used for witness generation and testing
+pub mod native {
+    use super::{psi_coeffs, SvdwConstants, BN_X, HASH_TO_FIELD_L, HASH_TO_G2_LEN_IN_BYTES};
+    use crate::ff::Field;
+    use crate::halo2_proofs::halo2curves::bn256::{Fq, Fq2, Fr, G2Affine, G2};
+    use halo2_base::utils::{biguint_to_fe, modulus};
+    use num_bigint::BigUint;
+    use sha2::{Digest, Sha256};
+
+    pub fn expand_message_xmd(msg: &[u8], dst: &[u8], len_in_bytes: usize) -> Vec<u8> {
+        let ell = (len_in_bytes + 31) / 32;
+        assert!(ell <= 255 && len_in_bytes <= u16::MAX as usize && dst.len() <= 255);
+        let dst_prime = [dst, &[dst.len() as u8][..]].concat();
+
+        let b_0 = Sha256::new()
+            .chain_update([0u8; 64])
+            .chain_update(msg)
+            .chain_update((len_in_bytes as u16).to_be_bytes())
+            .chain_update([0u8])
+            .chain_update(&dst_prime)
+            .finalize();
+        let mut b_i =
+            Sha256::new().chain_update(b_0).chain_update([1u8]).chain_update(&dst_prime).finalize();
+        let mut uniform_bytes = b_i.to_vec();
+        for i in 2..=ell {
+            let xored = b_0.iter().zip(b_i.iter()).map(|(a, b)| a ^ b).collect::<Vec<_>>();
+            b_i = Sha256::new()
+                .chain_update(xored)
+                .chain_update([i as u8])
+                .chain_update(&dst_prime)
+                .finalize();
+            uniform_bytes.extend_from_slice(&b_i);
+        }
+        uniform_bytes.truncate(len_in_bytes);
+        uniform_bytes
+    }
+
+    pub fn fp_to_bytes_be(a: &Fq) -> [u8; 32] {
+        let mut bytes = a.to_bytes();
+        bytes.reverse();
+        bytes
+    }
+
+    pub fn hash_to_field(msg: &[u8], dst: &[u8]) -> [Fq2; 2] {
+        let uniform_bytes = expand_message_xmd(msg, dst, HASH_TO_G2_LEN_IN_BYTES);
+        let p = modulus::<Fq>();
+        let fq = |bytes: &[u8]| biguint_to_fe::<Fq>(&(BigUint::from_bytes_be(bytes) % &p));
+        let u = |i: usize| {
+            let offset = 2 * HASH_TO_FIELD_L * i;
+            Fq2 {
+                c0: fq(&uniform_bytes[offset..offset + HASH_TO_FIELD_L]),
+                c1: fq(&uniform_bytes[offset + HASH_TO_FIELD_L..offset + 2 * HASH_TO_FIELD_L]),
+            }
+        };
+        [u(0), u(1)]
+    }
+
+    pub fn sgn0(a: &Fq2) -> bool {
+        let sign_0 = a.c0.to_bytes()[0] & 1 == 1;
+        let zero_0 = a.c0 == Fq::zero();
+        let sign_1 = a.c1.to_bytes()[0] & 1 == 1;
+        sign_0 || (zero_0 && sign_1)
+    }
+
+    pub fn is_square(a: &Fq2) -> bool {
+        bool::from(a.sqrt().is_some())
+    }
+
+    pub fn map_to_curve_svdw(u: Fq2) -> G2Affine {
+        let SvdwConstants { b, z, c1, c2, c3, c4, .. } = SvdwConstants::new();
+        let g = |x: Fq2| x.square() * x + b;
+
+        let tv1 = u.square() * c1;
+        let tv2 = Fq2::one() + tv1;
+        let tv1 = Fq2::one() - tv1;
+        let tv3 = (tv1 * tv2).invert().unwrap_or(Fq2::zero());
+        let tv4 = u * tv1 * tv3 * c3;
+        let x1 = c2 - tv4;
+        let e1 = is_square(&g(x1));
+        let x2 = c2 + tv4;
+        let e2 = is_square(&g(x2)) && !e1;
+        let x3 = (tv2.square() * tv3).square() * c4 + z;
+
+        let x = if e1 {
+            x1
+        } else if e2 {
+            x2
+        } else {
+            x3
+        };
+        let mut y = g(x).sqrt().unwrap();
+        if sgn0(&u) != sgn0(&y) {
+            y = -y;
+        }
+        G2Affine { x, y }
+    }
+
+    pub fn psi(p: &G2Affine) -> G2Affine {
+        let (c2, c3) = psi_coeffs();
+        let conjugate = |a: Fq2| Fq2 { c0: a.c0, c1: -a.c1 };
+        G2Affine { x: conjugate(p.x) * c2, y: conjugate(p.y) * c3 }
+    }
+
+    pub fn clear_cofactor(p: &G2Affine) -> G2Affine {
+        let x_p = G2Affine::from(p * Fr::from(BN_X));
+        let x3_p = G2Affine::from(x_p * Fr::from(3));
+        let out: G2 = G2::from(x_p) + psi(&x3_p) + psi(&psi(&x_p)) + psi(&psi(&psi(p)));
+        out.into()
+    }
+
+    pub fn hash_to_g2(msg: &[u8], dst: &[u8]) -> G2Affine {
+        let [u0, u1] = hash_to_field(msg, dst);
+        let q0 = map_to_curve_svdw(u0);
+        let q1 = map_to_curve_svdw(u1);
+        clear_cofactor(&(q0 + q1).into())
+    }
+}
@@ -0,0 +1,304 @@
+#![allow(non_snake_case)]
+// Circuit friendly hash to G1 for BN254, for BLS signatures with signatures in G1.
+// The message is hashed with Poseidon over the native field: d = H(msg), u_i = H(DST, d, i) for i = 0, 1.
+// Each u_i < r < p is read as an element of Fp and the output is map_to_curve(u_0) + map_to_curve(u_1)
+// with the Shallue-van de Woestijne (SVDW) map of RFC 9380. G1 has cofactor 1 so no cofactor clearing is needed.
+use super::merkle_tree::load_native;
+use super::{FpChip, FpPoint, Fq};
+use crate::ecc::{EcPoint, EccChip};
+use crate::ff::Field;
+use crate::fields::{FieldChip, Selectable};
+use halo2_base::gates::GateInstructions;
+use halo2_base::poseidon::hasher::PoseidonHasher;
+use halo2_base::utils::{modulus, BigPrimeField};
+use halo2_base::{AssignedValue, Context};
+
+// Default domain separation tag, at most 31 bytes so it fits in one field element
+pub const DST_G1: &[u8] = b"BLS_SIG_BN254G1_POSEIDON_SVDW_";
+
+// Constants of the SVDW map for y^2 = x^3 + 3, see RFC 9380 Section 6.6.1
+#[derive(Clone, Copy, Debug)]
+pub struct SvdwG1Constants {
+    pub b: Fq,
+    pub z: Fq,
+    pub c1: Fq,
+    pub c2: Fq,
+    pub c3: Fq,
+    pub c4: Fq,
+    // -1 is a non-square since p = 3 mod 4
+    pub non_residue: Fq,
+}
+
+impl SvdwG1Constants {
+    pub fn new() -> Self {
+        let b = Fq::from(3);
+        // Z = 1 is the output of find_z_svdw
+        let z = Fq::one();
+        let gz = z.square() * z + b;
+        let three_z2 = Fq::from(3) * z.square();
+        let c1 = gz;
+        let c2 = -(z * Fq::from(2).invert().unwrap());
+        let mut c3 = (-(gz * three_z2)).sqrt().unwrap();
+        if native::sgn0(&c3) {
+            c3 = -c3;
+        }
+        let c4 = -(Fq::from(4) * gz) * three_z2.invert().unwrap();
+        Self { b, z, c1, c2, c3, c4, non_residue: -Fq::one() }
+    }
+}
+
+impl Default for SvdwG1Constants {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct HashToG1Chip<'chip, F: BigPrimeField> {
+    pub fp_chip: &'chip FpChip<'chip, F>,
+    pub poseidon_chip: &'chip PoseidonHasher<F, 3, 2>,
+    pub dst: F,
+    pub constants: SvdwG1Constants,
+}
+
+impl<'chip, F: BigPrimeField> HashToG1Chip<'chip, F> {
+    // `poseidon_chip` must already have its constants initialized
+    pub fn new(
+        fp_chip: &'chip FpChip<F>,
+        poseidon_chip: &'chip PoseidonHasher<F, 3, 2>,
+        dst: &[u8],
+    ) -> Self {
+        assert!(dst.len() < 32, "domain separation tag must fit in one field element");
+        assert!(modulus::<F>() < modulus::<Fq>(), "native field must be smaller than Fp");
+        Self {
+            fp_chip,
+            poseidon_chip,
+            dst: F::from_bytes_le(dst),
+            constants: SvdwG1Constants::new(),
+        }
+    }
+
+    fn digest_to_field(&self, ctx: &mut Context<F>, digest: AssignedValue<F>) -> [FpPoint<F>; 2] {
+        let gate = self.fp_chip.gate();
+        let dst = ctx.load_constant(self.dst);
+        [0u64, 1].map(|i| {
+            let i = ctx.load_constant(F::from(i));
+            let u = self.poseidon_chip.hash_fix_len_array(ctx, gate, &[dst, digest, i]);
+            load_native(self.fp_chip, ctx, u)
+        })
+    }
+
+    // Poseidon hash_to_field for a fixed length message
+    pub fn hash_to_field(&self, ctx: &mut Context<F>, msg: &[AssignedValue<F>]) -> [FpPoint<F>; 2] {
+        let digest = self.poseidon_chip.hash_fix_len_array(ctx, self.fp_chip.gate(), msg);
+        self.digest_to_field(ctx, digest)
+    }
+
+    // Poseidon hash_to_field for `msg[..len]`, with `msg` right padded
+    pub fn hash_to_field_var_len(
+        &self,
+        ctx: &mut Context<F>,
+        msg: &[AssignedValue<F>],
+        len: AssignedValue<F>,
+    ) -> [FpPoint<F>; 2] {
+        let digest = self.poseidon_chip.hash_var_len_array(ctx, self.fp_chip.range(), msg, len);
+        self.digest_to_field(ctx, digest)
+    }
+
+    // sgn0(a) = a mod 2, constraining `a` to be reduced
+    pub fn sgn0(&self, ctx: &mut Context<F>, a: FpPoint<F>) -> AssignedValue<F> {
+        let is_even = self.fp_chip.is_even(ctx, a);
+        self.fp_chip.gate().not(ctx, is_even)
+    }
+
+    // Returns 1 iff `a` is a square in Fp.
+    // Witnesses `root` with `root^2 = a` if `a` is a square and `root^2 = -a` otherwise.
+    pub fn is_square(&self, ctx: &mut Context<F>, a: FpPoint<F>) -> AssignedValue<F> {
+        let fp_chip = self.fp_chip;
+        let a_val = fp_chip.get_assigned_value(&a.clone().into());
+        let is_square = native::is_square(&a_val);
+        let root_val = if is_square {
+            a_val.sqrt().unwrap()
+        } else {
+            (a_val * self.constants.non_residue).sqrt().unwrap()
+        };
+
+        let is_square = ctx.load_witness(F::from(is_square));
+        fp_chip.gate().assert_bit(ctx, is_square);
+        let root = fp_chip.load_private(ctx, root_val);
+        let neg_a = fp_chip.negate(ctx, a.clone());
+        let square = fp_chip.select(ctx, a, neg_a, is_square);
+        let root_sq = fp_chip.mul_no_carry(ctx, root.clone(), root);
+        let diff = fp_chip.sub_no_carry(ctx, root_sq, square);
+        fp_chip.check_carry_mod_to_zero(ctx, diff);
+        is_square
+    }
+
+    // x^3 + b
+    fn curve_rhs(&self, ctx: &mut Context<F>, x: FpPoint<F>) -> FpPoint<F> {
+        let fp_chip = self.fp_chip;
+        let x2 = fp_chip.mul(ctx, x.clone(), x.clone());
+        let x3 = fp_chip.mul_no_carry(ctx, x2, x);
+        let rhs = fp_chip.add_constant_no_carry(ctx, x3, self.constants.b);
+        fp_chip.carry_mod(ctx, rhs)
+    }
+
+    // map_to_curve_svdw from RFC 9380 Appendix F.1.
+    // inv0 is computed with `divide_unsafe`, so `u` with u^4 * c1^2 = 1 is not supported; such `u` are hit with negligible probability.
+    pub fn map_to_curve_svdw(&self, ctx: &mut Context<F>, u: FpPoint<F>) -> EcPoint<F, FpPoint<F>> {
+        let fp_chip = self.fp_chip;
+        let gate = fp_chip.gate();
+        let SvdwG1Constants { c1, c2, c3, c4, z, .. } = self.constants;
+
+        let c1 = fp_chip.load_constant(ctx, c1);
+        let c3 = fp_chip.load_constant(ctx, c3);
+        let c4 = fp_chip.load_constant(ctx, c4);
+        let one = fp_chip.load_constant(ctx, Fq::one());
+
+        let tv1 = fp_chip.mul(ctx, u.clone(), u.clone());
+        let tv1 = fp_chip.mul(ctx, tv1, c1);
+        let tv2 = fp_chip.add_constant_no_carry(ctx, tv1.clone(), Fq::one());
+        let tv2 = fp_chip.carry_mod(ctx, tv2);
+        let neg_tv1 = fp_chip.negate(ctx, tv1);
+        let tv1 = fp_chip.add_constant_no_carry(ctx, neg_tv1, Fq::one());
+        let tv1 = fp_chip.carry_mod(ctx, tv1);
+        let tv3 = fp_chip.mul(ctx, tv1.clone(), tv2.clone());
+        let tv3 = fp_chip.divide_unsafe(ctx, one, tv3);
+        let tv4 = fp_chip.mul(ctx, u.clone(), tv1);
+        let tv4 = fp_chip.mul(ctx, tv4, tv3.clone());
+        let tv4 = fp_chip.mul(ctx, tv4, c3);
+
+        let neg_tv4 = fp_chip.negate(ctx, tv4.clone());
+        let x1 = fp_chip.add_constant_no_carry(ctx, neg_tv4, c2);
+        let x1 = fp_chip.carry_mod(ctx, x1);
+        let gx1 = self.curve_rhs(ctx, x1.clone());
+        let e1 = self.is_square(ctx, gx1);
+
+        let x2 = fp_chip.add_constant_no_carry(ctx, tv4, c2);
+        let x2 = fp_chip.carry_mod(ctx, x2);
+        let gx2 = self.curve_rhs(ctx, x2.clone());
+        let gx2_is_square = self.is_square(ctx, gx2);
+        let not_e1 = gate.not(ctx, e1);
+        let e2 = gate.and(ctx, gx2_is_square, not_e1);
+
+        let x3 = fp_chip.mul(ctx, tv2.clone(), tv2);
+        let x3 = fp_chip.mul(ctx, x3, tv3);
+        let x3 = fp_chip.mul(ctx, x3.clone(), x3);
+        let x3 = fp_chip.mul(ctx, x3, c4);
+        let x3 = fp_chip.add_constant_no_carry(ctx, x3, z);
+        let x3 = fp_chip.carry_mod(ctx, x3);
+
+        let x = fp_chip.select(ctx, x1, x3, e1);
+        let x = fp_chip.select(ctx, x2, x, e2);
+        let gx = self.curve_rhs(ctx, x.clone());
+
+        // gx is a square by choice of x; witness the root whose sign matches u
+        let u_val = fp_chip.get_assigned_value(&u.clone().into());
+        let gx_val = fp_chip.get_assigned_value(&gx.clone().into());
+        let mut y_val = gx_val.sqrt().unwrap();
+        if native::sgn0(&u_val) != native::sgn0(&y_val) {
+            y_val = -y_val;
+        }
+        let y = fp_chip.load_private(ctx, y_val);
+        let y_sq = fp_chip.mul_no_carry(ctx, y.clone(), y.clone());
+        let diff = fp_chip.sub_no_carry(ctx, y_sq, gx);
+        fp_chip.check_carry_mod_to_zero(ctx, diff);
+
+        let u_sign = self.sgn0(ctx, u);
+        let y_sign = self.sgn0(ctx, y.clone());
+        ctx.constrain_equal(&u_sign, &y_sign);
+
+        EcPoint::new(x, y)
+    }
+
+    fn hash_field_to_g1(
+        &self,
+        ctx: &mut Context<F>,
+        [u0, u1]: [FpPoint<F>; 2],
+    ) -> EcPoint<F, FpPoint<F>> {
+        let g1_chip = EccChip::new(self.fp_chip);
+        let Q0 = self.map_to_curve_svdw(ctx, u0);
+        let Q1 = self.map_to_curve_svdw(ctx, u1);
+        g1_chip.add_unequal(ctx, &Q0, &Q1, true)
+    }
+
+    // Hashes a fixed length message of native field elements to G1; the output matches `native::hash_to_g1`
+    pub fn hash_to_g1(
+        &self,
+        ctx: &mut Context<F>,
+        msg: &[AssignedValue<F>],
+    ) -> EcPoint<F, FpPoint<F>> {
+        let u = self.hash_to_field(ctx, msg);
+        self.hash_field_to_g1(ctx, u)
+    }
+
+    // Hashes `msg[..len]` to G1; the output matches `native::hash_to_g1` on the first `len` elements
+    pub fn hash_to_g1_var_len(
+        &self,
+        ctx: &mut Context<F>,
+        msg: &[AssignedValue<F>],
+        len: AssignedValue<F>,
+    ) -> EcPoint<F, FpPoint<F>> {
+        let u = self.hash_to_field_var_len(ctx, msg, len);
+        self.hash_field_to_g1(ctx, u)
+    }
+}
+
+// Native reference implementation, used by signers and for testing
+pub mod native {
+    use super::SvdwG1Constants;
+    use crate::bn254::merkle_tree::native::poseidon_hash;
+    use crate::ff::Field;
+    use crate::halo2_proofs::halo2curves::bn256::{Fq, Fr, G1Affine};
+    use halo2_base::utils::{biguint_to_fe, fe_to_biguint, ScalarField};
+
+    pub fn hash_to_field(msg: &[Fr], dst: &[u8]) -> [Fq; 2] {
+        let digest = poseidon_hash(msg);
+        let dst = Fr::from_bytes_le(dst);
+        [0u64, 1].map(|i| {
+            let u = poseidon_hash(&[dst, digest, Fr::from(i)]);
+            biguint_to_fe(&fe_to_biguint(&u))
+        })
+    }
+
+    pub fn sgn0(a: &Fq) -> bool {
+        a.to_bytes()[0] & 1 == 1
+    }
+
+    pub fn is_square(a: &Fq) -> bool {
+        bool::from(a.sqrt().is_some())
+    }
+
+    pub fn map_to_curve_svdw(u: Fq) -> G1Affine {
+        let SvdwG1Constants { b, z, c1, c2, c3, c4, .. } = SvdwG1Constants::new();
+        let g = |x: Fq| x.square() * x + b;
+
+        let tv1 = u.square() * c1;
+        let tv2 = Fq::one() + tv1;
+        let tv1 = Fq::one() - tv1;
+        let tv3 = (tv1 * tv2).invert().unwrap_or(Fq::zero());
+        let tv4 = u * tv1 * tv3 * c3;
+        let x1 = c2 - tv4;
+        let e1 = is_square(&g(x1));
+        let x2 = c2 + tv4;
+        let e2 = is_square(&g(x2)) && !e1;
+        let x3 = (tv2.square() * tv3).square() * c4 + z;
+
+        let x = if e1 {
+            x1
+        } else if e2 {
+            x2
+        } else {
+            x3
+        };
+        let mut y = g(x).sqrt().unwrap();
+        if sgn0(&u) != sgn0(&y) {
+            y = -y;
+        }
+        G1Affine { x, y }
+    }
+
+    pub fn hash_to_g1(msg: &[Fr], dst: &[u8]) -> G1Affine {
+        let [u0, u1] = hash_to_field(msg, dst);
+        (map_to_curve_svdw(u0) + map_to_curve_svdw(u1)).into()
+    }
+}
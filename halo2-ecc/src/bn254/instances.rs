@@ -0,0 +1,146 @@
+use halo2_base::utils::ScalarField;
+use halo2_base::AssignedValue;
+
+// Statement proven by a verification chip, meant to be exposed through `BaseCircuitBuilder::assigned_instances`
+// so that the verifier learns what was verified and not only that something was.
+// `instances` lays it out in this order, skipping the values the statement does not have:
+//   result     : 1 iff the verification passed
+//   root       : Merkle root the leaves or keys were checked against
+//   msg_digest : Poseidon digest of the signed message
+//   agg_key    : CRT limbs of the aggregate public key, x then y (x.c0, x.c1, y.c0, y.c1 for a key in G2)
+// Which values a statement has only depends on the verification function, so the layout is fixed per circuit.
+#[derive(Clone, Debug)]
+pub struct PublicStatement<F: ScalarField> {
+    pub result: AssignedValue<F>,
+    pub root: Option<AssignedValue<F>>,
+    pub msg_digest: Option<AssignedValue<F>>,
+    pub agg_key: Vec<AssignedValue<F>>,
+}
+
+impl<F: ScalarField> PublicStatement<F> {
+    pub fn new(result: AssignedValue<F>) -> Self {
+        Self { result, root: None, msg_digest: None, agg_key: vec![] }
+    }
+
+    pub fn instances(&self) -> Vec<AssignedValue<F>> {
+        let mut instances = vec![self.result];
+        instances.extend(self.root);
+        instances.extend(self.msg_digest);
+        instances.extend(self.agg_key.iter().copied());
+        instances
+    }
+}
+
+// Appends the instances of each statement, in order, to the first instance column,
+// e.g. `assign_instances(&mut builder.assigned_instances, &statements)`
+pub fn assign_instances<F: ScalarField>(
+    assigned_instances: &mut [Vec<AssignedValue<F>>],
+    statements: &[PublicStatement<F>],
+) {
+    assert!(!assigned_instances.is_empty(), "the circuit must have an instance column");
+    for statement in statements {
+        assigned_instances[0].extend(statement.instances());
+    }
+}
+
+// Expected instances of honest statements, in the layout of `PublicStatement::instances`
+pub mod native {
+    use crate::bn254::merkle_tree::native::{g1_leaf, g1_limbs, g2_leaf, g2_limbs, hash_pair};
+    use crate::group::{Curve, Group};
+    use crate::halo2_proofs::halo2curves::bn256::{Fr, G1Affine, G2Affine, G1, G2};
+
+    // Native counterpart of `PublicStatement`
+    #[derive(Clone, Debug)]
+    pub struct PublicStatementValues {
+        pub result: bool,
+        pub root: Option<Fr>,
+        pub msg_digest: Option<Fr>,
+        pub agg_key: Vec<Fr>,
+    }
+
+    impl PublicStatementValues {
+        pub fn instances(&self) -> Vec<Fr> {
+            let mut instances = vec![Fr::from(self.result as u64)];
+            instances.extend(self.root);
+            instances.extend(self.msg_digest);
+            instances.extend(self.agg_key.iter().copied());
+            instances
+        }
+    }
+
+    // Instance column of a circuit exposing `statements` with `assign_instances`
+    pub fn expected_instances(statements: &[PublicStatementValues]) -> Vec<Fr> {
+        statements.iter().flat_map(|statement| statement.instances()).collect()
+    }
+
+    // Statement of `MerkleTreeChip::merkle_tree_verify_batch_public`
+    pub fn merkle_batch_statement(root: Fr) -> PublicStatementValues {
+        PublicStatementValues { result: true, root: Some(root), msg_digest: None, agg_key: vec![] }
+    }
+
+    // Statement of `CombineBlsMtChip::combine_bls_mt_verify_public`: the message digest is Poseidon([message, 0])
+    // and the aggregate key is the sum of `pubkeys`
+    pub fn combine_bls_mt_statement(
+        root: Fr,
+        message: Fr,
+        pubkeys: &[G1Affine],
+        limb_bits: usize,
+        num_limbs: usize,
+    ) -> PublicStatementValues {
+        let agg_key = pubkeys.iter().fold(G1::identity(), |acc, pk| acc + pk).to_affine();
+        PublicStatementValues {
+            result: true,
+            root: Some(root),
+            msg_digest: Some(hash_pair(message, Fr::zero())),
+            agg_key: g1_limbs(&agg_key, limb_bits, num_limbs),
+        }
+    }
+
+    // Statement of `CombineBlsMtChip::combine_bls_mt_verify_2_public`
+    pub fn combine_bls_mt_statement_2(
+        root: Fr,
+        message: Fr,
+        pubkeys: &[G2Affine],
+        limb_bits: usize,
+        num_limbs: usize,
+    ) -> PublicStatementValues {
+        let agg_key = pubkeys.iter().fold(G2::identity(), |acc, pk| acc + pk).to_affine();
+        PublicStatementValues {
+            result: true,
+            root: Some(root),
+            msg_digest: Some(hash_pair(message, Fr::zero())),
+            agg_key: g2_limbs(&agg_key, limb_bits, num_limbs),
+        }
+    }
+
+    // Statement of `MspChip::msp_verify_public`: the message digest is Poseidon over the CRT limbs of H(m)
+    // and the aggregate key is ivk
+    pub fn msp_statement(
+        msghash: &G2Affine,
+        ivk: &G1Affine,
+        limb_bits: usize,
+        num_limbs: usize,
+    ) -> PublicStatementValues {
+        PublicStatementValues {
+            result: true,
+            root: None,
+            msg_digest: Some(g2_leaf(msghash, limb_bits, num_limbs)),
+            agg_key: g1_limbs(ivk, limb_bits, num_limbs),
+        }
+    }
+
+    // Statement of `MspChip::msp_verify_2_public`
+    pub fn msp_statement_2(
+        msghash: &G1Affine,
+        ivk: &G2Affine,
+        limb_bits: usize,
+        num_limbs: usize,
+    ) -> PublicStatementValues {
+        PublicStatementValues {
+            result: true,
+            root: None,
+            msg_digest: Some(g1_leaf(msghash, limb_bits, num_limbs)),
+            agg_key: g2_limbs(ivk, limb_bits, num_limbs),
+        }
+    }
+}
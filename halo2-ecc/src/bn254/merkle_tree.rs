@@ -12,18 +12,123 @@
 // use super::FpChip;
 // use crate::ecc::EccChip;
 
-use halo2_base::utils::BigPrimeField;
+use halo2_base::utils::{biguint_to_fe, fe_to_biguint, modulus, BigPrimeField};
 use halo2_base::poseidon::hasher::PoseidonHasher;
 use halo2_base::gates::{GateChip, GateInstructions};
+use halo2_base::gates::flex_gate::threads::{parallelize_core, SinglePhaseCoreManager};
 // use super::FpChip;
 use halo2_base::{ AssignedValue, Context};
-use crate::bn254::MerkleInfo;
+use crate::bn254::{MerkleInfo, MerkleMultiProof};
+use super::instances::PublicStatement;
+use super::{FpChip, FpPoint, FqPoint, Fq};
+use crate::bigint::big_less_than;
+use crate::ecc::EcPoint;
+use crate::fields::FieldChip;
 
 // pub struct MerkleTreeChip<'chip, F: BigPrimeField, const T: usize, const RATE: usize> {
 //     pub fp_chip: &'chip FpChip<'chip, F>,
 //     pub poseidon_chip: &'chip PoseidonHasher<F, T, RATE>,
 // }
 
+// CRT limbs of the coordinates, constrained to be reduced so that they are a canonical encoding of the point
+pub fn coordinate_limbs<F: BigPrimeField>(
+    fp_chip: &FpChip<F>,
+    ctx: &mut Context<F>,
+    coordinates: Vec<FpPoint<F>>,
+) -> Vec<AssignedValue<F>> {
+    coordinates
+        .into_iter()
+        .flat_map(|a| {
+            fp_chip.enforce_less_than_p(ctx, a.clone());
+            a.limbs().to_vec()
+        })
+        .collect()
+}
+
+// x and y limbs of a point in G1
+pub fn g1_limbs<F: BigPrimeField>(
+    fp_chip: &FpChip<F>,
+    ctx: &mut Context<F>,
+    pt: &EcPoint<F, FpPoint<F>>,
+) -> Vec<AssignedValue<F>> {
+    coordinate_limbs(fp_chip, ctx, vec![pt.x.clone(), pt.y.clone()])
+}
+
+// x.c0, x.c1, y.c0 and y.c1 limbs of a point in G2
+pub fn g2_limbs<F: BigPrimeField>(
+    fp_chip: &FpChip<F>,
+    ctx: &mut Context<F>,
+    pt: &EcPoint<F, FqPoint<F>>,
+) -> Vec<AssignedValue<F>> {
+    let coordinates = pt.x.0.iter().chain(pt.y.0.iter()).cloned().collect();
+    coordinate_limbs(fp_chip, ctx, coordinates)
+}
+
+// Loads the native field element `x` as an element of Fp with the same integer value.
+// The limbs are constrained to equal `x` mod r and to be less than r, so the representation is unique.
+pub fn load_native<F: BigPrimeField>(
+    fp_chip: &FpChip<F>,
+    ctx: &mut Context<F>,
+    x: AssignedValue<F>,
+) -> FpPoint<F> {
+    let x_fp = fp_chip.load_private(ctx, biguint_to_fe::<Fq>(&fe_to_biguint(x.value())));
+    ctx.constrain_equal(x_fp.native(), &x);
+    let r = fp_chip.load_constant_uint(ctx, modulus::<F>());
+    let is_lt = big_less_than::assign(
+        fp_chip.range(),
+        ctx,
+        x_fp.clone(),
+        r,
+        fp_chip.limb_bits,
+        fp_chip.limb_bases[1],
+    );
+    fp_chip.gate().assert_is_const(ctx, &is_lt, &F::ONE);
+    x_fp
+}
+
+// How `compute_root` completes a level with an odd number of nodes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MerklePadding {
+    // the leaves are padded with zeros up to the next power of two
+    Zero,
+    // the last node of an odd level is hashed with itself
+    DuplicateLast,
+}
+
+// Hashes `num_levels` levels of the tree starting from `nodes`, completing odd levels according to `padding`.
+// A level with a single node is also completed, so a subtree is always hashed up to the requested height.
+fn hash_levels<F: BigPrimeField, const T: usize, const RATE: usize>(
+    ctx: &mut Context<F>,
+    poseidon_chip: &PoseidonHasher<F, T, RATE>,
+    gate_chip: &GateChip<F>,
+    mut nodes: Vec<AssignedValue<F>>,
+    num_levels: usize,
+    padding: MerklePadding,
+) -> Vec<AssignedValue<F>> {
+    for _ in 0..num_levels {
+        if nodes.len() % 2 == 1 {
+            let last = match padding {
+                MerklePadding::Zero => ctx.load_zero(),
+                MerklePadding::DuplicateLast => nodes[nodes.len() - 1],
+            };
+            nodes.push(last);
+        }
+        nodes =
+            nodes.chunks(2).map(|pair| poseidon_chip.hash_fix_len_array(ctx, gate_chip, pair)).collect();
+    }
+    nodes
+}
+
+// Height of the tree over `num_leaves` leaves
+fn tree_height(num_leaves: usize) -> usize {
+    num_leaves.next_power_of_two().trailing_zeros() as usize
+}
+
+// Position of a leaf from its path directions, where index[i] is true if the node at level i is a left child
+pub fn leaf_index<F: BigPrimeField>(index: &[bool]) -> F {
+    index.iter().rev().fold(F::ZERO, |acc, is_left| acc + acc + F::from(!is_left))
+}
+
 pub struct MerkleTreeChip<'chip, F: BigPrimeField, const T: usize, const RATE: usize> {
     pub poseidon_chip: &'chip PoseidonHasher<F, T, RATE>,
     pub gate_chip: GateChip<F>,
@@ -43,27 +148,39 @@ impl<'chip, F: BigPrimeField, const T: usize, const RATE: usize> MerkleTreeChip<
         root: F,
         merkle_info: MerkleInfo<F>,
     ) -> AssignedValue<F> {
+        self.merkle_tree_verify_one_with_index(ctx, root, merkle_info).0
+    }
+
+    // Same as `merkle_tree_verify_one`, also returning the assigned leaf index so that it can be made public
+    pub fn merkle_tree_verify_one_with_index(
+        &self,
+        ctx: &mut Context<F>,
+        root: F,
+        merkle_info: MerkleInfo<F>,
+    ) -> (AssignedValue<F>, AssignedValue<F>) {
         let MerkleInfo { leaf, path, index } = merkle_info;
-        println!("- Merkle: root: {:?}, leaf: {:?}, path: {:?}, index: {:?}", root, leaf, path, index);
 
         let x = ctx.load_witness(leaf);
+        let poseidon =self.poseidon_chip.hash_fix_len_array(ctx, &self.gate_chip, &[x]);
+        let leaf_index = ctx.load_witness(leaf_index(&index));
+        let result = self.merkle_tree_verify_index(ctx, root, poseidon, &path, leaf_index);
+        (result, leaf_index)
+    }
 
+    // Same as `merkle_tree_verify_one`, but constrains the path to be valid instead of returning a boolean.
+    // The `assert_*` variants return the assigned root so that it can be made public.
+    pub fn assert_merkle_tree_verify_one(
+        &self,
+        ctx: &mut Context<F>,
+        root: F,
+        merkle_info: MerkleInfo<F>,
+    ) -> AssignedValue<F> {
+        let MerkleInfo { leaf, path, index } = merkle_info;
+
+        let x = ctx.load_witness(leaf);
         let poseidon =self.poseidon_chip.hash_fix_len_array(ctx, &self.gate_chip, &[x]);
-        let path = path.iter().map(|x| ctx.load_witness(*x)).collect::<Vec<_>>();
-        let mut hash = poseidon;
-        println!("- Merkle: hash: {:?}", hash.value());
-        for i in 0..path.len() {
-            let path_i = path[i];
-            let mut inputs = [path_i, hash];
-            if index[i]{inputs.reverse();}
-            println!("- Merkle: inputs: {:?}", inputs.iter().map(|x| x.value()).collect::<Vec<_>>());
-            let _hash = self.poseidon_chip.hash_fix_len_array(ctx, &self.gate_chip, &inputs); 
-            hash = _hash;
-            println!("- Merkle: hash: {:?}", hash.value());
-        }
-        let root = ctx.load_witness(root);
-        let result = self.gate_chip.is_equal(ctx, hash, root);
-        result
+        let leaf_index = ctx.load_witness(leaf_index(&index));
+        self.assert_merkle_tree_verify_index(ctx, root, poseidon, &path, leaf_index)
     }
 
     pub fn merkle_tree_verify_batch(
@@ -72,31 +189,505 @@ impl<'chip, F: BigPrimeField, const T: usize, const RATE: usize> MerkleTreeChip<
         root: F,
         merkle_infos: &[MerkleInfo<F>],
     ) -> AssignedValue<F> {
+        self.merkle_tree_verify_batch_public(ctx, root, merkle_infos).result
+    }
+
+    // Same as `merkle_tree_verify_batch`, returning the result with the assigned root as a public statement
+    pub fn merkle_tree_verify_batch_public(
+        &self,
+        ctx: &mut Context<F>,
+        root: F,
+        merkle_infos: &[MerkleInfo<F>],
+    ) -> PublicStatement<F> {
+        let hashes = self.batch_roots(ctx, merkle_infos);
+        let root = ctx.load_witness(root);
+        let mut result = self.gate_chip.is_equal(ctx, hashes[0], root);
+        for i in 1..hashes.len() {
+            let _result = self.gate_chip.is_equal(ctx, hashes[i], root);
+            result = self.gate_chip.and(ctx, result, _result);
+        }
+        PublicStatement { root: Some(root), ..PublicStatement::new(result) }
+    }
+
+    // Same as `merkle_tree_verify_batch`, but constrains every path to be valid
+    pub fn assert_merkle_tree_verify_batch(
+        &self,
+        ctx: &mut Context<F>,
+        root: F,
+        merkle_infos: &[MerkleInfo<F>],
+    ) -> AssignedValue<F> {
+        let hashes = self.batch_roots(ctx, merkle_infos);
+        let root = ctx.load_witness(root);
+        for hash in hashes.iter() {
+            ctx.constrain_equal(hash, &root);
+        }
+        root
+    }
+
+    // Roots computed from the leaf nodes Poseidon([leaf, 0]) of a batch
+    fn batch_roots(&self, ctx: &mut Context<F>, merkle_infos: &[MerkleInfo<F>]) -> Vec<AssignedValue<F>> {
+        assert!(!merkle_infos.is_empty(), "merkle_infos must not be empty");
         let mut hashes = Vec::new();
         for merkle_info in merkle_infos.iter() {
             let MerkleInfo { leaf, path, index } = merkle_info;
             let x = ctx.load_witness(*leaf);
             let zero = ctx.load_witness(F::ZERO);
             let poseidon =self.poseidon_chip.hash_fix_len_array(ctx, &self.gate_chip, &[x,zero]);
-            let path = path.iter().map(|x| ctx.load_witness(*x)).collect::<Vec<_>>();
-            let mut hash = poseidon;
-            for i in 0..path.len() {
-                let path_i = path[i];
-                let mut inputs = [path_i, hash];
-                if index[i]{inputs.reverse();}
-                let _hash = self.poseidon_chip.hash_fix_len_array(ctx, &self.gate_chip, &inputs); 
-                hash = _hash;
-            }
+            let hash = self.merkle_root_from_leaf(ctx, poseidon, path, index);
             hashes.push(hash);
         }
+        hashes
+    }
+
+    // Checks the path of the leaf node at the assigned `leaf_index` against `root`, see `merkle_root_from_index`
+    pub fn merkle_tree_verify_index(
+        &self,
+        ctx: &mut Context<F>,
+        root: F,
+        leaf: AssignedValue<F>,
+        path: &[F],
+        leaf_index: AssignedValue<F>,
+    ) -> AssignedValue<F> {
+        let computed_root = self.merkle_root_from_index(ctx, leaf, path, leaf_index);
         let root = ctx.load_witness(root);
-        let mut result = self.gate_chip.is_equal(ctx, hashes[0], root);
-        for i in 1..hashes.len() {
-            let _result = self.gate_chip.is_equal(ctx, hashes[i], root);
-            result = self.gate_chip.and(ctx, result, _result);
+        self.gate_chip.is_equal(ctx, computed_root, root)
+    }
+
+    // Same as `merkle_tree_verify_index`, but constrains the path to be valid
+    pub fn assert_merkle_tree_verify_index(
+        &self,
+        ctx: &mut Context<F>,
+        root: F,
+        leaf: AssignedValue<F>,
+        path: &[F],
+        leaf_index: AssignedValue<F>,
+    ) -> AssignedValue<F> {
+        let computed_root = self.merkle_root_from_index(ctx, leaf, path, leaf_index);
+        let root = ctx.load_witness(root);
+        ctx.constrain_equal(&computed_root, &root);
+        root
+    }
+
+    // Leaf for a key in G1: Poseidon over all CRT limbs of its coordinates.
+    // Hashing the same assigned point that goes into the pairing binds the leaf to the key that is verified.
+    pub fn g1_leaf(
+        &self,
+        ctx: &mut Context<F>,
+        fp_chip: &FpChip<F>,
+        pt: &EcPoint<F, FpPoint<F>>,
+    ) -> AssignedValue<F> {
+        let limbs = g1_limbs(fp_chip, ctx, pt);
+        self.poseidon_chip.hash_fix_len_array(ctx, &self.gate_chip, &limbs)
+    }
+
+    // Leaf for a key in G2: Poseidon over all CRT limbs of its coordinates
+    pub fn g2_leaf(
+        &self,
+        ctx: &mut Context<F>,
+        fp_chip: &FpChip<F>,
+        pt: &EcPoint<F, FqPoint<F>>,
+    ) -> AssignedValue<F> {
+        let limbs = g2_limbs(fp_chip, ctx, pt);
+        self.poseidon_chip.hash_fix_len_array(ctx, &self.gate_chip, &limbs)
+    }
+
+    // Computes the root from an already assigned leaf node and its authentication path,
+    // where index[i] is true if the node at level i is a left child.
+    // The directions are only used to assign the leaf index, see `merkle_root_from_index`.
+    pub fn merkle_root_from_leaf(
+        &self,
+        ctx: &mut Context<F>,
+        leaf: AssignedValue<F>,
+        path: &[F],
+        index: &[bool],
+    ) -> AssignedValue<F> {
+        assert!(path.len() == index.len(), "path and index must be the same length");
+        let leaf_index = ctx.load_witness(leaf_index(index));
+        self.merkle_root_from_index(ctx, leaf, path, leaf_index)
+    }
+
+    // Computes the root from an assigned leaf node at position `leaf_index`.
+    // Bit i of the index is 1 iff the node at level i is a right child, so the order of each pair
+    // is constrained with `select` and the circuit does not depend on the position of the leaf.
+    // The index is range checked to path.len() bits by the decomposition.
+    pub fn merkle_root_from_index(
+        &self,
+        ctx: &mut Context<F>,
+        leaf: AssignedValue<F>,
+        path: &[F],
+        leaf_index: AssignedValue<F>,
+    ) -> AssignedValue<F> {
+        let bits = self.gate_chip.num_to_bits(ctx, leaf_index, path.len());
+        let path = ctx.assign_witnesses(path.iter().copied());
+        self.merkle_root_from_bits(ctx, leaf, &path, &bits)
+    }
+
+    // Computes the root from an assigned leaf node and assigned path, where bits[i] is 1 iff the node at level i
+    // is a right child. The bits are assumed to be constrained to be boolean, e.g. by `num_to_bits`.
+    pub fn merkle_root_from_bits(
+        &self,
+        ctx: &mut Context<F>,
+        leaf: AssignedValue<F>,
+        path: &[AssignedValue<F>],
+        bits: &[AssignedValue<F>],
+    ) -> AssignedValue<F> {
+        assert!(path.len() == bits.len(), "path and bits must be the same length");
+        let mut hash = leaf;
+        for (sibling, is_right) in path.iter().zip(bits.iter()) {
+            let left = self.gate_chip.select(ctx, *sibling, hash, *is_right);
+            let right = self.gate_chip.select(ctx, hash, *sibling, *is_right);
+            hash = self.poseidon_chip.hash_fix_len_array(ctx, &self.gate_chip, &[left, right]);
         }
-        result
-        
+        hash
     }
 
+    // Computes the root from an assigned leaf at position `leaf_index` in a tree of assigned height `depth`,
+    // where `path` is padded to `MAX_DEPTH` siblings. Steps at levels >= depth pass the running hash through,
+    // so the result is the node at level `depth`. Returns (root, is_valid), where is_valid is 1 iff
+    // depth <= MAX_DEPTH and leaf_index < 2^depth.
+    pub fn merkle_root_var_depth<const MAX_DEPTH: usize>(
+        &self,
+        ctx: &mut Context<F>,
+        leaf: AssignedValue<F>,
+        path: &[F],
+        leaf_index: AssignedValue<F>,
+        depth: AssignedValue<F>,
+    ) -> (AssignedValue<F>, AssignedValue<F>) {
+        assert!(path.len() == MAX_DEPTH, "path must be padded to MAX_DEPTH");
+        self.merkle_root_bounded_depth(ctx, leaf, path, leaf_index, depth)
+    }
+
+    // `merkle_root_var_depth` with the maximum depth given by the length of `path`
+    pub(crate) fn merkle_root_bounded_depth(
+        &self,
+        ctx: &mut Context<F>,
+        leaf: AssignedValue<F>,
+        path: &[F],
+        leaf_index: AssignedValue<F>,
+        depth: AssignedValue<F>,
+    ) -> (AssignedValue<F>, AssignedValue<F>) {
+        let gate = &self.gate_chip;
+        let max_depth = path.len();
+        if max_depth == 0 {
+            let is_empty = gate.is_zero(ctx, depth);
+            let is_first = gate.is_zero(ctx, leaf_index);
+            return (leaf, gate.and(ctx, is_empty, is_first));
+        }
+        // past_depth[i] is 1 iff i >= depth, the last partial sum is 1 iff depth <= max_depth
+        let indicator = gate.idx_to_indicator(ctx, depth, max_depth + 1);
+        let mut past_depth = gate.partial_sums(ctx, indicator).collect::<Vec<_>>();
+        let in_range = past_depth.pop().unwrap();
+
+        let bits = gate.num_to_bits(ctx, leaf_index, max_depth);
+        let path = ctx.assign_witnesses(path.iter().copied());
+        let mut hash = leaf;
+        for ((sibling, is_right), is_past) in path.iter().zip(bits.iter()).zip(past_depth.iter()) {
+            let left = gate.select(ctx, *sibling, hash, *is_right);
+            let right = gate.select(ctx, hash, *sibling, *is_right);
+            let parent = self.poseidon_chip.hash_fix_len_array(ctx, gate, &[left, right]);
+            hash = gate.select(ctx, hash, parent, *is_past);
+        }
+
+        // the index bits above the depth must be zero, otherwise several indices would reach the same leaf
+        let high_bits = gate.inner_product(ctx, bits, past_depth.into_iter().map(|b| b.into()));
+        let index_in_range = gate.is_zero(ctx, high_bits);
+        (hash, gate.and(ctx, in_range, index_in_range))
+    }
+
+    // Checks a variable depth path against `root`, see `merkle_root_var_depth`
+    pub fn merkle_tree_verify_var_depth<const MAX_DEPTH: usize>(
+        &self,
+        ctx: &mut Context<F>,
+        root: F,
+        leaf: AssignedValue<F>,
+        path: &[F],
+        leaf_index: AssignedValue<F>,
+        depth: AssignedValue<F>,
+    ) -> AssignedValue<F> {
+        let (computed_root, is_valid) =
+            self.merkle_root_var_depth::<MAX_DEPTH>(ctx, leaf, path, leaf_index, depth);
+        let root = ctx.load_witness(root);
+        let is_root = self.gate_chip.is_equal(ctx, computed_root, root);
+        self.gate_chip.and(ctx, is_root, is_valid)
+    }
+
+    // Same as `merkle_tree_verify_var_depth`, but constrains the path, index and depth to be valid
+    pub fn assert_merkle_tree_verify_var_depth<const MAX_DEPTH: usize>(
+        &self,
+        ctx: &mut Context<F>,
+        root: F,
+        leaf: AssignedValue<F>,
+        path: &[F],
+        leaf_index: AssignedValue<F>,
+        depth: AssignedValue<F>,
+    ) -> AssignedValue<F> {
+        let (computed_root, is_valid) =
+            self.merkle_root_var_depth::<MAX_DEPTH>(ctx, leaf, path, leaf_index, depth);
+        self.gate_chip.assert_is_const(ctx, &is_valid, &F::ONE);
+        let root = ctx.load_witness(root);
+        ctx.constrain_equal(&computed_root, &root);
+        root
+    }
+
+    // Verifies a multiproof against `root`, where the leaves of the proof are the leaf nodes themselves
+    pub fn merkle_tree_verify_multiproof(
+        &self,
+        ctx: &mut Context<F>,
+        root: F,
+        proof: &MerkleMultiProof<F>,
+    ) -> AssignedValue<F> {
+        let leaves = ctx.assign_witnesses(proof.leaves.iter().copied());
+        let computed_root =
+            self.merkle_multiproof_root(ctx, &leaves, &proof.indices, &proof.siblings, proof.depth);
+        let root = ctx.load_witness(root);
+        self.gate_chip.is_equal(ctx, computed_root, root)
+    }
+
+    // Same as `merkle_tree_verify_multiproof`, but constrains the proof to be valid
+    pub fn assert_merkle_tree_verify_multiproof(
+        &self,
+        ctx: &mut Context<F>,
+        root: F,
+        proof: &MerkleMultiProof<F>,
+    ) -> AssignedValue<F> {
+        let leaves = ctx.assign_witnesses(proof.leaves.iter().copied());
+        let computed_root =
+            self.merkle_multiproof_root(ctx, &leaves, &proof.indices, &proof.siblings, proof.depth);
+        let root = ctx.load_witness(root);
+        ctx.constrain_equal(&computed_root, &root);
+        root
+    }
+
+    // Computes the root from assigned leaf nodes at the sorted positions `indices`, hashing every internal node
+    // on the union of their paths exactly once. Nodes that are not on the union are taken from `siblings`.
+    // The positions are part of the circuit shape, i.e. one circuit serves one set of indices.
+    pub fn merkle_multiproof_root(
+        &self,
+        ctx: &mut Context<F>,
+        leaves: &[AssignedValue<F>],
+        indices: &[usize],
+        siblings: &[F],
+        depth: usize,
+    ) -> AssignedValue<F> {
+        assert!(leaves.len() == indices.len(), "leaves and indices must be the same length");
+        assert!(!leaves.is_empty(), "leaves must not be empty");
+        assert!(indices.windows(2).all(|w| w[0] < w[1]), "indices must be sorted and distinct");
+        assert!(indices[indices.len() - 1] < 1 << depth, "index out of range");
+
+        let mut siblings = siblings.iter();
+        let mut nodes = indices.iter().copied().zip(leaves.iter().copied()).collect::<Vec<_>>();
+        for _ in 0..depth {
+            let mut next = Vec::with_capacity(nodes.len());
+            let mut i = 0;
+            while i < nodes.len() {
+                let (pos, node) = nodes[i];
+                let inputs = if pos % 2 == 0 && i + 1 < nodes.len() && nodes[i + 1].0 == pos + 1 {
+                    i += 1;
+                    [node, nodes[i].1]
+                } else {
+                    let sibling = siblings.next().expect("not enough siblings in multiproof");
+                    let sibling = ctx.load_witness(*sibling);
+                    if pos % 2 == 0 {
+                        [node, sibling]
+                    } else {
+                        [sibling, node]
+                    }
+                };
+                next.push((pos / 2, self.poseidon_chip.hash_fix_len_array(ctx, &self.gate_chip, &inputs)));
+                i += 1;
+            }
+            nodes = next;
+        }
+        assert!(siblings.next().is_none(), "too many siblings in multiproof");
+        nodes[0].1
+    }
+
+    // Computes the root of the tree with `leaves` as leaf nodes, padded with zeros to a power of two
+    pub fn compute_root(
+        &self,
+        ctx: &mut Context<F>,
+        leaves: &[AssignedValue<F>],
+    ) -> AssignedValue<F> {
+        self.compute_root_with_padding(ctx, leaves, MerklePadding::Zero)
+    }
+
+    // Computes the root of the tree with `leaves` as leaf nodes, level by level in a single context
+    pub fn compute_root_with_padding(
+        &self,
+        ctx: &mut Context<F>,
+        leaves: &[AssignedValue<F>],
+        padding: MerklePadding,
+    ) -> AssignedValue<F> {
+        assert!(!leaves.is_empty(), "leaves must not be empty");
+        let nodes = self.pad_leaves(ctx, leaves, padding);
+        let height = tree_height(leaves.len());
+        hash_levels(ctx, self.poseidon_chip, &self.gate_chip, nodes, height, padding)[0]
+    }
+
+    // Same as `compute_root_with_padding`, with the subtrees of height `subtree_height` hashed in parallel,
+    // each in a new context of `pool`, and the levels above them in `pool.main()`.
+    // Returns the same root as the sequential version for any `subtree_height`.
+    pub fn compute_root_parallel(
+        &self,
+        pool: &mut SinglePhaseCoreManager<F>,
+        leaves: &[AssignedValue<F>],
+        padding: MerklePadding,
+        subtree_height: usize,
+    ) -> AssignedValue<F> {
+        assert!(!leaves.is_empty(), "leaves must not be empty");
+        let nodes = self.pad_leaves(pool.main(), leaves, padding);
+        let height = tree_height(leaves.len());
+        let subtree_height = subtree_height.min(height);
+
+        // `PoseidonHasher` is not `Sync`, so every subtree gets its own copy with the same constants
+        let gate_chip = &self.gate_chip;
+        let subtrees = nodes
+            .chunks(1 << subtree_height)
+            .map(|chunk| (self.poseidon_chip.clone(), chunk.to_vec()))
+            .collect();
+        let subtree_roots = parallelize_core(pool, subtrees, |ctx, (poseidon_chip, chunk)| {
+            hash_levels(ctx, &poseidon_chip, gate_chip, chunk, subtree_height, padding)[0]
+        });
+
+        let ctx = pool.main();
+        let num_levels = height - subtree_height;
+        hash_levels(ctx, self.poseidon_chip, gate_chip, subtree_roots, num_levels, padding)[0]
+    }
+
+    fn pad_leaves(
+        &self,
+        ctx: &mut Context<F>,
+        leaves: &[AssignedValue<F>],
+        padding: MerklePadding,
+    ) -> Vec<AssignedValue<F>> {
+        let mut nodes = leaves.to_vec();
+        if padding == MerklePadding::Zero {
+            let zero = ctx.load_zero();
+            nodes.resize(leaves.len().next_power_of_two(), zero);
+        }
+        nodes
+    }
+}
+
+// Native counterparts of the leaf encodings, `compute_root` and `merkle_root_from_leaf` with Poseidon<Fr, 3, 2>
+pub mod native {
+    use super::MerklePadding;
+    use crate::bn254::{MerkleInfo, MerkleMultiProof};
+    use crate::halo2_proofs::halo2curves::bn256::{Fq, Fr, G1Affine, G2Affine};
+    use halo2_base::utils::{decompose_biguint, fe_to_biguint};
+    use poseidon::Poseidon;
+
+    pub fn coordinate_limbs(coordinates: &[Fq], limb_bits: usize, num_limbs: usize) -> Vec<Fr> {
+        coordinates
+            .iter()
+            .flat_map(|a| decompose_biguint::<Fr>(&fe_to_biguint(a), num_limbs, limb_bits))
+            .collect()
+    }
+
+    pub fn g1_limbs(pt: &G1Affine, limb_bits: usize, num_limbs: usize) -> Vec<Fr> {
+        coordinate_limbs(&[pt.x, pt.y], limb_bits, num_limbs)
+    }
+
+    pub fn g2_limbs(pt: &G2Affine, limb_bits: usize, num_limbs: usize) -> Vec<Fr> {
+        coordinate_limbs(&[pt.x.c0, pt.x.c1, pt.y.c0, pt.y.c1], limb_bits, num_limbs)
+    }
+
+    pub fn poseidon_hash(inputs: &[Fr]) -> Fr {
+        let mut sponge = Poseidon::<Fr, 3, 2>::new(8, 57);
+        sponge.update(inputs);
+        sponge.squeeze()
+    }
+
+    pub fn g1_leaf(pt: &G1Affine, limb_bits: usize, num_limbs: usize) -> Fr {
+        poseidon_hash(&g1_limbs(pt, limb_bits, num_limbs))
+    }
+
+    pub fn g2_leaf(pt: &G2Affine, limb_bits: usize, num_limbs: usize) -> Fr {
+        poseidon_hash(&g2_limbs(pt, limb_bits, num_limbs))
+    }
+
+    pub fn hash_pair(left: Fr, right: Fr) -> Fr {
+        poseidon_hash(&[left, right])
+    }
+
+    // All levels of the tree, from the zero padded leaves up to the root
+    pub fn merkle_tree(leaves: &[Fr]) -> Vec<Vec<Fr>> {
+        assert!(!leaves.is_empty(), "leaves must not be empty");
+        let mut nodes = leaves.to_vec();
+        nodes.resize(leaves.len().next_power_of_two(), Fr::zero());
+        let mut levels = vec![nodes];
+        while levels.last().unwrap().len() > 1 {
+            let next =
+                levels.last().unwrap().chunks(2).map(|pair| hash_pair(pair[0], pair[1])).collect();
+            levels.push(next);
+        }
+        levels
+    }
+
+    pub fn merkle_root(leaves: &[Fr]) -> Fr {
+        merkle_tree(leaves).last().unwrap()[0]
+    }
+
+    pub fn merkle_root_with_padding(leaves: &[Fr], padding: MerklePadding) -> Fr {
+        match padding {
+            MerklePadding::Zero => merkle_root(leaves),
+            MerklePadding::DuplicateLast => {
+                assert!(!leaves.is_empty(), "leaves must not be empty");
+                let mut nodes = leaves.to_vec();
+                while nodes.len() > 1 {
+                    if nodes.len() % 2 == 1 {
+                        nodes.push(nodes[nodes.len() - 1]);
+                    }
+                    nodes = nodes.chunks(2).map(|pair| hash_pair(pair[0], pair[1])).collect();
+                }
+                nodes[0]
+            }
+        }
+    }
+
+    // Authentication path of leaves[index]
+    pub fn merkle_info(leaves: &[Fr], index: usize) -> MerkleInfo<Fr> {
+        let levels = merkle_tree(leaves);
+        let mut path = Vec::new();
+        let mut is_left = Vec::new();
+        let mut i = index;
+        for level in levels.iter().take(levels.len() - 1) {
+            path.push(level[i ^ 1]);
+            is_left.push(i % 2 == 0);
+            i /= 2;
+        }
+        MerkleInfo { leaf: leaves[index], path, index: is_left }
+    }
+
+    // Multiproof for the leaves at the sorted and distinct positions `indices`, see `merkle_multiproof_root`
+    pub fn merkle_multiproof(leaves: &[Fr], indices: &[usize]) -> MerkleMultiProof<Fr> {
+        assert!(!indices.is_empty(), "indices must not be empty");
+        assert!(indices.windows(2).all(|w| w[0] < w[1]), "indices must be sorted and distinct");
+        assert!(indices[indices.len() - 1] < leaves.len(), "index out of range");
+
+        let levels = merkle_tree(leaves);
+        let mut siblings = Vec::new();
+        let mut positions = indices.to_vec();
+        for level in levels.iter().take(levels.len() - 1) {
+            let mut next = Vec::with_capacity(positions.len());
+            let mut i = 0;
+            while i < positions.len() {
+                let pos = positions[i];
+                if pos % 2 == 0 && i + 1 < positions.len() && positions[i + 1] == pos + 1 {
+                    i += 1;
+                } else {
+                    siblings.push(level[pos ^ 1]);
+                }
+                next.push(pos / 2);
+                i += 1;
+            }
+            positions = next;
+        }
+        MerkleMultiProof {
+            leaves: indices.iter().map(|i| leaves[*i]).collect(),
+            indices: indices.to_vec(),
+            siblings,
+            depth: levels.len() - 1,
+        }
+    }
 }
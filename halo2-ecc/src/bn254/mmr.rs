@@ -0,0 +1,232 @@
+#![allow(non_snake_case)]
+
+use super::merkle_tree::MerkleTreeChip;
+use halo2_base::gates::GateInstructions;
+use halo2_base::utils::BigPrimeField;
+use halo2_base::QuantumCell::Constant;
+use halo2_base::{AssignedValue, Context};
+
+// Merkle mountain range over Poseidon: the leaves are split into perfect binary trees ("peaks"), one for each
+// set bit of the number of leaves `size`, from the highest to the lowest. Internal nodes are Poseidon([left, right])
+// as in `MerkleTreeChip`, and the root is Poseidon([size, bag]) where the peaks are bagged from the right,
+// bag = Poseidon([peak_0, Poseidon([peak_1, ... peak_last])]).
+// The size of the MMR is part of the circuit shape, the position of a leaf is not.
+
+// Inclusion proof for a leaf: the path inside its peak padded with zeros to the height of the highest peak,
+// and all peaks of the MMR
+#[derive(Clone, Debug)]
+pub struct MmrProof<F: BigPrimeField> {
+    pub leaf_index: u64,
+    pub path: Vec<F>,
+    pub peaks: Vec<F>,
+}
+
+// Heights of the peaks of an MMR with `size` leaves, from the highest to the lowest
+pub fn peak_heights(size: usize) -> Vec<usize> {
+    (0..usize::BITS as usize).rev().filter(|j| (size >> j) & 1 == 1).collect()
+}
+
+// Height of the highest peak, i.e. the length of the padded path of an inclusion proof
+pub fn max_height(size: usize) -> usize {
+    assert!(size > 0, "size must be positive");
+    (usize::BITS - size.leading_zeros() - 1) as usize
+}
+
+pub struct MmrChip<'chip, F: BigPrimeField, const T: usize, const RATE: usize> {
+    pub merkle_chip: MerkleTreeChip<'chip, F, T, RATE>,
+}
+
+impl<'chip, F: BigPrimeField, const T: usize, const RATE: usize> MmrChip<'chip, F, T, RATE> {
+    pub fn new(merkle_chip: MerkleTreeChip<'chip, F, T, RATE>) -> Self {
+        Self { merkle_chip }
+    }
+
+    fn hash(&self, ctx: &mut Context<F>, inputs: &[AssignedValue<F>]) -> AssignedValue<F> {
+        let merkle_chip = &self.merkle_chip;
+        merkle_chip.poseidon_chip.hash_fix_len_array(ctx, &merkle_chip.gate_chip, inputs)
+    }
+
+    // Root of an MMR with `size` leaves from its peaks
+    pub fn bag_peaks(
+        &self,
+        ctx: &mut Context<F>,
+        size: usize,
+        peaks: &[AssignedValue<F>],
+    ) -> AssignedValue<F> {
+        assert!(peaks.len() == peak_heights(size).len(), "wrong number of peaks for size");
+        let mut bag = peaks[peaks.len() - 1];
+        for peak in peaks.iter().rev().skip(1) {
+            bag = self.hash(ctx, &[*peak, bag]);
+        }
+        let size = ctx.load_constant(F::from(size as u64));
+        self.hash(ctx, &[size, bag])
+    }
+
+    // Checks that `leaf` is the leaf at the assigned position `leaf_index` of the MMR with `root` and `size` leaves.
+    // The peak of the leaf and the height of that peak are derived from the bits of the index: going from the top,
+    // the first bit where the index is 0 and the size is 1 is the height of the peak, and the bits above it
+    // select the offset of the peak.
+    pub fn verify_inclusion(
+        &self,
+        ctx: &mut Context<F>,
+        root: AssignedValue<F>,
+        size: usize,
+        leaf: AssignedValue<F>,
+        leaf_index: AssignedValue<F>,
+        proof: &MmrProof<F>,
+    ) -> AssignedValue<F> {
+        let heights = peak_heights(size);
+        assert!(proof.peaks.len() == heights.len(), "wrong number of peaks for size");
+        assert!(proof.path.len() == max_height(size), "path must be padded to the highest peak");
+        let gate = &self.merkle_chip.gate_chip;
+
+        let num_bits = max_height(size) + 1;
+        let bits = gate.num_to_bits(ctx, leaf_index, num_bits);
+        // is_peak[p] is 1 iff the leaf is in the p-th peak, all zero iff leaf_index >= size
+        let mut is_prefix = ctx.load_constant(F::ONE);
+        let mut is_peak = Vec::with_capacity(heights.len());
+        for j in (0..num_bits).rev() {
+            if (size >> j) & 1 == 1 {
+                is_peak.push(gate.mul_not(ctx, bits[j], is_prefix));
+                is_prefix = gate.mul(ctx, is_prefix, bits[j]);
+            } else {
+                is_prefix = gate.mul_not(ctx, bits[j], is_prefix);
+            }
+        }
+        let in_range = gate.sum(ctx, is_peak.clone());
+
+        let depth = gate.inner_product(
+            ctx,
+            is_peak.clone(),
+            heights.iter().map(|h| Constant(F::from(*h as u64))),
+        );
+        let offset = gate.inner_product(
+            ctx,
+            is_peak.clone(),
+            heights.iter().map(|h| Constant(F::from(((size >> (h + 1)) << (h + 1)) as u64))),
+        );
+        let local_index = gate.sub(ctx, leaf_index, offset);
+
+        let peaks = ctx.assign_witnesses(proof.peaks.iter().copied());
+        let peak = gate.select_by_indicator(ctx, peaks.clone(), is_peak);
+        let (computed_peak, is_valid) =
+            self.merkle_chip.merkle_root_bounded_depth(ctx, leaf, &proof.path, local_index, depth);
+        let is_valid = gate.and(ctx, is_valid, in_range);
+        let is_peak = gate.is_equal(ctx, computed_peak, peak);
+        let is_valid = gate.and(ctx, is_valid, is_peak);
+
+        let computed_root = self.bag_peaks(ctx, size, &peaks);
+        let is_root = gate.is_equal(ctx, computed_root, root);
+        gate.and(ctx, is_valid, is_root)
+    }
+
+    // Appends `leaves` to the MMR with `size` leaves and the given peaks, returns the new peaks.
+    // Peaks of equal height are merged as soon as they appear, as in the native `Mmr::append`.
+    pub fn append(
+        &self,
+        ctx: &mut Context<F>,
+        size: usize,
+        peaks: &[AssignedValue<F>],
+        leaves: &[AssignedValue<F>],
+    ) -> Vec<AssignedValue<F>> {
+        let heights = peak_heights(size);
+        assert!(peaks.len() == heights.len(), "wrong number of peaks for size");
+        let mut stack = heights.into_iter().zip(peaks.iter().copied()).collect::<Vec<_>>();
+        for leaf in leaves.iter() {
+            stack.push((0, *leaf));
+            while stack.len() > 1 && stack[stack.len() - 1].0 == stack[stack.len() - 2].0 {
+                let (height, right) = stack.pop().unwrap();
+                let (_, left) = stack.pop().unwrap();
+                stack.push((height + 1, self.hash(ctx, &[left, right])));
+            }
+        }
+        stack.into_iter().map(|(_, peak)| peak).collect()
+    }
+
+    // Append proof: checks the old peaks against `old_root` and returns (is_valid, new_root),
+    // the root of the MMR with size + leaves.len() leaves
+    pub fn verify_append(
+        &self,
+        ctx: &mut Context<F>,
+        old_root: AssignedValue<F>,
+        size: usize,
+        old_peaks: &[AssignedValue<F>],
+        leaves: &[AssignedValue<F>],
+    ) -> (AssignedValue<F>, AssignedValue<F>) {
+        let computed_root = self.bag_peaks(ctx, size, old_peaks);
+        let is_valid = self.merkle_chip.gate_chip.is_equal(ctx, computed_root, old_root);
+        let new_peaks = self.append(ctx, size, old_peaks, leaves);
+        let new_root = self.bag_peaks(ctx, size + leaves.len(), &new_peaks);
+        (is_valid, new_root)
+    }
+}
+
+// Native MMR with Poseidon<Fr, 3, 2>
+pub mod native {
+    use super::{max_height, peak_heights, MmrProof};
+    use crate::bn254::merkle_tree::native::{hash_pair, merkle_info, merkle_root};
+    use crate::halo2_proofs::halo2curves::bn256::Fr;
+
+    pub fn bag_peaks(size: usize, peaks: &[Fr]) -> Fr {
+        let mut bag = peaks[peaks.len() - 1];
+        for peak in peaks.iter().rev().skip(1) {
+            bag = hash_pair(*peak, bag);
+        }
+        hash_pair(Fr::from(size as u64), bag)
+    }
+
+    #[derive(Clone, Debug, Default)]
+    pub struct Mmr {
+        pub leaves: Vec<Fr>,
+    }
+
+    impl Mmr {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn size(&self) -> usize {
+            self.leaves.len()
+        }
+
+        pub fn append(&mut self, leaf: Fr) {
+            self.leaves.push(leaf);
+        }
+
+        // Leaves of each peak, from the highest to the lowest
+        fn peak_leaves(&self) -> Vec<&[Fr]> {
+            let mut offset = 0;
+            peak_heights(self.size())
+                .into_iter()
+                .map(|h| {
+                    let leaves = &self.leaves[offset..offset + (1 << h)];
+                    offset += 1 << h;
+                    leaves
+                })
+                .collect()
+        }
+
+        pub fn peaks(&self) -> Vec<Fr> {
+            self.peak_leaves().into_iter().map(merkle_root).collect()
+        }
+
+        pub fn root(&self) -> Fr {
+            bag_peaks(self.size(), &self.peaks())
+        }
+
+        pub fn proof(&self, leaf_index: usize) -> MmrProof<Fr> {
+            assert!(leaf_index < self.size(), "leaf index out of range");
+            let mut offset = 0;
+            let mut path = Vec::new();
+            for leaves in self.peak_leaves() {
+                if leaf_index < offset + leaves.len() {
+                    path = merkle_info(leaves, leaf_index - offset).path;
+                    break;
+                }
+                offset += leaves.len();
+            }
+            path.resize(max_height(self.size()), Fr::zero());
+            MmrProof { leaf_index: leaf_index as u64, path, peaks: self.peaks() }
+        }
+    }
+}
@@ -4,15 +4,22 @@ use crate::fields::{fp, fp12, fp2};
 use crate::halo2_proofs::halo2curves::bn256::{Fq, Fq12, Fq2};
 use halo2_base::utils::BigPrimeField;
 
+pub mod bls_batch;
 pub mod bls_signature;
 pub mod bls_signature_inverse;
 pub mod final_exp;
+pub mod hash_to_curve;
+pub mod hash_to_g1;
 pub mod pairing;
+pub mod instances;
 pub mod merkle_tree;
+pub mod mmr;
 pub mod combine_bls_mt;
 pub mod shuffle;
 pub mod msp;
 pub mod shuffle2;
+pub mod sparse_merkle_tree;
+pub mod threshold_bls;
 
 #[derive(Clone)]
 pub struct MerkleInfo<F: BigPrimeField>{
@@ -21,6 +28,17 @@ pub struct MerkleInfo<F: BigPrimeField>{
     pub index: Vec<bool>, // true: left, false: right
 }
 
+// Compact proof for several leaves of the same tree of height `depth`:
+// `indices` are sorted and distinct, `siblings` are the nodes that cannot be computed from the leaves,
+// ordered level by level from the leaves up and from left to right within a level
+#[derive(Clone)]
+pub struct MerkleMultiProof<F: BigPrimeField>{
+    pub leaves: Vec<F>,
+    pub indices: Vec<usize>,
+    pub siblings: Vec<F>,
+    pub depth: usize,
+}
+
 
 pub type FpChip<'range, F> = fp::FpChip<'range, F, Fq>;
 pub type FpPoint<F> = ProperCrtUint<F>;
@@ -1,26 +1,40 @@
 #![allow(non_snake_case)]
 
 use super::bls_signature::BlsSignatureChip;
-use super::pairing::PairingChip;
-use super::{Fp12Chip, Fp2Chip, FpChip};
-use crate::bigint::ProperCrtUint;
-use crate::ecc::{scalar_multiply, EcPoint, EccChip};
-use crate::fields::vector::{FieldVector};
-use crate::fields::{fp, fp12, fp2, FieldChip};
-use crate::halo2_proofs::halo2curves::bn256::Fq12;
+use super::bls_signature_inverse::BlsSignatureChip2;
+use super::hash_to_curve::HashToCurveChip;
+use super::hash_to_g1::HashToG1Chip;
+use super::instances::PublicStatement;
+use super::merkle_tree::{g1_limbs, g2_limbs, MerkleTreeChip};
+use super::{Fp2Chip, FpPoint, FqPoint, MerkleInfo};
+use crate::ecc::{EcPoint, EccChip};
+use crate::fields::FieldChip;
 use crate::halo2_proofs::halo2curves::bn256::{G1Affine, G2Affine};
-use halo2_base::gates::{GateChip,GateInstructions};
-use halo2_base::halo2_proofs::halo2curves::bn256::{Fq, Fq2};
+use halo2_base::gates::flex_gate::threads::SinglePhaseCoreManager;
+use halo2_base::gates::{GateChip,GateInstructions, RangeChip, RangeInstructions};
 use halo2_base::poseidon::hasher::PoseidonHasher;
-use halo2_base::utils::BigPrimeField;
-use halo2_base::{AssignedValue, Context};
-use rayon::result;
+use halo2_base::safe_types::{FixLenBytes, VarLenBytes};
+use halo2_base::utils::{biguint_to_fe, fe_to_biguint, modulus, BigPrimeField};
+use halo2_base::{AssignedValue, Context, QuantumCell::Constant};
+use num_bigint::BigUint;
+use num_traits::One;
+
+// Domain separation tags for proofs of possession, distinct from the ones used to sign messages
+pub const DST_POP_G2: &[u8] = b"BLS_POP_BN254G2_XMD:SHA-256_SVDW_RO_POP_";
+pub const DST_POP_G1: &[u8] = b"BLS_POP_BN254G1_POSEIDON_SVDW_";
+
+// Bit length of the challenges e_i used by `MspChip::new`, enough for BDN style aggregation.
+// Challenges of at least `F::NUM_BITS` bits are the full Poseidon outputs.
+pub const CHALLENGE_BITS: usize = 128;
 
 // To avoid issues with mutably borrowing twice (not allowed in Rust), we only store fp_chip and construct g2_chip and fp12_chip in scope when needed for temporary mutable borrows
 pub struct MspChip<'chip, F: BigPrimeField> {
     pub bls_signature_chip: &'chip BlsSignatureChip<'chip, F>,
     pub poseidon_chip: &'chip PoseidonHasher<F, 3, 2>,
     // pub fp_chip: &'chip FpChip<'chip, F>,
+    pub challenge_bits: usize,
+    // hash to G2 under DST_POP_G2, its SHA-256 promises must be bound to the SHA-256 component by the caller
+    pub pop_hash_chip: HashToCurveChip<'chip, F>,
 }
 
 impl<'chip, F: BigPrimeField> MspChip<'chip, F> {
@@ -29,16 +43,28 @@ impl<'chip, F: BigPrimeField> MspChip<'chip, F> {
         poseidon_chip: &'chip PoseidonHasher<F, 3, 2>,
         // fp_chip: &'chip FpChip<F>,
     ) -> Self {
+        Self::new_with_challenge_bits(bls_signature_chip, poseidon_chip, CHALLENGE_BITS)
+    }
+
+    // Same as `new`, with challenges e_i truncated to `challenge_bits` bits
+    pub fn new_with_challenge_bits(
+        bls_signature_chip: &'chip BlsSignatureChip<F>,
+        poseidon_chip: &'chip PoseidonHasher<F, 3, 2>,
+        challenge_bits: usize,
+    ) -> Self {
+        assert!(challenge_bits != 0, "challenges must have at least one bit");
         Self {
             bls_signature_chip,
             poseidon_chip,
             // fp_chip,
+            challenge_bits,
+            pop_hash_chip: HashToCurveChip::new(bls_signature_chip.fp_chip, DST_POP_G2),
         }
     }
 
     pub fn msp_verify(
         &self,
-        ctx: &mut Context<F>,
+        pool: &mut SinglePhaseCoreManager<F>,
         g1: G1Affine,
         signatures: &[G2Affine],
         pubkeys: &[G1Affine], // mvk
@@ -47,99 +73,115 @@ impl<'chip, F: BigPrimeField> MspChip<'chip, F> {
         ivk: G1Affine,
         isig: G2Affine, // \mu
     ) -> AssignedValue<F> {
-        // TODO: verify proof of possesion
+        self.msp_verify_public(pool, g1, signatures, pubkeys, msghash, weighting_seed, ivk, isig).result
+    }
 
-        // A: verify BLS signature
-        let verify_A = self.bls_signature_chip.bls_signature_verify(ctx, g1, signatures, pubkeys, msghash);
+    // Same as `msp_verify`, also returning the statement to expose as public instances:
+    // the Poseidon digest of the CRT limbs of H(m) and the limbs of ivk, see `instances::native::msp_statement`
+    pub fn msp_verify_public(
+        &self,
+        pool: &mut SinglePhaseCoreManager<F>,
+        g1: G1Affine,
+        signatures: &[G2Affine],
+        pubkeys: &[G1Affine], // mvk
+        msghash: G2Affine,
+        weighting_seed : F,
+        ivk: G1Affine,
+        isig: G2Affine, // \mu
+    ) -> PublicStatement<F> {
+        let ctx = pool.main();
+        // H(m) is loaded once so that both pairing checks and the exposed digest are about the same point
+        let hash_m = self.bls_signature_chip.pairing_chip.load_private_g2_checked(ctx, msghash);
+        let mvks = self.load_keys(ctx, pubkeys);
+        self.msp_verify_assigned(pool, g1, signatures, &mvks, hash_m, weighting_seed, ivk, isig)
+    }
 
+    // Same as `msp_verify_public` with the keys mvk_i and H(m) already assigned, so that the caller can
+    // run other checks on the same points, as `msp_verify_with_pop` and `msp_verify_attested` do
+    pub fn msp_verify_assigned(
+        &self,
+        pool: &mut SinglePhaseCoreManager<F>,
+        g1: G1Affine,
+        signatures: &[G2Affine],
+        mvks: &[EcPoint<F, FpPoint<F>>],
+        hash_m: EcPoint<F, FqPoint<F>>,
+        weighting_seed : F,
+        ivk: G1Affine,
+        isig: G2Affine, // \mu
+    ) -> PublicStatement<F> {
+        let ctx = pool.main();
+        // Proofs of possession are not checked here, see `msp_verify_with_pop` and `msp_verify_attested`
+        let merkle_chip = MerkleTreeChip::new(self.poseidon_chip, GateChip::default());
+        let msg_digest = merkle_chip.g2_leaf(ctx, self.bls_signature_chip.fp_chip, &hash_m);
+
+        // The signatures are loaded once and shared by A and B
+        let (sigs, seed_limbs) = self.load_aggregation(ctx, mvks, signatures);
+        let g1_chip = EccChip::new(self.bls_signature_chip.fp_chip);
+        let fp2_chip = Fp2Chip::new(self.bls_signature_chip.fp_chip);
+        let g2_chip = EccChip::new(&fp2_chip);
+
+        // A: verify BLS signature, e(g1, \sum_i sig_i) = e(\sum_i mvk_i, H(m))
+        let mvk_agg = g1_chip.sum::<G1Affine>(ctx, mvks.iter().cloned());
+        let sig_agg = g2_chip.sum::<G2Affine>(ctx, sigs.iter().cloned());
+        let verify_A = self.bls_signature_chip.bls_aggregate_verify_points(ctx, g1, sig_agg, &[mvk_agg], &[hash_m.clone()]);
 
         // B
-        let signatures_x_assigned = signatures.iter().map(|pt| {
-            ctx.load_witness(F::from_bytes_le(&pt.x.c0.to_bytes()))
-        }).collect::<Vec<_>>();
         let gate_chip = GateChip::<F>::default();
-        let weighting_seed_comp = self.poseidon_chip.hash_fix_len_array(ctx, &gate_chip, &signatures_x_assigned[..]);
-        let weighting_seed_assigned = ctx.load_witness(weighting_seed);
+        let (weighting_seed_assigned, weighting_seed_comp) = self.weighting_seed(ctx, &seed_limbs, weighting_seed);
         // B_1 : verify weighting seed
         let verify_B_1 = gate_chip.is_equal(ctx, weighting_seed_assigned, weighting_seed_comp);
-        // e_i = H(i,weighting_seed) for i in 0..n where n is the number of public keys
-        let e_is  = pubkeys.iter().enumerate().map(|(i, _)| {
-            let i_assigned = ctx.load_witness(F::from(i as u64));
-            self.poseidon_chip.hash_fix_len_array(ctx, &gate_chip, &[i_assigned, weighting_seed_assigned])
-        }).collect::<Vec<_>>();
+        let e_is = self.challenges(ctx, mvks.len(), weighting_seed_comp);
 
-        let g1_chip = EccChip::new(self.bls_signature_chip.fp_chip);
-        let fp2_chip = Fp2Chip::new(self.bls_signature_chip.fp_chip);
-        let g2_chip = EccChip::new(&fp2_chip);
         // B_2 : verify ivk, isig
         // ivk = \sum_{i=0}^{n-1} e_i * mvk_i
         let ivk_assigned = self.bls_signature_chip.pairing_chip.load_private_g1(ctx, ivk);
-        let mvks = pubkeys.iter().map(|pt| self.bls_signature_chip.pairing_chip.load_private_g1(ctx, *pt)).collect::<Vec<_>>();
-        let products = mvks.iter().zip(e_is.iter()).map(|(mvk, &e_i)| {
-            let e_vec = vec![e_i];
-            g1_chip.scalar_mult::<G1Affine>(ctx, mvk.clone(), e_vec,254,4)
-        }).collect::<Vec<_>>();
-
-        //println!("ivk_assigned x:{:?}", ivk_assigned.clone().x());
-        //println!("ivk_assigned y:{:?}", ivk_assigned.clone().y());
-        
-        let ivk_comp = g1_chip.sum::<G1Affine>(ctx, products);
-        
-        //println!("ivk_comp x:{:?}", ivk_comp.clone().x());
-        //println!("ivk_comp y:{:?}", ivk_comp.clone().y());
-        let verify_B_2 = g1_chip.is_equal(ctx, ivk_assigned, ivk_comp);
-        
-        
-
+        let agg_key = g1_limbs(self.bls_signature_chip.fp_chip, ctx, &ivk_assigned);
+        let isig_assigned = self.bls_signature_chip.pairing_chip.load_private_g2_checked(ctx, isig);
+        // each aggregate is a single MSM, which may use new threads of `pool`
+        let ivk_comp = self.combine_g1(pool, mvks, &e_is);
+        let isig_comp = self.combine_g2(pool, &sigs, &e_is);
+        let ctx = pool.main();
+        let verify_B_2 = g1_chip.is_equal(ctx, ivk_assigned.clone(), ivk_comp);
 
         // isig = \sum_{i=0}^{n-1} e_i * sig_i
-        let isig_assigned = self.bls_signature_chip.pairing_chip.load_private_g2(ctx, isig);
-        let sigs = signatures.iter().map(|pt| self.bls_signature_chip.pairing_chip.load_private_g2(ctx, *pt)).collect::<Vec<_>>();
-        let products = sigs.iter().zip(e_is.iter()).map(|(sig, &e_i)| {
-            let e_vec = vec![e_i];
-            g2_chip.scalar_mult::<G2Affine>(ctx, sig.clone(), e_vec,254,4)
-        }).collect::<Vec<_>>();
-        let isig_comp = g2_chip.sum::<G2Affine>(ctx, products);
-
-        //println!("{:?}",isig_assigned);
-        //println!("{:?}",isig_comp);
-
-
-        let verify_B_3 = g2_chip.is_equal(ctx, isig_assigned, isig_comp);
+        let verify_B_3 = g2_chip.is_equal(ctx, isig_assigned.clone(), isig_comp);
         
 
-        // B_4 : verify e(g1, isig) = e(ivk, H(m))
-        let verify_B_4 = self.bls_signature_chip.bls_signature_verify(ctx, g1, &[isig], &[ivk], msghash);
-
-        //let verify_B_5 = self.bls_signature_chip.bls_signature_verify(ctx, g1, &[isig_comp],&[ivk_comp], msghash);
+        // B_4 : verify e(g1, isig) = e(ivk, H(m)) on the same ivk, isig as B_2 and B_3
+        let verify_B_4 = self.bls_signature_chip.bls_aggregate_verify_points(ctx, g1, isig_assigned, &[ivk_assigned], &[hash_m]);
 
-
-        //println!("verify_A: {:?}", verify_A);
-        //println!("verify_B_1: {:?}", verify_B_1);
-        println!("verify_B_2: {:?}", verify_B_2);
-        println!("verify_B_3: {:?}", verify_B_3);
-        println!("verify_B_4: {:?}", verify_B_4);
         // Final result
-        // let result1 = gate_chip.and(ctx, verify_A, verify_B_1);
-        // println!("result1: {:?}", result1);
-        // let result2 = gate_chip.and(ctx, verify_B_2, verify_B_3);
-        // println!("result2: {:?}", result2);
-        // let result = gate_chip.and(ctx, result1, result2);
-        // println!("result: {:?}", result);
-        // let result = gate_chip.and(ctx, result, verify_B_4);
-        // println!("result: {:?}", result);
-    
         let result1 = gate_chip.and(ctx, verify_A, verify_B_1);
         let result2 = gate_chip.and(ctx, verify_B_2, verify_B_3);
         let result = gate_chip.and(ctx, result1, result2);
         let result = gate_chip.and(ctx, result, verify_B_4);
-        result
+        PublicStatement { msg_digest: Some(msg_digest), agg_key, ..PublicStatement::new(result) }
+    }
+
+    // Same as `msp_verify`, but H(m) is computed in circuit from the message bytes
+    // with `hash_to_curve_chip`, whose SHA-256 promises the caller must bind to the SHA-256 component
+    pub fn msp_verify_msg<const MAX_LEN: usize>(
+        &self,
+        pool: &mut SinglePhaseCoreManager<F>,
+        g1: G1Affine,
+        signatures: &[G2Affine],
+        pubkeys: &[G1Affine], // mvk
+        msg: &VarLenBytes<F, MAX_LEN>,
+        hash_to_curve_chip: &HashToCurveChip<F>,
+        weighting_seed: F,
+        ivk: G1Affine,
+        isig: G2Affine, // \mu
+    ) -> AssignedValue<F> {
+        let ctx = pool.main();
+        let hash_m = hash_to_curve_chip.hash_to_g2_var_len(ctx, msg);
+        let mvks = self.load_keys(ctx, pubkeys);
+        self.msp_verify_assigned(pool, g1, signatures, &mvks, hash_m, weighting_seed, ivk, isig).result
     }
 
 
     pub fn msp_verify_2(
         &self,
-        ctx: &mut Context<F>,
+        pool: &mut SinglePhaseCoreManager<F>,
         g2: G2Affine,
         signatures: &[G1Affine],
         pubkeys: &[G2Affine], // mvk
@@ -148,58 +190,744 @@ impl<'chip, F: BigPrimeField> MspChip<'chip, F> {
         ivk: G2Affine,
         isig: G1Affine, // \mu
     ) -> AssignedValue<F> {
-        // A : verify BLS signature
-        let verify_A = self.bls_signature_chip.bls_signature_verify_2(ctx, g2, signatures, pubkeys, msghash);
+        self.msp_verify_2_public(pool, g2, signatures, pubkeys, msghash, weighting_seed, ivk, isig).result
+    }
+
+    // Same as `msp_verify_2`, also returning the statement to expose as public instances,
+    // see `instances::native::msp_statement_2`
+    pub fn msp_verify_2_public(
+        &self,
+        pool: &mut SinglePhaseCoreManager<F>,
+        g2: G2Affine,
+        signatures: &[G1Affine],
+        pubkeys: &[G2Affine], // mvk
+        msghash: G1Affine,
+        weighting_seed : F,
+        ivk: G2Affine,
+        isig: G1Affine, // \mu
+    ) -> PublicStatement<F> {
+        let ctx = pool.main();
+        let hash_m = self.bls_signature_chip.pairing_chip.load_private_g1(ctx, msghash);
+        let mvks = self.load_keys_2(ctx, pubkeys);
+        self.msp_verify_2_assigned(pool, g2, signatures, &mvks, hash_m, weighting_seed, ivk, isig)
+    }
+
+    // Same as `msp_verify_2_public` with the keys mvk_i and H(m) already assigned, as in `msp_verify_assigned`
+    pub fn msp_verify_2_assigned(
+        &self,
+        pool: &mut SinglePhaseCoreManager<F>,
+        g2: G2Affine,
+        signatures: &[G1Affine],
+        mvks: &[EcPoint<F, FqPoint<F>>],
+        hash_m: EcPoint<F, FpPoint<F>>,
+        weighting_seed : F,
+        ivk: G2Affine,
+        isig: G1Affine, // \mu
+    ) -> PublicStatement<F> {
+        let ctx = pool.main();
+        let bls_signature_chip_2 = BlsSignatureChip2::new(
+            self.bls_signature_chip.fp_chip,
+            self.bls_signature_chip.pairing_chip,
+        );
+        let merkle_chip = MerkleTreeChip::new(self.poseidon_chip, GateChip::default());
+        let msg_digest = merkle_chip.g1_leaf(ctx, self.bls_signature_chip.fp_chip, &hash_m);
+
+        // The signatures are loaded once and shared by A and B
+        let (sigs, seed_limbs) = self.load_aggregation_2(ctx, mvks, signatures);
+        let g1_chip = EccChip::new(self.bls_signature_chip.fp_chip);
+        let fp2_chip = Fp2Chip::new(self.bls_signature_chip.fp_chip);
+        let g2_chip = EccChip::new(&fp2_chip);
+
+        // A : verify BLS signature, e(\sum_i sig_i, g2) = e(H(m), \sum_i mvk_i)
+        let mvk_agg = g2_chip.sum::<G2Affine>(ctx, mvks.iter().cloned());
+        let sig_agg = g1_chip.sum::<G1Affine>(ctx, sigs.iter().cloned());
+        let verify_A = bls_signature_chip_2.bls_aggregate_verify_points(ctx, g2, sig_agg, &[mvk_agg], &[hash_m.clone()]);
 
         // B
-        let signatures_x_assigned = signatures.iter().map(|pt| {
-            ctx.load_witness(F::from_bytes_le(&pt.x.to_bytes()))
-        }).collect::<Vec<_>>();
         let gate_chip = GateChip::<F>::default();
-        let weighting_seed_comp = self.poseidon_chip.hash_fix_len_array(ctx, &gate_chip, &signatures_x_assigned[..]);
-        let weighting_seed_assigned = ctx.load_witness(weighting_seed);
+        let (weighting_seed_assigned, weighting_seed_comp) = self.weighting_seed(ctx, &seed_limbs, weighting_seed);
         // B_1 : verify weighting seed
         let verify_B_1 = gate_chip.is_equal(ctx, weighting_seed_assigned, weighting_seed_comp);
-        // e_i = H(i,weighting_seed) for i in 0..n where n is the number of public keys
-        let e_is  = pubkeys.iter().enumerate().map(|(i, _)| {
-            let i_assigned = ctx.load_witness(F::from(i as u64));
-            self.poseidon_chip.hash_fix_len_array(ctx, &gate_chip, &[i_assigned, weighting_seed_assigned])
-        }).collect::<Vec<_>>();
+        let e_is = self.challenges(ctx, mvks.len(), weighting_seed_comp);
 
-        let g1_chip = EccChip::new(self.bls_signature_chip.fp_chip);
-        let fp2_chip = Fp2Chip::new(self.bls_signature_chip.fp_chip);
-        let g2_chip = EccChip::new(&fp2_chip);
         // B_2 : verify ivk, isig
         // ivk = \sum_{i=0}^{n-1} e_i * mvk_i
-        let ivk_assigned = self.bls_signature_chip.pairing_chip.load_private_g2(ctx, ivk);
-        let mvks = pubkeys.iter().map(|pt| self.bls_signature_chip.pairing_chip.load_private_g2(ctx, *pt)).collect::<Vec<_>>();
-        let products = mvks.iter().zip(e_is.iter()).map(|(mvk, &e_i)| {
-            let e_vec = vec![e_i];
-            g2_chip.scalar_mult::<G2Affine>(ctx, mvk.clone(), e_vec,254,4)
-        }).collect::<Vec<_>>();
-        let ivk_comp = g2_chip.sum::<G2Affine>(ctx, products);
-        let verify_B_2 = g2_chip.is_equal(ctx, ivk_assigned, ivk_comp);
+        let ivk_assigned = self.bls_signature_chip.pairing_chip.load_private_g2_checked(ctx, ivk);
+        let agg_key = g2_limbs(self.bls_signature_chip.fp_chip, ctx, &ivk_assigned);
+        let isig_assigned = self.bls_signature_chip.pairing_chip.load_private_g1(ctx, isig);
+        // each aggregate is a single MSM, which may use new threads of `pool`
+        let ivk_comp = self.combine_g2(pool, mvks, &e_is);
+        let isig_comp = self.combine_g1(pool, &sigs, &e_is);
+        let ctx = pool.main();
+        let verify_B_2 = g2_chip.is_equal(ctx, ivk_assigned.clone(), ivk_comp);
 
         // isig = \sum_{i=0}^{n-1} e_i * sig_i
-        let isig_assigned = self.bls_signature_chip.pairing_chip.load_private_g1(ctx, isig);
-        let sigs = signatures.iter().map(|pt| self.bls_signature_chip.pairing_chip.load_private_g1(ctx, *pt)).collect::<Vec<_>>();
-        let products = sigs.iter().zip(e_is.iter()).map(|(sig, &e_i)| {
-            let e_vec = vec![e_i];
-            g1_chip.scalar_mult::<G1Affine>(ctx, sig.clone(), e_vec,254,4)
-        }).collect::<Vec<_>>();
-        let isig_comp = g1_chip.sum::<G1Affine>(ctx, products);
-        let verify_B_3 = g1_chip.is_equal(ctx, isig_assigned, isig_comp);
+        let verify_B_3 = g1_chip.is_equal(ctx, isig_assigned.clone(), isig_comp);
 
-        // B_4 : verify e(isig, g2) = e(ivk, H(m))
-        let verify_B_4 = self.bls_signature_chip.bls_signature_verify_2(ctx, g2, &[isig], &[ivk], msghash);
+        // B_4 : verify e(isig, g2) = e(H(m), ivk) on the same ivk, isig as B_2 and B_3
+        let verify_B_4 = bls_signature_chip_2.bls_aggregate_verify_points(ctx, g2, isig_assigned, &[ivk_assigned], &[hash_m]);
 
         // Final result
         let result1 = gate_chip.and(ctx, verify_A, verify_B_1);
         let result2 = gate_chip.and(ctx, verify_B_2, verify_B_3);
         let result = gate_chip.and(ctx, result1, result2);
         let result = gate_chip.and(ctx, result, verify_B_4);
+        PublicStatement { msg_digest: Some(msg_digest), agg_key, ..PublicStatement::new(result) }
+    }
+
+    // Same as `msp_verify_2`, but H(m) is computed in circuit from the message field elements
+    // with the Poseidon based hash to G1 under the domain separation tag `dst`
+    pub fn msp_verify_2_msg(
+        &self,
+        pool: &mut SinglePhaseCoreManager<F>,
+        g2: G2Affine,
+        signatures: &[G1Affine],
+        pubkeys: &[G2Affine], // mvk
+        msg: &[AssignedValue<F>],
+        dst: &[u8],
+        weighting_seed: F,
+        ivk: G2Affine,
+        isig: G1Affine, // \mu
+    ) -> AssignedValue<F> {
+        let ctx = pool.main();
+        let hash_to_g1_chip =
+            HashToG1Chip::new(self.bls_signature_chip.fp_chip, self.poseidon_chip, dst);
+        let hash_m = hash_to_g1_chip.hash_to_g1(ctx, msg);
+        let mvks = self.load_keys_2(ctx, pubkeys);
+        self.msp_verify_2_assigned(pool, g2, signatures, &mvks, hash_m, weighting_seed, ivk, isig).result
+    }
+
+    // Same as `msp_verify_public`, but constrains every check to pass instead of returning a boolean,
+    // which is cheaper since the pairing checks skip the `is_equal` over Fp12. The result of the statement is the constant 1.
+    pub fn assert_msp_verify(
+        &self,
+        pool: &mut SinglePhaseCoreManager<F>,
+        g1: G1Affine,
+        signatures: &[G2Affine],
+        pubkeys: &[G1Affine], // mvk
+        msghash: G2Affine,
+        weighting_seed: F,
+        ivk: G1Affine,
+        isig: G2Affine, // \mu
+    ) -> PublicStatement<F> {
+        let ctx = pool.main();
+        let hash_m = self.bls_signature_chip.pairing_chip.load_private_g2_checked(ctx, msghash);
+        let merkle_chip = MerkleTreeChip::new(self.poseidon_chip, GateChip::default());
+        let msg_digest = merkle_chip.g2_leaf(ctx, self.bls_signature_chip.fp_chip, &hash_m);
+
+        let mvks = self.load_keys(ctx, pubkeys);
+        let (sigs, seed_limbs) = self.load_aggregation(ctx, &mvks, signatures);
+        let g1_chip = EccChip::new(self.bls_signature_chip.fp_chip);
+        let fp2_chip = Fp2Chip::new(self.bls_signature_chip.fp_chip);
+        let g2_chip = EccChip::new(&fp2_chip);
+
+        // A
+        let mvk_agg = g1_chip.sum::<G1Affine>(ctx, mvks.iter().cloned());
+        let sig_agg = g2_chip.sum::<G2Affine>(ctx, sigs.iter().cloned());
+        self.bls_signature_chip.assert_bls_aggregate_verify_points(ctx, g1, sig_agg, &[mvk_agg], &[hash_m.clone()]);
+
+        // B_1
+        let (weighting_seed_assigned, weighting_seed_comp) = self.weighting_seed(ctx, &seed_limbs, weighting_seed);
+        ctx.constrain_equal(&weighting_seed_assigned, &weighting_seed_comp);
+        let e_is = self.challenges(ctx, pubkeys.len(), weighting_seed_comp);
+
+        // B_2
+        let ivk_assigned = self.bls_signature_chip.pairing_chip.load_private_g1(ctx, ivk);
+        let agg_key = g1_limbs(self.bls_signature_chip.fp_chip, ctx, &ivk_assigned);
+        let isig_assigned = self.bls_signature_chip.pairing_chip.load_private_g2_checked(ctx, isig);
+        // each aggregate is a single MSM, which may use new threads of `pool`
+        let ivk_comp = self.combine_g1(pool, &mvks, &e_is);
+        let isig_comp = self.combine_g2(pool, &sigs, &e_is);
+        let ctx = pool.main();
+        g1_chip.assert_equal(ctx, ivk_assigned.clone(), ivk_comp);
+
+        // B_3
+        g2_chip.assert_equal(ctx, isig_assigned.clone(), isig_comp);
+
+        // B_4
+        self.bls_signature_chip.assert_bls_aggregate_verify_points(ctx, g1, isig_assigned, &[ivk_assigned], &[hash_m]);
+
+        let result = ctx.load_constant(F::ONE);
+        PublicStatement { msg_digest: Some(msg_digest), agg_key, ..PublicStatement::new(result) }
+    }
+
+    // Same as `msp_verify_2_public`, but constrains every check to pass as in `assert_msp_verify`
+    pub fn assert_msp_verify_2(
+        &self,
+        pool: &mut SinglePhaseCoreManager<F>,
+        g2: G2Affine,
+        signatures: &[G1Affine],
+        pubkeys: &[G2Affine], // mvk
+        msghash: G1Affine,
+        weighting_seed: F,
+        ivk: G2Affine,
+        isig: G1Affine, // \mu
+    ) -> PublicStatement<F> {
+        let ctx = pool.main();
+        let bls_signature_chip_2 = BlsSignatureChip2::new(
+            self.bls_signature_chip.fp_chip,
+            self.bls_signature_chip.pairing_chip,
+        );
+        let hash_m = self.bls_signature_chip.pairing_chip.load_private_g1(ctx, msghash);
+        let merkle_chip = MerkleTreeChip::new(self.poseidon_chip, GateChip::default());
+        let msg_digest = merkle_chip.g1_leaf(ctx, self.bls_signature_chip.fp_chip, &hash_m);
+
+        let mvks = self.load_keys_2(ctx, pubkeys);
+        let (sigs, seed_limbs) = self.load_aggregation_2(ctx, &mvks, signatures);
+        let g1_chip = EccChip::new(self.bls_signature_chip.fp_chip);
+        let fp2_chip = Fp2Chip::new(self.bls_signature_chip.fp_chip);
+        let g2_chip = EccChip::new(&fp2_chip);
+
+        // A
+        let mvk_agg = g2_chip.sum::<G2Affine>(ctx, mvks.iter().cloned());
+        let sig_agg = g1_chip.sum::<G1Affine>(ctx, sigs.iter().cloned());
+        bls_signature_chip_2.assert_bls_aggregate_verify_points(ctx, g2, sig_agg, &[mvk_agg], &[hash_m.clone()]);
+
+        // B_1
+        let (weighting_seed_assigned, weighting_seed_comp) = self.weighting_seed(ctx, &seed_limbs, weighting_seed);
+        ctx.constrain_equal(&weighting_seed_assigned, &weighting_seed_comp);
+        let e_is = self.challenges(ctx, pubkeys.len(), weighting_seed_comp);
+
+        // B_2
+        let ivk_assigned = self.bls_signature_chip.pairing_chip.load_private_g2_checked(ctx, ivk);
+        let agg_key = g2_limbs(self.bls_signature_chip.fp_chip, ctx, &ivk_assigned);
+        let isig_assigned = self.bls_signature_chip.pairing_chip.load_private_g1(ctx, isig);
+        // each aggregate is a single MSM, which may use new threads of `pool`
+        let ivk_comp = self.combine_g2(pool, &mvks, &e_is);
+        let isig_comp = self.combine_g1(pool, &sigs, &e_is);
+        let ctx = pool.main();
+        g2_chip.assert_equal(ctx, ivk_assigned.clone(), ivk_comp);
+
+        // B_3
+        g1_chip.assert_equal(ctx, isig_assigned.clone(), isig_comp);
+
+        // B_4
+        bls_signature_chip_2.assert_bls_aggregate_verify_points(ctx, g2, isig_assigned, &[ivk_assigned], &[hash_m]);
+
+        let result = ctx.load_constant(F::ONE);
+        PublicStatement { msg_digest: Some(msg_digest), agg_key, ..PublicStatement::new(result) }
+    }
+
+    // Loads the keys mvk_i, constrained to lie on G1
+    pub fn load_keys(&self, ctx: &mut Context<F>, pubkeys: &[G1Affine]) -> Vec<EcPoint<F, FpPoint<F>>> {
+        pubkeys.iter().map(|pt| self.bls_signature_chip.pairing_chip.load_private_g1(ctx, *pt)).collect()
+    }
+
+    // Loads the keys mvk_i, constrained to lie in the order r subgroup of G2
+    pub fn load_keys_2(&self, ctx: &mut Context<F>, pubkeys: &[G2Affine]) -> Vec<EcPoint<F, FqPoint<F>>> {
+        pubkeys.iter().map(|pt| self.bls_signature_chip.pairing_chip.load_private_g2_checked(ctx, *pt)).collect()
+    }
+
+    // Loads the signatures used in the aggregation, returning them together with the CRT limbs
+    // of all the keys first and then all the signatures, which are hashed into the weighting seed
+    fn load_aggregation(
+        &self,
+        ctx: &mut Context<F>,
+        mvks: &[EcPoint<F, FpPoint<F>>],
+        signatures: &[G2Affine],
+    ) -> (Vec<EcPoint<F, FqPoint<F>>>, Vec<AssignedValue<F>>) {
+        assert!(signatures.len() == mvks.len(), "signatures and pubkeys must be the same length");
+        let fp_chip = self.bls_signature_chip.fp_chip;
+        let pairing_chip = self.bls_signature_chip.pairing_chip;
+        let sigs = signatures.iter().map(|pt| pairing_chip.load_private_g2_checked(ctx, *pt)).collect::<Vec<_>>();
+        let mut limbs = vec![];
+        for mvk in mvks.iter() {
+            limbs.extend(g1_limbs(fp_chip, ctx, mvk));
+        }
+        for sig in sigs.iter() {
+            limbs.extend(g2_limbs(fp_chip, ctx, sig));
+        }
+        (sigs, limbs)
+    }
+
+    // Same as `load_aggregation`, with keys in G2 and signatures in G1
+    fn load_aggregation_2(
+        &self,
+        ctx: &mut Context<F>,
+        mvks: &[EcPoint<F, FqPoint<F>>],
+        signatures: &[G1Affine],
+    ) -> (Vec<EcPoint<F, FpPoint<F>>>, Vec<AssignedValue<F>>) {
+        assert!(signatures.len() == mvks.len(), "signatures and pubkeys must be the same length");
+        let fp_chip = self.bls_signature_chip.fp_chip;
+        let pairing_chip = self.bls_signature_chip.pairing_chip;
+        let sigs = signatures.iter().map(|pt| pairing_chip.load_private_g1(ctx, *pt)).collect::<Vec<_>>();
+        let mut limbs = vec![];
+        for mvk in mvks.iter() {
+            limbs.extend(g2_limbs(fp_chip, ctx, mvk));
+        }
+        for sig in sigs.iter() {
+            limbs.extend(g1_limbs(fp_chip, ctx, sig));
+        }
+        (sigs, limbs)
+    }
+
+    // Returns the assigned weighting seed together with the seed computed in circuit as Poseidon over the
+    // CRT limbs returned by `load_aggregation`, see `native::weighting_seed`
+    fn weighting_seed(
+        &self,
+        ctx: &mut Context<F>,
+        seed_limbs: &[AssignedValue<F>],
+        weighting_seed: F,
+    ) -> (AssignedValue<F>, AssignedValue<F>) {
+        let gate_chip = GateChip::<F>::default();
+        let weighting_seed_comp = self.poseidon_chip.hash_fix_len_array(ctx, &gate_chip, seed_limbs);
+        let weighting_seed_assigned = ctx.load_witness(weighting_seed);
+        (weighting_seed_assigned, weighting_seed_comp)
+    }
+
+    // e_i = H(i,weighting_seed) mod 2^challenge_bits for i in 0..n where n is the number of public keys
+    fn challenges(
+        &self,
+        ctx: &mut Context<F>,
+        n: usize,
+        weighting_seed: AssignedValue<F>,
+    ) -> Vec<AssignedValue<F>> {
+        let gate_chip = GateChip::<F>::default();
+        (0..n)
+            .map(|i| {
+                let i_assigned = ctx.load_constant(F::from(i as u64));
+                let e_i = self.poseidon_chip.hash_fix_len_array(ctx, &gate_chip, &[i_assigned, weighting_seed]);
+                self.truncate_challenge(ctx, e_i)
+            })
+            .collect()
+    }
+
+    // Bit length of the challenges as passed to `scalar_mult`
+    fn challenge_max_bits(&self) -> usize {
+        self.challenge_bits.min(F::NUM_BITS as usize)
+    }
+
+    fn truncate_challenge(&self, ctx: &mut Context<F>, e: AssignedValue<F>) -> AssignedValue<F> {
+        truncate_challenge(self.bls_signature_chip.fp_chip.range(), ctx, e, self.challenge_bits)
+    }
+
+    // \sum_i e_i * points_i over G1, computed with `variable_base_msm`
+    fn combine_g1(
+        &self,
+        pool: &mut SinglePhaseCoreManager<F>,
+        points: &[EcPoint<F, FpPoint<F>>],
+        e_is: &[AssignedValue<F>],
+    ) -> EcPoint<F, FpPoint<F>> {
+        let g1_chip = EccChip::new(self.bls_signature_chip.fp_chip);
+        let scalars = e_is.iter().map(|&e_i| vec![e_i]).collect();
+        g1_chip.variable_base_msm::<G1Affine>(pool, points, scalars, self.challenge_max_bits())
+    }
+
+    // \sum_i e_i * points_i over G2, computed with `variable_base_msm`
+    fn combine_g2(
+        &self,
+        pool: &mut SinglePhaseCoreManager<F>,
+        points: &[EcPoint<F, FqPoint<F>>],
+        e_is: &[AssignedValue<F>],
+    ) -> EcPoint<F, FqPoint<F>> {
+        let fp2_chip = Fp2Chip::new(self.bls_signature_chip.fp_chip);
+        let g2_chip = EccChip::new(&fp2_chip);
+        let scalars = e_is.iter().map(|&e_i| vec![e_i]).collect();
+        g2_chip.variable_base_msm::<G2Affine>(pool, points, scalars, self.challenge_max_bits())
+    }
+
+    // Same as `msp_verify`, additionally verifying the proof of possession pops[i] of every mvk_i
+    pub fn msp_verify_with_pop(
+        &self,
+        pool: &mut SinglePhaseCoreManager<F>,
+        g1: G1Affine,
+        signatures: &[G2Affine],
+        pubkeys: &[G1Affine], // mvk
+        msghash: G2Affine,
+        weighting_seed: F,
+        ivk: G1Affine,
+        isig: G2Affine, // \mu
+        pops: &[G2Affine],
+    ) -> AssignedValue<F> {
+        let ctx = pool.main();
+        let hash_m = self.bls_signature_chip.pairing_chip.load_private_g2_checked(ctx, msghash);
+        let mvks = self.load_keys(ctx, pubkeys);
+        let verify_pop = self.pop_verify(ctx, g1, &mvks, pops);
+        let verify_msp = self
+            .msp_verify_assigned(pool, g1, signatures, &mvks, hash_m, weighting_seed, ivk, isig)
+            .result;
+        self.bls_signature_chip.fp_chip.gate().and(pool.main(), verify_pop, verify_msp)
+    }
+
+    // Same as `msp_verify_2`, additionally verifying the proof of possession pops[i] of every mvk_i
+    pub fn msp_verify_2_with_pop(
+        &self,
+        pool: &mut SinglePhaseCoreManager<F>,
+        g2: G2Affine,
+        signatures: &[G1Affine],
+        pubkeys: &[G2Affine], // mvk
+        msghash: G1Affine,
+        weighting_seed: F,
+        ivk: G2Affine,
+        isig: G1Affine, // \mu
+        pops: &[G1Affine],
+    ) -> AssignedValue<F> {
+        let ctx = pool.main();
+        let hash_m = self.bls_signature_chip.pairing_chip.load_private_g1(ctx, msghash);
+        let mvks = self.load_keys_2(ctx, pubkeys);
+        let verify_pop = self.pop_verify_2(ctx, g2, &mvks, pops);
+        let verify_msp = self
+            .msp_verify_2_assigned(pool, g2, signatures, &mvks, hash_m, weighting_seed, ivk, isig)
+            .result;
+        self.bls_signature_chip.fp_chip.gate().and(pool.main(), verify_pop, verify_msp)
+    }
+
+    // Same as `msp_verify`, but instead of checking proofs of possession every mvk_i must be a member of
+    // a committee whose proofs of possession were verified once by `register_committee`,
+    // with the assigned `committee_root` bound by the caller as in `committee_verify`
+    pub fn msp_verify_attested(
+        &self,
+        pool: &mut SinglePhaseCoreManager<F>,
+        g1: G1Affine,
+        signatures: &[G2Affine],
+        pubkeys: &[G1Affine], // mvk
+        msghash: G2Affine,
+        weighting_seed: F,
+        ivk: G1Affine,
+        isig: G2Affine, // \mu
+        committee_root: AssignedValue<F>,
+        merkle_infos: &[MerkleInfo<F>],
+    ) -> AssignedValue<F> {
+        let ctx = pool.main();
+        let hash_m = self.bls_signature_chip.pairing_chip.load_private_g2_checked(ctx, msghash);
+        let mvks = self.load_keys(ctx, pubkeys);
+        let leaves = mvks.iter().map(|mvk| self.committee_leaf(ctx, mvk)).collect::<Vec<_>>();
+        let verify_committee = self.committee_verify(ctx, &leaves, committee_root, merkle_infos);
+        let verify_msp = self
+            .msp_verify_assigned(pool, g1, signatures, &mvks, hash_m, weighting_seed, ivk, isig)
+            .result;
+        self.bls_signature_chip.fp_chip.gate().and(pool.main(), verify_committee, verify_msp)
+    }
+
+    // Same as `msp_verify_2`, with committee membership in place of proofs of possession as in `msp_verify_attested`
+    pub fn msp_verify_2_attested(
+        &self,
+        pool: &mut SinglePhaseCoreManager<F>,
+        g2: G2Affine,
+        signatures: &[G1Affine],
+        pubkeys: &[G2Affine], // mvk
+        msghash: G1Affine,
+        weighting_seed: F,
+        ivk: G2Affine,
+        isig: G1Affine, // \mu
+        committee_root: AssignedValue<F>,
+        merkle_infos: &[MerkleInfo<F>],
+    ) -> AssignedValue<F> {
+        let ctx = pool.main();
+        let hash_m = self.bls_signature_chip.pairing_chip.load_private_g1(ctx, msghash);
+        let mvks = self.load_keys_2(ctx, pubkeys);
+        let leaves = mvks.iter().map(|mvk| self.committee_leaf_2(ctx, mvk)).collect::<Vec<_>>();
+        let verify_committee = self.committee_verify(ctx, &leaves, committee_root, merkle_infos);
+        let verify_msp = self
+            .msp_verify_2_assigned(pool, g2, signatures, &mvks, hash_m, weighting_seed, ivk, isig)
+            .result;
+        self.bls_signature_chip.fp_chip.gate().and(pool.main(), verify_committee, verify_msp)
+    }
+
+    // H_pop(mvk) for a key in G1: hash to G2 of the 64 byte encoding x || y under DST_POP_G2
+    pub fn pop_hash(
+        &self,
+        ctx: &mut Context<F>,
+        mvk: &EcPoint<F, FpPoint<F>>,
+    ) -> EcPoint<F, FqPoint<F>> {
+        let mut bytes = self.pop_hash_chip.fp_to_bytes_be(ctx, mvk.x.clone());
+        bytes.extend(self.pop_hash_chip.fp_to_bytes_be(ctx, mvk.y.clone()));
+        let msg = FixLenBytes::<F, 64>::new(bytes.try_into().unwrap());
+        self.pop_hash_chip.hash_to_g2_fix_len(ctx, &msg)
+    }
+
+    // H_pop(mvk) for a key in G2: Poseidon hash to G1 of the CRT limbs of the coordinates under DST_POP_G1
+    pub fn pop_hash_2(
+        &self,
+        ctx: &mut Context<F>,
+        mvk: &EcPoint<F, FqPoint<F>>,
+    ) -> EcPoint<F, FpPoint<F>> {
+        let hash_to_g1_chip =
+            HashToG1Chip::new(self.bls_signature_chip.fp_chip, self.poseidon_chip, DST_POP_G1);
+        let limbs = g2_limbs(self.bls_signature_chip.fp_chip, ctx, mvk);
+        hash_to_g1_chip.hash_to_g1(ctx, &limbs)
+    }
+
+    // Verifies the proofs of possession pops[i] = sk_i * H_pop(mvk_i) with one aggregate check over the messages H_pop(mvk_i).
+    // The messages are constrained to be distinct, so this also rejects duplicate keys.
+    pub fn pop_verify(
+        &self,
+        ctx: &mut Context<F>,
+        g1: G1Affine,
+        mvks: &[EcPoint<F, FpPoint<F>>],
+        pops: &[G2Affine],
+    ) -> AssignedValue<F> {
+        assert!(
+            mvks.len() == pops.len(),
+            "pubkeys and proofs of possession must be the same length"
+        );
+        let fp2_chip = Fp2Chip::new(self.bls_signature_chip.fp_chip);
+        let g2_chip = EccChip::new(&fp2_chip);
+
+        let hashes = mvks.iter().map(|mvk| self.pop_hash(ctx, mvk)).collect::<Vec<_>>();
+        let pop_points = pops
+            .iter()
+            .map(|pt| self.bls_signature_chip.pairing_chip.load_private_g2_checked(ctx, *pt))
+            .collect::<Vec<_>>();
+        let pop_agg = g2_chip.sum::<G2Affine>(ctx, pop_points);
+        self.bls_signature_chip.bls_aggregate_verify_points(ctx, g1, pop_agg, mvks, &hashes)
+    }
+
+    // Same as `pop_verify` for keys in G2 and proofs of possession in G1
+    pub fn pop_verify_2(
+        &self,
+        ctx: &mut Context<F>,
+        g2: G2Affine,
+        mvks: &[EcPoint<F, FqPoint<F>>],
+        pops: &[G1Affine],
+    ) -> AssignedValue<F> {
+        assert!(
+            mvks.len() == pops.len(),
+            "pubkeys and proofs of possession must be the same length"
+        );
+        let g1_chip = EccChip::new(self.bls_signature_chip.fp_chip);
+        let bls_signature_chip_2 = BlsSignatureChip2::new(
+            self.bls_signature_chip.fp_chip,
+            self.bls_signature_chip.pairing_chip,
+        );
+
+        let hashes = mvks.iter().map(|mvk| self.pop_hash_2(ctx, mvk)).collect::<Vec<_>>();
+        let pop_points = pops
+            .iter()
+            .map(|pt| self.bls_signature_chip.pairing_chip.load_private_g1(ctx, *pt))
+            .collect::<Vec<_>>();
+        let pop_agg = g1_chip.sum::<G1Affine>(ctx, pop_points);
+        bls_signature_chip_2.bls_aggregate_verify_points(ctx, g2, pop_agg, mvks, &hashes)
+    }
+
+    // Leaf of the committee Merkle tree for a key in G1, see `MerkleTreeChip::g1_leaf`
+    pub fn committee_leaf(
+        &self,
+        ctx: &mut Context<F>,
+        mvk: &EcPoint<F, FpPoint<F>>,
+    ) -> AssignedValue<F> {
+        let merkle_chip = MerkleTreeChip::new(self.poseidon_chip, GateChip::default());
+        merkle_chip.g1_leaf(ctx, self.bls_signature_chip.fp_chip, mvk)
+    }
+
+    // Leaf of the committee Merkle tree for a key in G2, see `MerkleTreeChip::g2_leaf`
+    pub fn committee_leaf_2(
+        &self,
+        ctx: &mut Context<F>,
+        mvk: &EcPoint<F, FqPoint<F>>,
+    ) -> AssignedValue<F> {
+        let merkle_chip = MerkleTreeChip::new(self.poseidon_chip, GateChip::default());
+        merkle_chip.g2_leaf(ctx, self.bls_signature_chip.fp_chip, mvk)
+    }
+
+    // Registration of a committee: verifies the proofs of possession once and returns (is_valid, committee_root),
+    // where committee_root is the Merkle root of the committee leaves in the order of `pubkeys`
+    pub fn register_committee(
+        &self,
+        ctx: &mut Context<F>,
+        g1: G1Affine,
+        pubkeys: &[G1Affine],
+        pops: &[G2Affine],
+    ) -> (AssignedValue<F>, AssignedValue<F>) {
+        let mvks = self.load_keys(ctx, pubkeys);
+        let verify_pop = self.pop_verify(ctx, g1, &mvks, pops);
+        let leaves = mvks.iter().map(|mvk| self.committee_leaf(ctx, mvk)).collect::<Vec<_>>();
+        let merkle_chip = MerkleTreeChip::new(self.poseidon_chip, GateChip::default());
+        (verify_pop, merkle_chip.compute_root(ctx, &leaves))
+    }
+
+    // Same as `register_committee` for keys in G2 and proofs of possession in G1
+    pub fn register_committee_2(
+        &self,
+        ctx: &mut Context<F>,
+        g2: G2Affine,
+        pubkeys: &[G2Affine],
+        pops: &[G1Affine],
+    ) -> (AssignedValue<F>, AssignedValue<F>) {
+        let mvks = self.load_keys_2(ctx, pubkeys);
+        let verify_pop = self.pop_verify_2(ctx, g2, &mvks, pops);
+        let leaves = mvks.iter().map(|mvk| self.committee_leaf_2(ctx, mvk)).collect::<Vec<_>>();
+        let merkle_chip = MerkleTreeChip::new(self.poseidon_chip, GateChip::default());
+        (verify_pop, merkle_chip.compute_root(ctx, &leaves))
+    }
+
+    // Returns 1 iff every leaf is in the committee tree with root `committee_root`,
+    // authenticated by the path and index of merkle_infos[i].
+    // The root is taken assigned so that the caller binds it, e.g. to the root returned by `register_committee` or to a public instance
+    pub fn committee_verify(
+        &self,
+        ctx: &mut Context<F>,
+        leaves: &[AssignedValue<F>],
+        committee_root: AssignedValue<F>,
+        merkle_infos: &[MerkleInfo<F>],
+    ) -> AssignedValue<F> {
+        assert!(
+            leaves.len() == merkle_infos.len(),
+            "leaves and merkle_infos must be the same length"
+        );
+        let merkle_chip = MerkleTreeChip::new(self.poseidon_chip, GateChip::default());
+        let gate = &merkle_chip.gate_chip;
+        let mut result = ctx.load_constant(F::ONE);
+        for (leaf, merkle_info) in leaves.iter().zip(merkle_infos.iter()) {
+            let computed_root = merkle_chip.merkle_root_from_leaf(
+                ctx,
+                *leaf,
+                &merkle_info.path,
+                &merkle_info.index,
+            );
+            let is_member = gate.is_equal(ctx, computed_root, committee_root);
+            result = gate.and(ctx, result, is_member);
+        }
         result
+    }
+}
 
+// Low `num_bits` bits of `e`, from the decomposition e = hi * 2^num_bits + lo, or `e` itself if num_bits >= F::NUM_BITS.
+// The decomposition is also constrained to be below the modulus, otherwise the prover could pick
+// the low bits of e + r instead whenever e + r fits in F::NUM_BITS bits.
+pub fn truncate_challenge<F: BigPrimeField>(
+    range: &RangeChip<F>,
+    ctx: &mut Context<F>,
+    e: AssignedValue<F>,
+    num_bits: usize,
+) -> AssignedValue<F> {
+    if num_bits >= F::NUM_BITS as usize {
+        return e;
+    }
+    let gate = range.gate();
+    let hi_bits = F::NUM_BITS as usize - num_bits;
+    let e_val = fe_to_biguint(e.value());
+    let [hi, lo] = [&e_val >> num_bits, e_val & ((BigUint::one() << num_bits) - 1u64)]
+        .map(|v| ctx.load_witness(biguint_to_fe(&v)));
+    let recomposed = gate.mul_add(ctx, hi, Constant(gate.pow_of_two()[num_bits]), lo);
+    ctx.constrain_equal(&recomposed, &e);
+    range.range_check(ctx, lo, num_bits);
+    range.range_check(ctx, hi, hi_bits);
 
+    let r = modulus::<F>();
+    let [r_hi, r_lo] = [&r >> num_bits, r & ((BigUint::one() << num_bits) - 1u64)]
+        .map(|v| Constant(biguint_to_fe::<F>(&v)));
+    let hi_lt = range.is_less_than(ctx, hi, r_hi, hi_bits);
+    let hi_eq = gate.is_equal(ctx, hi, r_hi);
+    let lo_lt = range.is_less_than(ctx, lo, r_lo, num_bits);
+    let hi_eq_lo_lt = gate.and(ctx, hi_eq, lo_lt);
+    let is_canonical = gate.or(ctx, hi_lt, hi_eq_lo_lt);
+    gate.assert_is_const(ctx, &is_canonical, &F::ONE);
+    lo
+}
+
+// Native counterparts for signers and committee registration
+pub mod native {
+    use super::{DST_POP_G1, DST_POP_G2};
+    use crate::bn254::hash_to_curve::native::{fp_to_bytes_be, hash_to_g2};
+    use crate::bn254::hash_to_g1::native::hash_to_g1;
+    use crate::bn254::merkle_tree::native::{g1_leaf, g1_limbs, g2_leaf, g2_limbs, poseidon_hash};
+    use crate::group::{Curve, Group};
+    use crate::halo2_proofs::halo2curves::bn256::{Fr, G1Affine, G2Affine, G1, G2};
+    use halo2_base::utils::{biguint_to_fe, fe_to_biguint};
+    use num_bigint::BigUint;
+    use num_traits::One;
+
+    pub fn pop_hash(pk: &G1Affine) -> G2Affine {
+        let msg = [fp_to_bytes_be(&pk.x), fp_to_bytes_be(&pk.y)].concat();
+        hash_to_g2(&msg, DST_POP_G2)
+    }
+
+    pub fn pop_prove(sk: &Fr, pk: &G1Affine) -> G2Affine {
+        (pop_hash(pk) * sk).into()
+    }
+
+    pub fn pop_hash_2(pk: &G2Affine, limb_bits: usize, num_limbs: usize) -> G1Affine {
+        hash_to_g1(&g2_limbs(pk, limb_bits, num_limbs), DST_POP_G1)
+    }
+
+    pub fn pop_prove_2(sk: &Fr, pk: &G2Affine, limb_bits: usize, num_limbs: usize) -> G1Affine {
+        (pop_hash_2(pk, limb_bits, num_limbs) * sk).into()
+    }
+
+    pub fn committee_leaf(pk: &G1Affine, limb_bits: usize, num_limbs: usize) -> Fr {
+        g1_leaf(pk, limb_bits, num_limbs)
+    }
+
+    pub fn committee_leaf_2(pk: &G2Affine, limb_bits: usize, num_limbs: usize) -> Fr {
+        g2_leaf(pk, limb_bits, num_limbs)
+    }
+    // Weighting seed of `MspChip::msp_verify`: Poseidon over the CRT limbs of all the keys followed by all the signatures
+    pub fn weighting_seed(
+        pubkeys: &[G1Affine],
+        signatures: &[G2Affine],
+        limb_bits: usize,
+        num_limbs: usize,
+    ) -> Fr {
+        assert!(signatures.len() == pubkeys.len(), "signatures and pubkeys must be the same length");
+        let limbs = pubkeys
+            .iter()
+            .flat_map(|pk| g1_limbs(pk, limb_bits, num_limbs))
+            .chain(signatures.iter().flat_map(|sig| g2_limbs(sig, limb_bits, num_limbs)))
+            .collect::<Vec<_>>();
+        poseidon_hash(&limbs)
+    }
+
+    // Weighting seed of `MspChip::msp_verify_2`
+    pub fn weighting_seed_2(
+        pubkeys: &[G2Affine],
+        signatures: &[G1Affine],
+        limb_bits: usize,
+        num_limbs: usize,
+    ) -> Fr {
+        assert!(signatures.len() == pubkeys.len(), "signatures and pubkeys must be the same length");
+        let limbs = pubkeys
+            .iter()
+            .flat_map(|pk| g2_limbs(pk, limb_bits, num_limbs))
+            .chain(signatures.iter().flat_map(|sig| g1_limbs(sig, limb_bits, num_limbs)))
+            .collect::<Vec<_>>();
+        poseidon_hash(&limbs)
+    }
+
+    // e_i = Poseidon([i, weighting_seed]) mod 2^challenge_bits for i in 0..n
+    pub fn challenges(weighting_seed: Fr, n: usize, challenge_bits: usize) -> Vec<Fr> {
+        (0..n)
+            .map(|i| {
+                truncate_challenge(poseidon_hash(&[Fr::from(i as u64), weighting_seed]), challenge_bits)
+            })
+            .collect()
+    }
+
+    // Native counterpart of `super::truncate_challenge`
+    pub fn truncate_challenge(e: Fr, num_bits: usize) -> Fr {
+        biguint_to_fe(&(fe_to_biguint(&e) % (BigUint::one() << num_bits)))
+    }
+
+    // (weighting_seed, ivk, isig) expected by `MspChip::msp_verify` for the given keys and signatures,
+    // with challenges of `challenge_bits` bits as in `MspChip::new_with_challenge_bits`
+    pub fn aggregate(
+        pubkeys: &[G1Affine],
+        signatures: &[G2Affine],
+        limb_bits: usize,
+        num_limbs: usize,
+        challenge_bits: usize,
+    ) -> (Fr, G1Affine, G2Affine) {
+        let weighting_seed = weighting_seed(pubkeys, signatures, limb_bits, num_limbs);
+        let e_is = challenges(weighting_seed, pubkeys.len(), challenge_bits);
+        let ivk = pubkeys.iter().zip(e_is.iter()).fold(G1::identity(), |acc, (pk, e_i)| acc + pk * e_i);
+        let isig =
+            signatures.iter().zip(e_is.iter()).fold(G2::identity(), |acc, (sig, e_i)| acc + sig * e_i);
+        (weighting_seed, ivk.to_affine(), isig.to_affine())
+    }
+
+    // (weighting_seed, ivk, isig) expected by `MspChip::msp_verify_2`
+    pub fn aggregate_2(
+        pubkeys: &[G2Affine],
+        signatures: &[G1Affine],
+        limb_bits: usize,
+        num_limbs: usize,
+        challenge_bits: usize,
+    ) -> (Fr, G2Affine, G1Affine) {
+        let weighting_seed = weighting_seed_2(pubkeys, signatures, limb_bits, num_limbs);
+        let e_is = challenges(weighting_seed, pubkeys.len(), challenge_bits);
+        let ivk = pubkeys.iter().zip(e_is.iter()).fold(G2::identity(), |acc, (pk, e_i)| acc + pk * e_i);
+        let isig =
+            signatures.iter().zip(e_is.iter()).fold(G1::identity(), |acc, (sig, e_i)| acc + sig * e_i);
+        (weighting_seed, ivk.to_affine(), isig.to_affine())
     }
 }
@@ -1,4 +1,5 @@
 #![allow(non_snake_case)]
+use super::merkle_tree::coordinate_limbs;
 use super::{Fp12Chip, Fp2Chip, FpChip, FpPoint, Fq, FqPoint};
 use crate::fields::vector::FieldVector;
 use crate::halo2_proofs::halo2curves::bn256::{
@@ -9,11 +10,18 @@ use crate::{
     fields::fp12::mul_no_carry_w6,
     fields::FieldChip,
 };
+use halo2_base::poseidon::hasher::PoseidonHasher;
 use halo2_base::utils::BigPrimeField;
-use halo2_base::Context;
+use halo2_base::{AssignedValue, Context};
 
 const XI_0: i64 = 9;
 
+// BN254 curve parameter x, so that p and r are polynomials in x and the ate loop count is 6x + 2
+pub const BN_X: u64 = 4965661367192848881;
+
+// 6x^2, which fits in 127 bits
+const SIX_X_SQUARED: u128 = 6 * (BN_X as u128) * (BN_X as u128);
+
 // Inputs:
 //  Q0 = (x_1, y_1) and Q1 = (x_2, y_2) are points in E(Fp2)
 //  P is point (X, Y) in E(Fp)
@@ -444,6 +452,192 @@ pub fn neg_twisted_frobenius<F: BigPrimeField>(
     EcPoint::new(out_x, out_y)
 }
 
+// Evaluates at P = (X, Y) a line function of a fixed point of E(Fp2) from its precomputed coefficients
+// [c_1, c_X, c_Y], see `native::g2_line_coeffs`. The line is c_1 + c_X * X + c_Y * Y where the three terms sit at
+// - w^0, w^4, w^3 for a tangent line, as in `sparse_line_function_equal`
+// - w^5, w^3, w^2 for a line through two distinct points, as in `sparse_line_function_unequal`
+// so only two Fp2 by Fp multiplications are left in circuit.
+// Output is a len 6 vector of `Option<FqPoint>`s as for the other sparse line functions
+pub fn fixed_line_function<F: BigPrimeField>(
+    fp2_chip: &Fp2Chip<F>,
+    ctx: &mut Context<F>,
+    coeffs: &[FqPoint<F>; 3],
+    P: &EcPoint<F, FpPoint<F>>,
+    is_tangent: bool,
+) -> Vec<Option<FqPoint<F>>> {
+    let [c_1, c_X, c_Y] = coeffs;
+    let c_X_X = fp2_chip.0.fp_mul_no_carry(ctx, c_X.clone(), &P.x);
+    let c_Y_Y = fp2_chip.0.fp_mul_no_carry(ctx, c_Y.clone(), &P.y);
+    let c_X_X = fp2_chip.carry_mod(ctx, c_X_X);
+    let c_Y_Y = fp2_chip.carry_mod(ctx, c_Y_Y);
+    if is_tangent {
+        vec![Some(c_1.clone()), None, None, Some(c_Y_Y), Some(c_X_X), None]
+    } else {
+        vec![None, None, Some(c_Y_Y), Some(c_X_X), None, Some(c_1.clone())]
+    }
+}
+
+// Same as `multi_miller_loop_BN`, where additionally each of `fixed_pairs` is (a_i, lines_i) with lines_i the
+// line coefficients of some b_i in G_2 from `native::g2_line_coeffs`, loaded as constants or as witnesses.
+// The line functions of fixed pairs are only evaluated at a_i, which skips all of the Fp2 arithmetic on b_i.
+// Assumes `fixed_pairs` and `pairs` are not both empty.
+pub fn multi_miller_loop_mixed_BN<F: BigPrimeField>(
+    ecc_chip: &EccChip<F, Fp2Chip<F>>,
+    ctx: &mut Context<F>,
+    fixed_pairs: Vec<(&EcPoint<F, FpPoint<F>>, &[[FqPoint<F>; 3]])>,
+    pairs: Vec<(&EcPoint<F, FpPoint<F>>, &EcPoint<F, FqPoint<F>>)>,
+    pseudo_binary_encoding: &[i8],
+) -> FqPoint<F> {
+    assert!(!fixed_pairs.is_empty() || !pairs.is_empty(), "pairs must not be empty");
+    let mut i = pseudo_binary_encoding.len() - 1;
+    while pseudo_binary_encoding[i] == 0 {
+        i -= 1;
+    }
+    let last_index = i;
+    assert_eq!(pseudo_binary_encoding[last_index], 1);
+
+    let fp2_chip = ecc_chip.field_chip();
+    let fp_chip = fp2_chip.fp_chip();
+    let neg_b = pairs.iter().map(|pair| ecc_chip.negate(ctx, pair.1)).collect::<Vec<_>>();
+    let mut fixed_lines =
+        fixed_pairs.iter().map(|&(a, lines)| (a, lines.iter())).collect::<Vec<_>>();
+
+    // initialize the first line functions into Fq12 point
+    let mut first_lines = vec![];
+    for (a, lines) in fixed_lines.iter_mut() {
+        let coeffs = lines.next().expect("not enough line coefficients");
+        first_lines.push(fixed_line_function::<F>(fp2_chip, ctx, coeffs, *a, true));
+    }
+    for &(a, b) in pairs.iter() {
+        first_lines.push(sparse_line_function_equal::<F>(fp2_chip, ctx, b, a));
+    }
+    let mut f = {
+        let zero_fp = fp_chip.load_constant(ctx, Fq::zero());
+        let mut f_coeffs = Vec::with_capacity(12);
+        for coeff in &first_lines[0] {
+            f_coeffs.push(coeff.as_ref().map_or(zero_fp.clone(), |fp2_point| fp2_point[0].clone()));
+        }
+        for coeff in &first_lines[0] {
+            f_coeffs.push(coeff.as_ref().map_or(zero_fp.clone(), |fp2_point| fp2_point[1].clone()));
+        }
+        FieldVector(f_coeffs)
+    };
+    for line in first_lines.iter().skip(1) {
+        f = sparse_fp12_multiply::<F>(fp2_chip, ctx, &f, line);
+    }
+
+    i -= 1;
+    let mut r = pairs.iter().map(|pair| pair.1.clone()).collect::<Vec<_>>();
+    let fp12_chip = Fp12Chip::<F>::new(fp_chip);
+    loop {
+        if i != last_index - 1 {
+            f = fp12_chip.mul(ctx, &f, &f);
+            for (a, lines) in fixed_lines.iter_mut() {
+                let coeffs = lines.next().expect("not enough line coefficients");
+                let line = fixed_line_function::<F>(fp2_chip, ctx, coeffs, *a, true);
+                f = sparse_fp12_multiply::<F>(fp2_chip, ctx, &f, &line);
+            }
+            for (r, &(a, _)) in r.iter().zip(pairs.iter()) {
+                f = fp12_multiply_with_line_equal::<F>(fp2_chip, ctx, &f, r, a);
+            }
+        }
+        for r in r.iter_mut() {
+            *r = ecc_chip.double(ctx, r.clone());
+        }
+
+        assert!(pseudo_binary_encoding[i] <= 1 && pseudo_binary_encoding[i] >= -1);
+        if pseudo_binary_encoding[i] != 0 {
+            for (a, lines) in fixed_lines.iter_mut() {
+                let coeffs = lines.next().expect("not enough line coefficients");
+                let line = fixed_line_function::<F>(fp2_chip, ctx, coeffs, *a, false);
+                f = sparse_fp12_multiply::<F>(fp2_chip, ctx, &f, &line);
+            }
+            for ((r, neg_b), &(a, b)) in r.iter_mut().zip(neg_b.iter()).zip(pairs.iter()) {
+                let sign_b = if pseudo_binary_encoding[i] == 1 { b } else { neg_b };
+                f = fp12_multiply_with_line_unequal::<F>(fp2_chip, ctx, &f, (r, sign_b), a);
+                *r = ecc_chip.add_unequal(ctx, r.clone(), sign_b, false);
+            }
+        }
+        if i == 0 {
+            break;
+        }
+        i -= 1;
+    }
+
+    // the two Frobenius lines of fixed pairs
+    for (a, lines) in fixed_lines.iter_mut() {
+        for _ in 0..2 {
+            let coeffs = lines.next().expect("not enough line coefficients");
+            let line = fixed_line_function::<F>(fp2_chip, ctx, coeffs, *a, false);
+            f = sparse_fp12_multiply::<F>(fp2_chip, ctx, &f, &line);
+        }
+        assert!(lines.next().is_none(), "too many line coefficients");
+    }
+    if pairs.is_empty() {
+        return f;
+    }
+
+    // Frobenius coefficient coeff[1][j] = ((9+u)^{(p-1)/6})^j
+    // load coeff[1][2], coeff[1][3]
+    let c2 = FROBENIUS_COEFF_FQ12_C1[1] * FROBENIUS_COEFF_FQ12_C1[1];
+    let c3 = c2 * FROBENIUS_COEFF_FQ12_C1[1];
+    let c2 = ecc_chip.field_chip.load_constant(ctx, c2);
+    let c3 = ecc_chip.field_chip.load_constant(ctx, c3);
+
+    // finish multiplying remaining line functions outside the loop
+    for (r, (a, b)) in r.iter_mut().zip(pairs) {
+        let b_1 = twisted_frobenius(ecc_chip, ctx, b, &c2, &c3);
+        let neg_b_2 = neg_twisted_frobenius(ecc_chip, ctx, &b_1, &c2, &c3);
+        f = fp12_multiply_with_line_unequal(fp2_chip, ctx, &f, (r, &b_1), a);
+        *r = ecc_chip.add_unequal(ctx, r.clone(), b_1, false);
+        f = fp12_multiply_with_line_unequal::<F>(fp2_chip, ctx, &f, (r, &neg_b_2), a);
+    }
+    f
+}
+
+// [k]Q for a fixed scalar k > 1 by double-and-add over the bits of k
+// Assumes Q has no 2-torsion part, which holds on the BN254 twist since its order h * r is odd.
+// Additions are strict: if some intermediate multiple collides with +-Q, which can only happen for Q outside
+// the order r subgroup, the witness is rejected instead of the slope being left unconstrained.
+pub fn g2_scalar_mult_by_constant<F: BigPrimeField>(
+    ecc_chip: &EccChip<F, Fp2Chip<F>>,
+    ctx: &mut Context<F>,
+    Q: &EcPoint<F, FqPoint<F>>,
+    k: u128,
+) -> EcPoint<F, FqPoint<F>> {
+    assert!(k > 1);
+    let num_bits = 128 - k.leading_zeros() as usize;
+    let mut R = Q.clone();
+    for i in (0..num_bits - 1).rev() {
+        R = ecc_chip.double(ctx, R);
+        if (k >> i) & 1 == 1 {
+            R = ecc_chip.add_unequal(ctx, R, Q, true);
+        }
+    }
+    R
+}
+
+// Constrains a point Q of E(Fp2), assumed to be on the curve, to lie in the order r subgroup G2.
+// For BN curves Q is in G2 iff psi(Q) = [6x^2]Q, where psi = twist . Frob_p . untwist is `twisted_frobenius`
+// (El Housni, Guillevic, Piellard, "Co-factor clearing and subgroup membership testing on pairing-friendly curves").
+// This costs one Frobenius and a 127 bit scalar multiplication by a constant, instead of a 254 bit one by r.
+pub fn assert_g2_subgroup<F: BigPrimeField>(
+    ecc_chip: &EccChip<F, Fp2Chip<F>>,
+    ctx: &mut Context<F>,
+    Q: &EcPoint<F, FqPoint<F>>,
+) {
+    // Frobenius coefficient coeff[1][j] = ((9+u)^{(p-1)/6})^j
+    // load coeff[1][2], coeff[1][3]
+    let c2 = FROBENIUS_COEFF_FQ12_C1[1] * FROBENIUS_COEFF_FQ12_C1[1];
+    let c3 = c2 * FROBENIUS_COEFF_FQ12_C1[1];
+    let c2 = ecc_chip.field_chip.load_constant(ctx, c2);
+    let c3 = ecc_chip.field_chip.load_constant(ctx, c3);
+
+    let psi_Q = twisted_frobenius::<F>(ecc_chip, ctx, Q.clone(), c2, c3);
+    let six_x_sq_Q = g2_scalar_mult_by_constant::<F>(ecc_chip, ctx, Q, SIX_X_SQUARED);
+    ecc_chip.assert_equal(ctx, psi_Q, six_x_sq_Q);
+}
+
 // To avoid issues with mutably borrowing twice (not allowed in Rust), we only store fp_chip and construct g2_chip and fp12_chip in scope when needed for temporary mutable borrows
 pub struct PairingChip<'chip, F: BigPrimeField> {
     pub fp_chip: &'chip FpChip<'chip, F>,
@@ -465,6 +659,25 @@ impl<'chip, F: BigPrimeField> PairingChip<'chip, F> {
         g2_chip.load_private::<G2Affine>(ctx, (point.x, point.y))
     }
 
+    // Same as `load_private_g2`, but also constrains the point to be in the prime order subgroup G2.
+    // `load_private_g2` only checks that the point is on the twist, which has a large cofactor.
+    pub fn load_private_g2_checked(
+        &self,
+        ctx: &mut Context<F>,
+        point: G2Affine,
+    ) -> EcPoint<F, FqPoint<F>> {
+        let point = self.load_private_g2(ctx, point);
+        self.assert_g2_subgroup(ctx, &point);
+        point
+    }
+
+    // Constrains an assigned point of the twist to be in G2, see `assert_g2_subgroup`
+    pub fn assert_g2_subgroup(&self, ctx: &mut Context<F>, point: &EcPoint<F, FqPoint<F>>) {
+        let fp2_chip = Fp2Chip::<F>::new(self.fp_chip);
+        let g2_chip = EccChip::new(&fp2_chip);
+        assert_g2_subgroup::<F>(&g2_chip, ctx, point);
+    }
+
     pub fn miller_loop(
         &self,
         ctx: &mut Context<F>,
@@ -497,6 +710,72 @@ impl<'chip, F: BigPrimeField> PairingChip<'chip, F> {
         )
     }
 
+    // Line coefficients of the Miller loop of a fixed Q, loaded as constants
+    pub fn load_fixed_g2_lines(&self, ctx: &mut Context<F>, Q: G2Affine) -> Vec<[FqPoint<F>; 3]> {
+        let fp2_chip = Fp2Chip::<F>::new(self.fp_chip);
+        native::g2_line_coeffs(Q)
+            .into_iter()
+            .map(|coeffs| coeffs.map(|c| fp2_chip.load_constant(ctx, c)))
+            .collect()
+    }
+
+    // Line coefficients of the Miller loop of Q, loaded as witnesses so that they can be shared by several
+    // Miller loops of a circuit whose Q is fixed per instance but not per circuit, e.g. the aggregate key of a committee.
+    // The coefficients are not constrained to come from any point: the caller must bind them,
+    // e.g. by exposing `g2_lines_commitment` as a public instance.
+    pub fn load_private_g2_lines(&self, ctx: &mut Context<F>, Q: G2Affine) -> Vec<[FqPoint<F>; 3]> {
+        let fp2_chip = Fp2Chip::<F>::new(self.fp_chip);
+        native::g2_line_coeffs(Q)
+            .into_iter()
+            .map(|coeffs| coeffs.map(|c| fp2_chip.load_private(ctx, c)))
+            .collect()
+    }
+
+    // Poseidon hash of the CRT limbs of line coefficients, c_1, c_X, c_Y of each line with c0 then c1 of each coefficient.
+    // Exposed as a public instance, it binds the coefficients of `load_private_g2_lines` to the Q the verifier
+    // recomputes it from with `native::g2_lines_commitment`. The limbs are constrained to be reduced so the hash is unique.
+    pub fn g2_lines_commitment(
+        &self,
+        ctx: &mut Context<F>,
+        poseidon_chip: &PoseidonHasher<F, 3, 2>,
+        lines: &[[FqPoint<F>; 3]],
+    ) -> AssignedValue<F> {
+        let coordinates =
+            lines.iter().flat_map(|line| line.iter().flat_map(|c| c.0.iter().cloned())).collect();
+        let limbs = coordinate_limbs(self.fp_chip, ctx, coordinates);
+        poseidon_chip.hash_fix_len_array(ctx, self.fp_chip.gate(), &limbs)
+    }
+
+    // Miller loop of a constant Q, with its line functions precomputed natively
+    pub fn miller_loop_fixed_g2(
+        &self,
+        ctx: &mut Context<F>,
+        Q: G2Affine,
+        P: &EcPoint<F, FpPoint<F>>,
+    ) -> FqPoint<F> {
+        let lines = self.load_fixed_g2_lines(ctx, Q);
+        self.multi_miller_loop_mixed(ctx, vec![(P, &lines[..])], vec![])
+    }
+
+    // Same as `multi_miller_loop`, with some of the G2 points given by their line coefficients
+    // from `load_fixed_g2_lines` or `load_private_g2_lines`
+    pub fn multi_miller_loop_mixed(
+        &self,
+        ctx: &mut Context<F>,
+        fixed_pairs: Vec<(&EcPoint<F, FpPoint<F>>, &[[FqPoint<F>; 3]])>,
+        pairs: Vec<(&EcPoint<F, FpPoint<F>>, &EcPoint<F, FqPoint<F>>)>,
+    ) -> FqPoint<F> {
+        let fp2_chip = Fp2Chip::<F>::new(self.fp_chip);
+        let g2_chip = EccChip::new(&fp2_chip);
+        multi_miller_loop_mixed_BN::<F>(
+            &g2_chip,
+            ctx,
+            fixed_pairs,
+            pairs,
+            &SIX_U_PLUS_2_NAF, // pseudo binary encoding for BN254
+        )
+    }
+
     pub fn final_exp(&self, ctx: &mut Context<F>, f: FqPoint<F>) -> FqPoint<F> {
         let fp12_chip = Fp12Chip::<F>::new(self.fp_chip);
         fp12_chip.final_exp(ctx, f)
@@ -539,3 +818,79 @@ impl<'chip, F: BigPrimeField> PairingChip<'chip, F> {
         fp12_chip.assert_equal(ctx, fe, fp12_one);
     }
 }
+
+// Native precomputation of the line functions of a fixed point of G2
+pub mod native {
+    use crate::bn254::merkle_tree::native::{coordinate_limbs, poseidon_hash};
+    use crate::ff::Field;
+    use crate::halo2_proofs::arithmetic::CurveAffine;
+    use crate::halo2_proofs::halo2curves::bn256::{
+        Fq, Fq2, Fr, G2Affine, FROBENIUS_COEFF_FQ12_C1, SIX_U_PLUS_2_NAF,
+    };
+
+    // Coefficients [c_1, c_X, c_Y] of the line functions of `miller_loop_BN` on Q, in the order in which
+    // the Miller loop multiplies them in, see `super::fixed_line_function`
+    pub fn g2_line_coeffs(Q: G2Affine) -> Vec<[Fq2; 3]> {
+        let pseudo_binary_encoding = &SIX_U_PLUS_2_NAF;
+        let mut last_index = pseudo_binary_encoding.len() - 1;
+        while pseudo_binary_encoding[last_index] == 0 {
+            last_index -= 1;
+        }
+        assert_eq!(pseudo_binary_encoding[last_index], 1);
+
+        let neg_Q = -Q;
+        let mut R = Q;
+        let mut lines = vec![tangent_line(R)];
+        for i in (0..last_index).rev() {
+            if i != last_index - 1 {
+                lines.push(tangent_line(R));
+            }
+            R = (R + R).into();
+            if pseudo_binary_encoding[i] != 0 {
+                let sign_Q = if pseudo_binary_encoding[i] == 1 { Q } else { neg_Q };
+                lines.push(secant_line(R, sign_Q));
+                R = (R + sign_Q).into();
+            }
+        }
+
+        let Q_1 = twisted_frobenius(Q);
+        let neg_Q_2 = -twisted_frobenius(Q_1);
+        lines.push(secant_line(R, Q_1));
+        R = (R + Q_1).into();
+        lines.push(secant_line(R, neg_Q_2));
+        lines
+    }
+
+    // Native counterpart of `PairingChip::g2_lines_commitment` on the line coefficients of Q
+    pub fn g2_lines_commitment(Q: G2Affine, limb_bits: usize, num_limbs: usize) -> Fr {
+        let coordinates = g2_line_coeffs(Q)
+            .into_iter()
+            .flat_map(|line| line.into_iter().flat_map(|c| [c.c0, c.c1]))
+            .collect::<Vec<_>>();
+        poseidon_hash(&coordinate_limbs(&coordinates, limb_bits, num_limbs))
+    }
+
+    // Native counterpart of `super::sparse_line_function_equal`:
+    // c_1 = (3x^3 - 2y^2)(9 + u), c_X = -3x^2, c_Y = 2y
+    fn tangent_line(R: G2Affine) -> [Fq2; 3] {
+        let xi = Fq2 { c0: Fq::from(9), c1: Fq::ONE };
+        let x_sq = R.x.square();
+        let three_x_sq = x_sq.double() + x_sq;
+        let c_1 = (three_x_sq * R.x - R.y.square().double()) * xi;
+        [c_1, -three_x_sq, R.y.double()]
+    }
+
+    // Native counterpart of `super::sparse_line_function_unequal`:
+    // c_1 = x_1 y_2 - x_2 y_1, c_X = y_1 - y_2, c_Y = x_2 - x_1
+    fn secant_line(R: G2Affine, S: G2Affine) -> [Fq2; 3] {
+        [R.x * S.y - S.x * R.y, R.y - S.y, S.x - R.x]
+    }
+
+    // Native counterpart of `super::twisted_frobenius`
+    fn twisted_frobenius(Q: G2Affine) -> G2Affine {
+        let c2 = FROBENIUS_COEFF_FQ12_C1[1] * FROBENIUS_COEFF_FQ12_C1[1];
+        let c3 = c2 * FROBENIUS_COEFF_FQ12_C1[1];
+        let conjugate = |a: Fq2| Fq2 { c0: a.c0, c1: -a.c1 };
+        G2Affine::from_xy(c2 * conjugate(Q.x), c3 * conjugate(Q.y)).unwrap()
+    }
+}
@@ -0,0 +1,293 @@
+#![allow(non_snake_case)]
+
+use super::merkle_tree::MerkleTreeChip;
+use halo2_base::gates::GateInstructions;
+use halo2_base::utils::BigPrimeField;
+use halo2_base::{AssignedValue, Context};
+
+// Sparse Merkle tree of height `depth` keyed by field elements of at most `depth` bits:
+// the leaf for `key` sits at position `key`, an empty leaf is 0 and an occupied one is Poseidon([key, value]).
+// Internal nodes are Poseidon([left, right]) as in `MerkleTreeChip`, so the empty subtree of height i+1 is
+// Poseidon([z_i, z_i]) with z_0 = 0.
+
+// Witness for changing the leaf at `key` from `old_leaf` to `new_leaf`, where `siblings` is the path
+// of the key in the tree before (and after) the update. Leaves are leaf nodes, 0 for an empty leaf.
+#[derive(Clone, Debug)]
+pub struct SmtUpdateProof<F: BigPrimeField> {
+    pub key: F,
+    pub old_leaf: F,
+    pub new_leaf: F,
+    pub siblings: Vec<F>,
+}
+
+// Key and leaves of an update as assigned by `update_batch`, for the caller to bind to the rest of the circuit
+#[derive(Clone, Debug)]
+pub struct AssignedSmtUpdate<F: BigPrimeField> {
+    pub key: AssignedValue<F>,
+    pub old_leaf: AssignedValue<F>,
+    pub new_leaf: AssignedValue<F>,
+}
+
+pub struct SparseMerkleTreeChip<'chip, F: BigPrimeField, const T: usize, const RATE: usize> {
+    pub merkle_chip: MerkleTreeChip<'chip, F, T, RATE>,
+    pub depth: usize,
+}
+
+impl<'chip, F: BigPrimeField, const T: usize, const RATE: usize>
+    SparseMerkleTreeChip<'chip, F, T, RATE>
+{
+    pub fn new(merkle_chip: MerkleTreeChip<'chip, F, T, RATE>, depth: usize) -> Self {
+        // the decomposition of the key into `depth` bits is only unique below the field size
+        assert!(depth < F::NUM_BITS as usize, "depth must be smaller than the field size in bits");
+        Self { merkle_chip, depth }
+    }
+
+    // Leaf node of an occupied leaf
+    pub fn leaf_node(
+        &self,
+        ctx: &mut Context<F>,
+        key: AssignedValue<F>,
+        value: AssignedValue<F>,
+    ) -> AssignedValue<F> {
+        let merkle_chip = &self.merkle_chip;
+        merkle_chip.poseidon_chip.hash_fix_len_array(ctx, &merkle_chip.gate_chip, &[key, value])
+    }
+
+    // Path directions of `key`, which also constrains the key to `depth` bits
+    pub fn key_bits(&self, ctx: &mut Context<F>, key: AssignedValue<F>) -> Vec<AssignedValue<F>> {
+        self.merkle_chip.gate_chip.num_to_bits(ctx, key, self.depth)
+    }
+
+    // Root of the tree with `leaf` at `key` and the given path
+    pub fn root_from_leaf(
+        &self,
+        ctx: &mut Context<F>,
+        key: AssignedValue<F>,
+        leaf: AssignedValue<F>,
+        siblings: &[AssignedValue<F>],
+    ) -> AssignedValue<F> {
+        assert!(siblings.len() == self.depth, "siblings must have length depth");
+        let bits = self.key_bits(ctx, key);
+        self.merkle_chip.merkle_root_from_bits(ctx, leaf, siblings, &bits)
+    }
+
+    // Returns 1 iff `key` is mapped to `value` in the tree with `root`
+    pub fn verify_membership(
+        &self,
+        ctx: &mut Context<F>,
+        root: AssignedValue<F>,
+        key: AssignedValue<F>,
+        value: AssignedValue<F>,
+        siblings: &[AssignedValue<F>],
+    ) -> AssignedValue<F> {
+        let leaf = self.leaf_node(ctx, key, value);
+        let computed_root = self.root_from_leaf(ctx, key, leaf, siblings);
+        self.merkle_chip.gate_chip.is_equal(ctx, computed_root, root)
+    }
+
+    // Returns 1 iff the leaf at `key` is empty in the tree with `root`
+    pub fn verify_non_membership(
+        &self,
+        ctx: &mut Context<F>,
+        root: AssignedValue<F>,
+        key: AssignedValue<F>,
+        siblings: &[AssignedValue<F>],
+    ) -> AssignedValue<F> {
+        let empty = ctx.load_zero();
+        let computed_root = self.root_from_leaf(ctx, key, empty, siblings);
+        self.merkle_chip.gate_chip.is_equal(ctx, computed_root, root)
+    }
+
+    // Replaces `old_leaf` by `new_leaf` at `key`. Returns (is_valid, new_root), where is_valid is 1 iff
+    // `old_leaf` is at `key` in the tree with `old_root`. Both roots are computed from the same siblings,
+    // so no other leaf can change. Insertion and deletion use an empty (zero) old or new leaf.
+    pub fn update_leaf(
+        &self,
+        ctx: &mut Context<F>,
+        old_root: AssignedValue<F>,
+        key: AssignedValue<F>,
+        old_leaf: AssignedValue<F>,
+        new_leaf: AssignedValue<F>,
+        siblings: &[AssignedValue<F>],
+    ) -> (AssignedValue<F>, AssignedValue<F>) {
+        assert!(siblings.len() == self.depth, "siblings must have length depth");
+        let bits = self.key_bits(ctx, key);
+        let computed_root = self.merkle_chip.merkle_root_from_bits(ctx, old_leaf, siblings, &bits);
+        let is_valid = self.merkle_chip.gate_chip.is_equal(ctx, computed_root, old_root);
+        let new_root = self.merkle_chip.merkle_root_from_bits(ctx, new_leaf, siblings, &bits);
+        (is_valid, new_root)
+    }
+
+    // Inserts `value` at the empty leaf `key`
+    pub fn insert(
+        &self,
+        ctx: &mut Context<F>,
+        old_root: AssignedValue<F>,
+        key: AssignedValue<F>,
+        value: AssignedValue<F>,
+        siblings: &[AssignedValue<F>],
+    ) -> (AssignedValue<F>, AssignedValue<F>) {
+        let empty = ctx.load_zero();
+        let new_leaf = self.leaf_node(ctx, key, value);
+        self.update_leaf(ctx, old_root, key, empty, new_leaf, siblings)
+    }
+
+    // Changes the value at `key` from `old_value` to `new_value`
+    pub fn update(
+        &self,
+        ctx: &mut Context<F>,
+        old_root: AssignedValue<F>,
+        key: AssignedValue<F>,
+        old_value: AssignedValue<F>,
+        new_value: AssignedValue<F>,
+        siblings: &[AssignedValue<F>],
+    ) -> (AssignedValue<F>, AssignedValue<F>) {
+        let old_leaf = self.leaf_node(ctx, key, old_value);
+        let new_leaf = self.leaf_node(ctx, key, new_value);
+        self.update_leaf(ctx, old_root, key, old_leaf, new_leaf, siblings)
+    }
+
+    // Applies the updates in order, each one against the root left by the previous one.
+    // Returns (is_valid, new_root, updates), where is_valid is 1 iff every update is valid and updates holds
+    // the assigned key and leaves of every update, which are otherwise unconstrained by the caller.
+    pub fn update_batch(
+        &self,
+        ctx: &mut Context<F>,
+        old_root: AssignedValue<F>,
+        proofs: &[SmtUpdateProof<F>],
+    ) -> (AssignedValue<F>, AssignedValue<F>, Vec<AssignedSmtUpdate<F>>) {
+        let gate = &self.merkle_chip.gate_chip;
+        let mut is_valid = ctx.load_constant(F::ONE);
+        let mut root = old_root;
+        let mut updates = Vec::with_capacity(proofs.len());
+        for proof in proofs.iter() {
+            let key = ctx.load_witness(proof.key);
+            let old_leaf = ctx.load_witness(proof.old_leaf);
+            let new_leaf = ctx.load_witness(proof.new_leaf);
+            let siblings = ctx.assign_witnesses(proof.siblings.iter().copied());
+            let (valid, new_root) = self.update_leaf(ctx, root, key, old_leaf, new_leaf, &siblings);
+            is_valid = gate.and(ctx, is_valid, valid);
+            root = new_root;
+            updates.push(AssignedSmtUpdate { key, old_leaf, new_leaf });
+        }
+        (is_valid, root, updates)
+    }
+}
+
+// Native sparse Merkle tree with Poseidon<Fr, 3, 2>, storing only the non-empty nodes
+pub mod native {
+    use super::SmtUpdateProof;
+    use crate::bn254::merkle_tree::native::hash_pair;
+    use crate::halo2_proofs::halo2curves::bn256::Fr;
+    use crate::halo2curves::pairing::group::ff::PrimeField;
+    use halo2_base::utils::fe_to_biguint;
+    use num_bigint::BigUint;
+    use std::collections::HashMap;
+
+    pub fn leaf_node(key: Fr, value: Fr) -> Fr {
+        hash_pair(key, value)
+    }
+
+    #[derive(Clone, Debug)]
+    pub struct SparseMerkleTree {
+        pub depth: usize,
+        // root of the empty subtree of each height
+        zero_hashes: Vec<Fr>,
+        // non-empty nodes by (height, position within the level)
+        nodes: HashMap<(usize, BigUint), Fr>,
+        values: HashMap<BigUint, Fr>,
+    }
+
+    impl SparseMerkleTree {
+        pub fn new(depth: usize) -> Self {
+            assert!(
+                depth < Fr::NUM_BITS as usize,
+                "depth must be smaller than the field size in bits"
+            );
+            let mut zero_hashes = vec![Fr::zero()];
+            for i in 0..depth {
+                zero_hashes.push(hash_pair(zero_hashes[i], zero_hashes[i]));
+            }
+            Self { depth, zero_hashes, nodes: HashMap::new(), values: HashMap::new() }
+        }
+
+        fn position(&self, key: &Fr) -> BigUint {
+            let position = fe_to_biguint(key);
+            assert!(position.bits() as usize <= self.depth, "key does not fit in depth bits");
+            position
+        }
+
+        fn node(&self, height: usize, position: &BigUint) -> Fr {
+            *self.nodes.get(&(height, position.clone())).unwrap_or(&self.zero_hashes[height])
+        }
+
+        pub fn root(&self) -> Fr {
+            self.node(self.depth, &BigUint::from(0u64))
+        }
+
+        pub fn get(&self, key: &Fr) -> Option<Fr> {
+            self.values.get(&self.position(key)).copied()
+        }
+
+        // Leaf node at `key`, 0 if empty
+        pub fn leaf(&self, key: &Fr) -> Fr {
+            self.node(0, &self.position(key))
+        }
+
+        // Path of `key` from the leaf level up, valid for membership and non-membership proofs
+        pub fn siblings(&self, key: &Fr) -> Vec<Fr> {
+            let mut position = self.position(key);
+            let one = BigUint::from(1u64);
+            (0..self.depth)
+                .map(|height| {
+                    let sibling = self.node(height, &(&position ^ &one));
+                    position >>= 1;
+                    sibling
+                })
+                .collect()
+        }
+
+        fn set_leaf(&mut self, key: &Fr, leaf: Fr) {
+            let mut position = self.position(key);
+            let one = BigUint::from(1u64);
+            let mut hash = leaf;
+            for height in 0..=self.depth {
+                if hash == self.zero_hashes[height] {
+                    self.nodes.remove(&(height, position.clone()));
+                } else {
+                    self.nodes.insert((height, position.clone()), hash);
+                }
+                if height == self.depth {
+                    break;
+                }
+                let sibling = self.node(height, &(&position ^ &one));
+                hash = if (&position & &one) == one {
+                    hash_pair(sibling, hash)
+                } else {
+                    hash_pair(hash, sibling)
+                };
+                position >>= 1;
+            }
+        }
+
+        // Sets `key` to `value`, inserting it if absent, and returns the proof of the change
+        pub fn insert(&mut self, key: Fr, value: Fr) -> SmtUpdateProof<Fr> {
+            let new_leaf = leaf_node(key, value);
+            self.values.insert(self.position(&key), value);
+            self.update_leaf(key, new_leaf)
+        }
+
+        // Empties the leaf at `key` and returns the proof of the change
+        pub fn remove(&mut self, key: Fr) -> SmtUpdateProof<Fr> {
+            self.values.remove(&self.position(&key));
+            self.update_leaf(key, Fr::zero())
+        }
+
+        fn update_leaf(&mut self, key: Fr, new_leaf: Fr) -> SmtUpdateProof<Fr> {
+            let old_leaf = self.leaf(&key);
+            let siblings = self.siblings(&key);
+            self.set_leaf(&key, new_leaf);
+            SmtUpdateProof { key, old_leaf, new_leaf, siblings }
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use std::fs::File;
+
+use super::*;
+use crate::bn254::bls_batch::{native, BlsBatchChip, COEFF_BITS};
+use crate::bn254::merkle_tree::{g1_limbs, g2_limbs};
+use crate::fields::FpStrategy;
+use crate::halo2_proofs::halo2curves::bn256::G2Affine;
+use crate::halo2curves::pairing::group::ff::Field;
+use halo2_base::gates::GateChip;
+use halo2_base::poseidon::hasher::{spec::OptimizedPoseidonSpec, PoseidonHasher};
+use rand_core::OsRng;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+struct BlsBatchCircuitParams {
+    strategy: FpStrategy,
+    degree: u32,
+    num_advice: usize,
+    num_lookup_advice: usize,
+    num_fixed: usize,
+    lookup_bits: usize,
+    limb_bits: usize,
+    num_limbs: usize,
+    num_aggregation: u32,
+}
+
+fn read_params() -> BlsBatchCircuitParams {
+    let path = "configs/bn254/bls_batch_circuit.config";
+    serde_json::from_reader(
+        File::open(path).unwrap_or_else(|e| panic!("{path} does not exist: {e:?}")),
+    )
+    .unwrap()
+}
+
+// k signatures with keys in G1, each on its own message
+fn random_batch(k: u32) -> (Vec<G2Affine>, Vec<G1Affine>, Vec<G2Affine>) {
+    let mut signatures = vec![];
+    let mut pubkeys = vec![];
+    let mut msghashes = vec![];
+    for _ in 0..k {
+        let sk = Fr::random(OsRng);
+        let msghash = G2Affine::from(G2Affine::generator() * Fr::random(OsRng));
+        signatures.push(G2Affine::from(msghash * sk));
+        pubkeys.push(G1Affine::from(G1Affine::generator() * sk));
+        msghashes.push(msghash);
+    }
+    (signatures, pubkeys, msghashes)
+}
+
+// k signatures with keys in G2, each on its own message
+fn random_batch_2(k: u32) -> (Vec<G1Affine>, Vec<G2Affine>, Vec<G1Affine>) {
+    let mut signatures = vec![];
+    let mut pubkeys = vec![];
+    let mut msghashes = vec![];
+    for _ in 0..k {
+        let sk = Fr::random(OsRng);
+        let msghash = G1Affine::from(G1Affine::generator() * Fr::random(OsRng));
+        signatures.push(G1Affine::from(msghash * sk));
+        pubkeys.push(G2Affine::from(G2Affine::generator() * sk));
+        msghashes.push(msghash);
+    }
+    (signatures, pubkeys, msghashes)
+}
+
+fn bls_batch_test(
+    pool: &mut SinglePhaseCoreManager<Fr>,
+    range: &RangeChip<Fr>,
+    params: BlsBatchCircuitParams,
+    signatures: &[G2Affine],
+    pubkeys: &[G1Affine],
+    msghashes: &[G2Affine],
+) -> Fr {
+    let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+    let pairing_chip = PairingChip::new(&fp_chip);
+    let gate_chip = GateChip::<Fr>::default();
+    let mut poseidon_chip =
+        PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+    poseidon_chip.initialize_consts(pool.main(), &gate_chip);
+    let batch_chip = BlsBatchChip::new(&fp_chip, &pairing_chip, &poseidon_chip);
+    let result = batch_chip.bls_batch_verify(
+        pool.main(),
+        G1Affine::generator(),
+        signatures,
+        pubkeys,
+        msghashes,
+    );
+    *result.value()
+}
+
+fn bls_batch_test_2(
+    pool: &mut SinglePhaseCoreManager<Fr>,
+    range: &RangeChip<Fr>,
+    params: BlsBatchCircuitParams,
+    signatures: &[G1Affine],
+    pubkeys: &[G2Affine],
+    msghashes: &[G1Affine],
+) -> Fr {
+    let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+    let pairing_chip = PairingChip::new(&fp_chip);
+    let gate_chip = GateChip::<Fr>::default();
+    let mut poseidon_chip =
+        PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+    poseidon_chip.initialize_consts(pool.main(), &gate_chip);
+    let batch_chip = BlsBatchChip::new(&fp_chip, &pairing_chip, &poseidon_chip);
+    let result = batch_chip.bls_batch_verify_2(
+        pool.main(),
+        G2Affine::generator(),
+        signatures,
+        pubkeys,
+        msghashes,
+    );
+    *result.value()
+}
+
+#[test]
+fn test_bls_batch() {
+    let params = read_params();
+    let (signatures, pubkeys, msghashes) = random_batch(params.num_aggregation);
+
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run_builder(|pool, range| {
+        let result = bls_batch_test(pool, range, params, &signatures, &pubkeys, &msghashes);
+        assert_eq!(result, Fr::one());
+    })
+}
+
+#[test]
+fn test_bls_batch_invalid_signature() {
+    let params = read_params();
+    let (mut signatures, pubkeys, msghashes) = random_batch(params.num_aggregation);
+    // a valid signature on another message of the batch
+    signatures[0] = G2Affine::from(msghashes[1] * Fr::random(OsRng));
+
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run_builder(|pool, range| {
+        let result = bls_batch_test(pool, range, params, &signatures, &pubkeys, &msghashes);
+        assert_eq!(result, Fr::zero());
+    })
+}
+
+#[test]
+fn test_bls_batch_swapped_signatures() {
+    // aggregate verification would accept signatures swapped between two signers on the same message,
+    // while the per-signature coefficients reject them
+    let params = read_params();
+    let (mut signatures, pubkeys, mut msghashes) = random_batch(params.num_aggregation);
+    msghashes[1] = msghashes[0];
+    let sk_0 = Fr::random(OsRng);
+    let sk_1 = Fr::random(OsRng);
+    let pubkeys = [
+        vec![
+            G1Affine::from(G1Affine::generator() * sk_0),
+            G1Affine::from(G1Affine::generator() * sk_1),
+        ],
+        pubkeys[2..].to_vec(),
+    ]
+    .concat();
+    signatures[0] = G2Affine::from(msghashes[0] * sk_1);
+    signatures[1] = G2Affine::from(msghashes[0] * sk_0);
+
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run_builder(|pool, range| {
+        let result = bls_batch_test(pool, range, params, &signatures, &pubkeys, &msghashes);
+        assert_eq!(result, Fr::zero());
+    })
+}
+
+#[test]
+fn test_assert_bls_batch_invalid_signature() {
+    let params = read_params();
+    let (mut signatures, pubkeys, msghashes) = random_batch(params.num_aggregation);
+    signatures[0] = G2Affine::from(msghashes[0] * Fr::random(OsRng));
+
+    base_test()
+        .k(params.degree)
+        .lookup_bits(params.lookup_bits)
+        .expect_satisfied(false)
+        .run_builder(|pool, range| {
+            let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+            let pairing_chip = PairingChip::new(&fp_chip);
+            let gate_chip = GateChip::<Fr>::default();
+            let mut poseidon_chip =
+                PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+            poseidon_chip.initialize_consts(pool.main(), &gate_chip);
+            let batch_chip = BlsBatchChip::new(&fp_chip, &pairing_chip, &poseidon_chip);
+            batch_chip.assert_bls_batch_verify(
+                pool.main(),
+                G1Affine::generator(),
+                &signatures,
+                &pubkeys,
+                &msghashes,
+            );
+        })
+}
+
+#[test]
+fn test_bls_batch_2() {
+    let params = read_params();
+    let (signatures, pubkeys, msghashes) = random_batch_2(params.num_aggregation);
+
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run_builder(|pool, range| {
+        let result = bls_batch_test_2(pool, range, params, &signatures, &pubkeys, &msghashes);
+        assert_eq!(result, Fr::one());
+    })
+}
+
+#[test]
+fn test_bls_batch_2_invalid_signature() {
+    let params = read_params();
+    let (mut signatures, pubkeys, msghashes) = random_batch_2(params.num_aggregation);
+    signatures[0] = G1Affine::from(msghashes[1] * Fr::random(OsRng));
+
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run_builder(|pool, range| {
+        let result = bls_batch_test_2(pool, range, params, &signatures, &pubkeys, &msghashes);
+        assert_eq!(result, Fr::zero());
+    })
+}
+
+#[test]
+fn test_bls_batch_coefficients() {
+    let params = read_params();
+    let (signatures, pubkeys, msghashes) = random_batch(params.num_aggregation);
+    let expected = native::coefficients(
+        &signatures,
+        &pubkeys,
+        &msghashes,
+        params.limb_bits,
+        params.num_limbs,
+        COEFF_BITS,
+    );
+
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run_builder(|pool, range| {
+        let ctx = pool.main();
+        let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+        let pairing_chip = PairingChip::new(&fp_chip);
+        let gate_chip = GateChip::<Fr>::default();
+        let mut poseidon_chip =
+            PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+        poseidon_chip.initialize_consts(ctx, &gate_chip);
+        let batch_chip = BlsBatchChip::new(&fp_chip, &pairing_chip, &poseidon_chip);
+
+        let mut transcript = vec![];
+        for pt in pubkeys.iter() {
+            let pt = pairing_chip.load_private_g1(ctx, *pt);
+            transcript.extend(g1_limbs(&fp_chip, ctx, &pt));
+        }
+        for pt in msghashes.iter().chain(signatures.iter()) {
+            let pt = pairing_chip.load_private_g2(ctx, *pt);
+            transcript.extend(g2_limbs(&fp_chip, ctx, &pt));
+        }
+        let r_is = batch_chip.coefficients(ctx, &transcript, pubkeys.len());
+        for (r_i, expected) in r_is.iter().zip(expected.iter()) {
+            assert_eq!(r_i.value(), expected);
+            assert!(fe_to_biguint(r_i.value()).bits() <= COEFF_BITS as u64);
+        }
+    })
+}
@@ -169,6 +169,93 @@ fn bench_bls_signature() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+// `assert_bls_signature_verify` constrains the signature instead of returning a boolean,
+// so a forged signature must make the circuit unsatisfiable
+fn assert_bls_signature_test(valid: bool) {
+    let path = "configs/bn254/bls_signature_circuit.config";
+    let params: BlsSignatureCircuitParams = serde_json::from_reader(
+        File::open(path).unwrap_or_else(|e| panic!("{path} does not exist: {e:?}")),
+    )
+    .unwrap();
+
+    let msg_hash = G2Affine::random(OsRng);
+    let g1 = G1Affine::generator();
+    let sks = (0..params.num_aggregation).map(|_| Fr::random(OsRng)).collect::<Vec<_>>();
+    let mut signatures = sks.iter().map(|sk| G2Affine::from(msg_hash * sk)).collect::<Vec<_>>();
+    let pubkeys = sks.iter().map(|sk| G1Affine::from(g1 * sk)).collect::<Vec<_>>();
+    if !valid {
+        signatures[0] = G2Affine::random(OsRng);
+    }
+
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).expect_satisfied(valid).run(
+        |ctx, range| {
+            let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+            let pairing_chip = PairingChip::new(&fp_chip);
+            let bls_signature_chip = BlsSignatureChip::new(&fp_chip, &pairing_chip);
+            bls_signature_chip.assert_bls_signature_verify(ctx, g1, &signatures, &pubkeys, msg_hash);
+        },
+    )
+}
+
+#[test]
+fn test_assert_bls_signature() {
+    assert_bls_signature_test(true);
+}
+
+#[test]
+fn test_assert_bls_signature_invalid() {
+    assert_bls_signature_test(false);
+}
+
+// Signers i sign distinct messages m_i; verify e(g1, signature_agg) = prod_i e(pubkey_i, H(m_i))
+fn bls_aggregate_verify_test(num_signers: usize, distinct: bool) {
+    let run_path = "configs/bn254/bls_signature_circuit.config";
+    let path = run_path;
+    let params: BlsSignatureCircuitParams = serde_json::from_reader(
+        File::open(path).unwrap_or_else(|e| panic!("{path} does not exist: {e:?}")),
+    )
+    .unwrap();
+
+    let g1 = G1Affine::generator();
+    let mut msg_hashes = (0..num_signers).map(|_| G2Affine::random(OsRng)).collect::<Vec<_>>();
+    if !distinct {
+        msg_hashes[1] = msg_hashes[0];
+    }
+    let sks = (0..num_signers).map(|_| Fr::random(OsRng)).collect::<Vec<_>>();
+    let mut signature_agg = msg_hashes[0] * sks[0];
+    for (sk, msg_hash) in sks.iter().zip(msg_hashes.iter()).skip(1) {
+        signature_agg += msg_hash * sk;
+    }
+    let signature_agg = G2Affine::from(signature_agg);
+    let pubkeys = sks.iter().map(|sk| G1Affine::from(g1 * sk)).collect::<Vec<_>>();
+
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).expect_satisfied(distinct).run(
+        |ctx, range| {
+            let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+            let pairing_chip = PairingChip::new(&fp_chip);
+            let bls_signature_chip = BlsSignatureChip::new(&fp_chip, &pairing_chip);
+            let result = bls_signature_chip.bls_aggregate_verify(
+                ctx,
+                g1,
+                signature_agg,
+                &pubkeys,
+                &msg_hashes,
+            );
+            assert_eq!(*result.value(), Fr::one());
+        },
+    )
+}
+
+#[test]
+fn test_bls_aggregate_verify() {
+    bls_aggregate_verify_test(4, true);
+}
+
+#[test]
+fn test_bls_aggregate_verify_duplicate_msg() {
+    bls_aggregate_verify_test(4, false);
+}
+
 
 // #[test]
 // fn bls_with_merkle_tree_test<F:BigPrimeField>(
@@ -12,13 +12,16 @@ use itertools::Itertools;
 use serde::{Serialize, Deserialize};
 use super::*;
 use crate::bn254::{
-    MerkleInfo, merkle_tree::MerkleTreeChip, 
+    MerkleInfo, merkle_tree::{native as merkle_native, MerkleTreeChip},
     bls_signature::BlsSignatureChip, 
-    combine_bls_mt::CombineBlsMtChip
+    combine_bls_mt::{native as combine_native, CombineBlsMtChip},
+    instances::native as instances_native,
 };
 use crate::halo2_proofs::halo2curves::bn256::G2Affine;
+use crate::halo2curves::pairing::group::ff::Field;
 use std::io::Read;
-use rand::seq::SliceRandom; // For random selection
+use rand::seq::index::sample; // For random selection
+use rand_core::OsRng;
 
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -35,7 +38,11 @@ pub struct MerklePath {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MerkleData {
     message: String,
+    // [hash]G of the message, the circuit hashes the message to the curve instead
+    #[allow(dead_code)]
     hash_msg: String,
+    // root over the x coordinates only, the tests rebuild the tree over the full point encodings
+    #[allow(dead_code)]
     root: String,
     leaves: Vec<MerklePath>,
 }
@@ -68,6 +75,28 @@ fn fq_from_string(s: &str) -> Fq {
     result
 }
 
+// Rebuilds the committee tree over the full point encodings of all keys in the data file,
+// returns the root together with the merkle infos, pubkeys and secret keys of `num_agg` random signers
+fn load_committee(
+    json_data: &MerkleData,
+    params: CombineBlsMtCircuitParams,
+) -> (Fr, Vec<MerkleInfo<Fr>>, Vec<G1Affine>, Vec<Fr>) {
+    let all_pubkeys = json_data.leaves.iter().map(|x|
+        G1Affine::from_xy(fq_from_string(&x.pk_x), fq_from_string(&x.pk_y)).unwrap()
+    ).collect_vec();
+    let leaves = all_pubkeys.iter().map(|pk|
+        merkle_native::g1_leaf(pk, params.limb_bits, params.num_limbs)
+    ).collect_vec();
+    let root = merkle_native::merkle_root(&leaves);
+
+    let mut rng = rand::thread_rng();
+    let selected = sample(&mut rng, json_data.leaves.len(), params.num_aggregation as usize).into_vec();
+    let merkle_infos = selected.iter().map(|i| merkle_native::merkle_info(&leaves, *i)).collect_vec();
+    let pubkeys = selected.iter().map(|i| all_pubkeys[*i]).collect_vec();
+    let sks = selected.iter().map(|i| fr_from_string(&json_data.leaves[*i].sk)).collect_vec();
+    (root, merkle_infos, pubkeys, sks)
+}
+
 fn combine_bls_mt_test<F: BigPrimeField>(
     ctx: &mut Context<F>,
     range: &RangeChip<F>,
@@ -78,7 +107,7 @@ fn combine_bls_mt_test<F: BigPrimeField>(
     signatures: &[G2Affine],
     pubkeys: &[G1Affine],
     message: F,
-) {
+) -> F {
     let fp_chip = FpChip::<F>::new(range, params.limb_bits, params.num_limbs);
     let pairing_chip = PairingChip::new(&fp_chip);
     let bls_signature_chip = BlsSignatureChip::new(&fp_chip, &pairing_chip);
@@ -89,8 +118,31 @@ fn combine_bls_mt_test<F: BigPrimeField>(
     let combine_bls_mt_chip = CombineBlsMtChip::new(&bls_signature_chip, merkle_tree_chip);
 
     let result = combine_bls_mt_chip.combine_bls_mt_verify(ctx, root, merkle_infos, g1, signatures, pubkeys, message);
+    *result.value()
+}
 
-    assert_eq!(*result.value(), F::from(1));
+fn combine_bls_mt_2_test<F: BigPrimeField>(
+    ctx: &mut Context<F>,
+    range: &RangeChip<F>,
+    params: CombineBlsMtCircuitParams,
+    root: F,
+    merkle_infos: &[MerkleInfo<F>],
+    g2: G2Affine,
+    signatures: &[G1Affine],
+    pubkeys: &[G2Affine],
+    message: F,
+) -> Vec<F> {
+    let fp_chip = FpChip::<F>::new(range, params.limb_bits, params.num_limbs);
+    let pairing_chip = PairingChip::new(&fp_chip);
+    let bls_signature_chip = BlsSignatureChip::new(&fp_chip, &pairing_chip);
+    let gate_chip = GateChip::<F>::default();
+    let mut poseidon_chip = PoseidonHasher::<F, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+    poseidon_chip.initialize_consts(ctx, &gate_chip);
+    let merkle_tree_chip = MerkleTreeChip::new(&poseidon_chip, gate_chip);
+    let combine_bls_mt_chip = CombineBlsMtChip::new(&bls_signature_chip, merkle_tree_chip);
+
+    let statement = combine_bls_mt_chip.combine_bls_mt_verify_2_public(ctx, root, merkle_infos, g2, signatures, pubkeys, message);
+    statement.instances().iter().map(|x| *x.value()).collect()
 }
 #[test]
 fn test_combine_bls_mt() {
@@ -106,46 +158,79 @@ fn test_combine_bls_mt() {
     file.read_to_string(&mut data).expect("Unable to read file");
 
     let json_data: MerkleData = serde_json::from_str(&data).expect("Invalid JSON");
-    let root = f_from_string::<Fr>(&json_data.root);
     let message = json_data.message.clone();
     let message = f_from_string(&message);
-    let msg_hash = json_data.hash_msg.clone();
-    let msg_hash_to_fr = fr_from_string(&msg_hash);
-    let msg_hash = G2Affine::from(G2Affine::generator() * msg_hash_to_fr);
+    let msg_hash = combine_native::message_hash(message);
 
-    let mut rng = rand::thread_rng();
-    let num_agg = params.num_aggregation as usize;
-    let selected_leaves: Vec<&MerklePath> = json_data.leaves.choose_multiple(&mut rng, num_agg).collect();
-    let pubkeys = selected_leaves.iter().map(|x| 
-        G1Affine::from_xy(fq_from_string(&x.pk_x), fq_from_string(&x.pk_y)).unwrap()
-    ).collect_vec();
-    let sks = selected_leaves.iter().map(|x| fr_from_string(&x.sk)).collect_vec();
+    let (root, merkle_infos, pubkeys, sks) = load_committee(&json_data, params);
+    let signatures = sks.iter().map(|x| G2Affine::from(msg_hash * x)).collect_vec();
 
-    // TODO: fix this
-    // let message_str = "msg_hash".to_string();
-    // let msg_hash ="ec335129ec86d9704a3c93b47c7280fbe5d42b6768dd43ef613edb188f102b2e".to_string();
-    // let msg_to_fr = fr_from_string(&msg_hash);
-    // println!("msg_byte:{:?}",msg_to_fr);
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run(|ctx, range| {
+        let result = combine_bls_mt_test(ctx,range,params,root, &merkle_infos, G1Affine::generator()
+                , &signatures, &pubkeys, message);
+        assert_eq!(result, Fr::one());
+    });
+}
 
-    // let msg_hash = G2Affine::from(G2Affine::generator() * Fr::from(OsRng));
-    // let msg_hash = G2Affine::from(G2Affine::generator() * Fr::from(123456));
-    // let msg_hash = G2Affine::from(G2Affine::generator() * msg_to_fr);
+// A signer whose key is not the one committed in its leaf must be rejected even if its signature is valid
+#[test]
+fn test_combine_bls_mt_wrong_key() {
+    let path = "configs/bn254/combine_bls_mt.config";
+    let params: CombineBlsMtCircuitParams = serde_json::from_reader(
+        File::open(path).unwrap_or_else(|e| panic!("{path} does not exist: {e:?}")),
+    ).unwrap();
 
-    // println!("msg_hash:{:?}",msg_hash);
+    let merkle_input_path = "data/merkle_tree_from_g1_{num}.json".replace("{num}", &params.num_origin.to_string());
+    let mut file = File::open(merkle_input_path).expect("Unable to open file");
+    let mut data = String::new();
+    file.read_to_string(&mut data).expect("Unable to read file");
+    let json_data: MerkleData = serde_json::from_str(&data).expect("Invalid JSON");
+    let message = f_from_string(&json_data.message);
+    let msg_hash = combine_native::message_hash(message);
 
+    let (root, merkle_infos, mut pubkeys, mut sks) = load_committee(&json_data, params);
+    sks[0] = Fr::random(OsRng);
+    pubkeys[0] = G1Affine::from(G1Affine::generator() * sks[0]);
     let signatures = sks.iter().map(|x| G2Affine::from(msg_hash * x)).collect_vec();
 
-    let merkle_infos = selected_leaves.iter().map(|path| {
-        let leaf = fr_from_string(&path.pk_x);
-        let path_vals: Vec<Fr> = path.path.iter().map(|s| f_from_string(s)).collect();
-        let index = path.index.clone();
-        MerkleInfo { leaf, path: path_vals, index }
-    }).collect_vec();
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run(|ctx, range| {
+        let result = combine_bls_mt_test(ctx, range, params, root, &merkle_infos, G1Affine::generator(),
+                &signatures, &pubkeys, message);
+        assert_eq!(result, Fr::zero());
+    });
+}
 
+#[test]
+fn test_combine_bls_mt_2() {
+    let path = "configs/bn254/combine_bls_mt.config";
+    let params: CombineBlsMtCircuitParams = serde_json::from_reader(
+        File::open(path).unwrap_or_else(|e| panic!("{path} does not exist: {e:?}")),
+    ).unwrap();
+
+    let g2 = G2Affine::generator();
+    let committee_sks = (0..params.num_origin).map(|_| Fr::random(OsRng)).collect_vec();
+    let committee = committee_sks.iter().map(|sk| G2Affine::from(g2 * sk)).collect_vec();
+    let leaves = committee.iter().map(|pk|
+        merkle_native::g2_leaf(pk, params.limb_bits, params.num_limbs)
+    ).collect_vec();
+    let root = merkle_native::merkle_root(&leaves);
+
+    let mut rng = rand::thread_rng();
+    let selected = sample(&mut rng, committee.len(), params.num_aggregation as usize).into_vec();
+    let merkle_infos = selected.iter().map(|i| merkle_native::merkle_info(&leaves, *i)).collect_vec();
+    let pubkeys = selected.iter().map(|i| committee[*i]).collect_vec();
+
+    let message = Fr::random(OsRng);
+    let msg_hash = combine_native::message_hash_2(message);
+    let signatures = selected.iter().map(|i| G1Affine::from(msg_hash * committee_sks[*i])).collect_vec();
 
     base_test().k(params.degree).lookup_bits(params.lookup_bits).run(|ctx, range| {
-        combine_bls_mt_test(ctx,range,params,root, &merkle_infos, G1Affine::generator()
-                , &signatures, &pubkeys, message);
+        let instances = combine_bls_mt_2_test(ctx, range, params, root, &merkle_infos, g2, &signatures, &pubkeys, message);
+        // result, root, message digest and aggregate key, as a verifier would recompute them
+        let expected = instances_native::expected_instances(&[instances_native::combine_bls_mt_statement_2(
+            root, message, &pubkeys, params.limb_bits, params.num_limbs,
+        )]);
+        assert_eq!(instances, expected);
     });
 }
 
@@ -174,28 +259,12 @@ fn bench_merkle_tree() -> Result<(), Box<dyn std::error::Error>> {
 
         let json_data: MerkleData = serde_json::from_str(&data).expect("Invalid JSON");
 
-        let root = f_from_string::<Fr>(&json_data.root);
         let message = json_data.message.clone();
         let message = f_from_string::<Fr>(&message);
-        let msg_hash = json_data.hash_msg.clone();
-        let msg_hash_to_fr = fr_from_string(&msg_hash);
-        let msg_hash = G2Affine::from(G2Affine::generator() * msg_hash_to_fr);
-
-        let mut rng = rand::thread_rng();
-        let num_agg = bench_params.num_aggregation as usize;
-        let selected_leaves: Vec<&MerklePath> = json_data.leaves.choose_multiple(&mut rng, num_agg).collect();
-        let pubkeys = selected_leaves.iter().map(|x| 
-            G1Affine::from_xy(fq_from_string(&x.pk_x), fq_from_string(&x.pk_y)).unwrap()
-        ).collect_vec();
-        let sks = selected_leaves.iter().map(|x| fr_from_string(&x.sk)).collect_vec();
+        let msg_hash = combine_native::message_hash(message);
 
+        let (root, merkle_infos, pubkeys, sks) = load_committee(&json_data, bench_params);
         let signatures = sks.iter().map(|x| G2Affine::from(msg_hash * x)).collect_vec();
-        let merkle_infos = selected_leaves.iter().map(|path| {
-            let leaf = fr_from_string(&path.pk_x);
-            let path_vals: Vec<Fr> = path.path.iter().map(|s| f_from_string(s)).collect();
-            let index = path.index.clone();
-            MerkleInfo { leaf, path: path_vals, index }
-        }).collect_vec();
         let g1 = G1Affine::generator();
         let stats = base_test().k(k).lookup_bits(bench_params.lookup_bits).bench_builder(
             (root, merkle_infos.clone(), g1, signatures.clone(), pubkeys.clone(), message.clone()),
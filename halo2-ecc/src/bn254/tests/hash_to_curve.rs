@@ -0,0 +1,282 @@
+use std::fs::File;
+
+use super::*;
+use crate::bn254::bls_signature::BlsSignatureChip;
+use crate::bn254::hash_to_curve::{
+    native, HashToCurveChip, Sha256Input, Sha256Promise, DST_G2, HASH_TO_G2_LEN_IN_BYTES,
+};
+use crate::fields::FieldChip;
+use crate::halo2_proofs::arithmetic::CurveAffine;
+use crate::halo2_proofs::halo2curves::bn256::{Fq, Fq2, G2Affine};
+use crate::halo2curves::pairing::group::ff::Field;
+use halo2_base::gates::RangeChip;
+use halo2_base::safe_types::{SafeByte, SafeTypeChip};
+use halo2_base::utils::{BigPrimeField, ScalarField};
+use halo2_base::Context;
+use rand_core::OsRng;
+use sha2::{Digest, Sha256};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+struct HashToCurveCircuitParams {
+    strategy: FpStrategy,
+    degree: u32,
+    num_advice: usize,
+    num_lookup_advice: usize,
+    num_fixed: usize,
+    lookup_bits: usize,
+    limb_bits: usize,
+    num_limbs: usize,
+}
+
+const MAX_MSG_LEN: usize = 64;
+
+// DST of the expand_message_xmd test vectors in RFC 9380 Appendix K.1
+const QUUX_DST: &[u8] = b"QUUX-V01-CS02-with-expander-SHA256-128";
+
+// DST of the hash_to_g2 test vectors below, in the style of the RFC 9380 Appendix J suites
+const QUUX_G2_DST: &[u8] = b"QUUX-V01-CS02-with-BN254G2_XMD:SHA-256_SVDW_RO_";
+
+// Test vectors for BN254G2_XMD:SHA-256_SVDW_RO_ with L = 48: msg, u[0], u[1] and the output point (x, y),
+// each element of Fp2 as (c0, c1) in big-endian hex.
+// Computed with an independent Python implementation of RFC 9380 (expand_message_xmd, hash_to_field,
+// the SVDW map with Z = 1 and the same cofactor clearing), not with this crate.
+#[allow(clippy::type_complexity)]
+const HASH_TO_G2_VECTORS: [(&[u8], [(&str, &str); 4]); 2] = [
+    (
+        b"",
+        [
+            (
+                "2c85988ecf26034a6d6c495c467150aeaead51fceb623aa99b0433275c8952c7",
+                "182126b31e6df7cf33844bf16a92f42072ee47f80539dace68dbfc3380d1fcbd",
+            ),
+            (
+                "1c3035901eab4768d522b3d0eb7e58b05c130603c8f43587345dc51745fa3533",
+                "23597b1c4f238038ba6579d203e7fcb7d427c63d4e0d037185453168718203bb",
+            ),
+            (
+                "1192005a0f121921a6d5629946199e4b27ff8ee4d6dd4f9581dc550ade851300",
+                "1747d950a6f23c16156e2171bce95d1189b04148ad12628869ed21c96a8c9335",
+            ),
+            (
+                "0498f6bb5ac309a07d9a8b88e6ff4b8de0d5f27a075830e1eb0e68ea318201d8",
+                "2c9755350ca363ef2cf541005437221c5740086c2e909b71d075152484e845f4",
+            ),
+        ],
+    ),
+    (
+        b"abc",
+        [
+            (
+                "234b244ed36d5acbb96a4f5fb67094945a0bb4ecf33d55bcc218ce834dc82c63",
+                "04ca11f51d0cf7e7393a0e6d7be3d0e6b07652d5ba308554a72dafe502dd59cc",
+            ),
+            (
+                "1c31ec87881353ec57fc87c27e31099a0705390c52dbfc8c047d14260658df71",
+                "2daa8e05eb3367285b5de508d248b3153207498f3e9e51cbe6183ff7dae286a6",
+            ),
+            (
+                "16c88b54eec9af86a41569608cd0f60aab43464e52ce7e6e298bf584b94fccd2",
+                "0b5db3ca7e8ef5edf3a33dfc3242357fbccead98099c3eb564b3d9d13cba4efd",
+            ),
+            (
+                "1c42ba524cb74db8e2c680449746c028f7bea923f245e69f89256af2d6c5f3ac",
+                "22d02d2da7f288545ff8789e789902245ab08c6b1d253561eec789ec2c1bd630",
+            ),
+        ],
+    ),
+];
+
+fn fq2_from_hex((c0, c1): (&str, &str)) -> Fq2 {
+    let fq = |be_hex: &str| {
+        let mut bytes: [u8; 32] = hex::decode(be_hex).unwrap().try_into().unwrap();
+        bytes.reverse();
+        Fq::from_bytes(&bytes).unwrap()
+    };
+    Fq2 { c0: fq(c0), c1: fq(c1) }
+}
+
+fn read_params() -> HashToCurveCircuitParams {
+    let path = "configs/bn254/hash_to_curve_circuit.config";
+    serde_json::from_reader(
+        File::open(path).unwrap_or_else(|e| panic!("{path} does not exist: {e:?}")),
+    )
+    .unwrap()
+}
+
+pub(super) fn load_var_len_msg<F: BigPrimeField>(
+    ctx: &mut Context<F>,
+    range: &RangeChip<F>,
+    msg: &[u8],
+) -> halo2_base::safe_types::VarLenBytes<F, MAX_MSG_LEN> {
+    let mut padded = msg.to_vec();
+    padded.resize(MAX_MSG_LEN, 0);
+    let bytes = padded.iter().map(|b| ctx.load_witness(F::from(*b as u64))).collect::<Vec<_>>();
+    let len = ctx.load_witness(F::from(msg.len() as u64));
+    SafeTypeChip::new(range).raw_to_var_len_bytes::<MAX_MSG_LEN>(
+        ctx,
+        bytes.try_into().unwrap(),
+        len,
+    )
+}
+
+fn hash_to_g2_test<F: BigPrimeField>(
+    ctx: &mut Context<F>,
+    range: &RangeChip<F>,
+    params: HashToCurveCircuitParams,
+    msg: &[u8],
+) {
+    let fp_chip = FpChip::<F>::new(range, params.limb_bits, params.num_limbs);
+    let fp2_chip = Fp2Chip::<F>::new(&fp_chip);
+    let hash_to_curve_chip = HashToCurveChip::new(&fp_chip, DST_G2);
+
+    let msg_assigned = load_var_len_msg(ctx, range, msg);
+    let hash = hash_to_curve_chip.hash_to_g2_var_len(ctx, &msg_assigned);
+
+    let expected = native::hash_to_g2(msg, DST_G2);
+    assert_eq!(fp2_chip.get_assigned_value(&hash.x.into()), expected.x);
+    assert_eq!(fp2_chip.get_assigned_value(&hash.y.into()), expected.y);
+}
+
+#[test]
+fn test_expand_message_xmd_native() {
+    let uniform_bytes = native::expand_message_xmd(b"", QUUX_DST, 0x20);
+    assert_eq!(
+        hex::encode(uniform_bytes),
+        "68a985b87eb6b46952128911f2a4412bbc302a9d759667f87f7a21d803f07235"
+    );
+    let uniform_bytes = native::expand_message_xmd(b"abc", QUUX_DST, 0x20);
+    assert_eq!(
+        hex::encode(uniform_bytes),
+        "d8ccab23b5985ccea865c6c97b6e5b8350e794e603b4b97902f53a8a0d605615"
+    );
+}
+
+#[test]
+fn test_hash_to_g2_native_vectors() {
+    for (msg, [u0, u1, x, y]) in HASH_TO_G2_VECTORS {
+        assert_eq!(native::hash_to_field(msg, QUUX_G2_DST), [fq2_from_hex(u0), fq2_from_hex(u1)]);
+        let point = native::hash_to_g2(msg, QUUX_G2_DST);
+        assert_eq!((point.x, point.y), (fq2_from_hex(x), fq2_from_hex(y)));
+    }
+}
+
+#[test]
+fn test_hash_to_g2_native_in_subgroup() {
+    let point = native::hash_to_g2(b"abc", DST_G2);
+    assert!(bool::from(point.is_on_curve()));
+    // [r - 1]P = -P iff P has order r
+    assert_eq!(G2Affine::from(point * -Fr::one()), -point);
+}
+
+#[test]
+fn test_expand_message_xmd() {
+    let params = read_params();
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run(|ctx, range| {
+        let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+        let hash_to_curve_chip = HashToCurveChip::new(&fp_chip, QUUX_DST);
+        let expected = native::expand_message_xmd(b"abc", QUUX_DST, 0x80);
+
+        let msg = [b'a', b'b', b'c'].map(|b| ctx.load_witness(Fr::from(b as u64)));
+        let msg = SafeTypeChip::new(range).raw_to_fix_len_bytes(ctx, msg);
+        let uniform_bytes = hash_to_curve_chip.expand_message_xmd_fix_len(ctx, &msg, 0x80);
+        let uniform_bytes =
+            uniform_bytes.iter().map(|b| b.value().get_lower_32() as u8).collect::<Vec<_>>();
+        assert_eq!(uniform_bytes, expected);
+
+        let msg = load_var_len_msg(ctx, range, b"abc");
+        let uniform_bytes = hash_to_curve_chip.expand_message_xmd_var_len(ctx, &msg, 0x80);
+        let uniform_bytes =
+            uniform_bytes.iter().map(|b| b.value().get_lower_32() as u8).collect::<Vec<_>>();
+        assert_eq!(uniform_bytes, expected);
+    })
+}
+
+#[test]
+fn test_hash_to_g2() {
+    let params = read_params();
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run(|ctx, range| {
+        hash_to_g2_test(ctx, range, params, b"hello world");
+    })
+}
+
+#[test]
+fn test_hash_to_g2_vectors() {
+    let params = read_params();
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run(|ctx, range| {
+        let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+        let fp2_chip = Fp2Chip::<Fr>::new(&fp_chip);
+        let hash_to_curve_chip = HashToCurveChip::new(&fp_chip, QUUX_G2_DST);
+        for (msg, [_, _, x, y]) in HASH_TO_G2_VECTORS {
+            let msg = load_var_len_msg(ctx, range, msg);
+            let hash = hash_to_curve_chip.hash_to_g2_var_len(ctx, &msg);
+            assert_eq!(fp2_chip.get_assigned_value(&hash.x.into()), fq2_from_hex(x));
+            assert_eq!(fp2_chip.get_assigned_value(&hash.y.into()), fq2_from_hex(y));
+        }
+    })
+}
+
+// Every SHA-256 call is recorded as a promise whose digest is the SHA-256 of its input
+#[test]
+fn test_hash_to_g2_sha256_promises() {
+    let params = read_params();
+    let msg = b"hello world";
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run(|ctx, range| {
+        let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+        let hash_to_curve_chip = HashToCurveChip::new(&fp_chip, DST_G2);
+        let to_bytes = |bytes: &[SafeByte<Fr>]| {
+            bytes.iter().map(|b| b.as_ref().value().get_lower_32() as u8).collect::<Vec<_>>()
+        };
+
+        let msg_assigned = load_var_len_msg(ctx, range, msg);
+        hash_to_curve_chip.hash_to_g2_var_len(ctx, &msg_assigned);
+        let msg_fix = msg.map(|b| ctx.load_witness(Fr::from(b as u64)));
+        let msg_fix = SafeTypeChip::new(range).raw_to_fix_len_bytes(ctx, msg_fix);
+        hash_to_curve_chip.hash_to_g2_fix_len(ctx, &msg_fix);
+
+        let promises = hash_to_curve_chip.sha256_promises();
+        // b_0, ..., b_ell for each hash
+        let ell = HASH_TO_G2_LEN_IN_BYTES / 32;
+        assert_eq!(promises.len(), 2 * (ell + 1));
+        for promise in &promises {
+            let input = match &promise.input {
+                Sha256Input::Fix(bytes) => to_bytes(bytes.bytes()),
+                Sha256Input::Var(bytes) => {
+                    let len = bytes.len().value().get_lower_32() as usize;
+                    to_bytes(&bytes.bytes()[..len])
+                }
+            };
+            assert_eq!(to_bytes(promise.digest.bytes()), Sha256::digest(input).to_vec());
+        }
+        // the var length b_0 commits to the same bytes as the fixed length one
+        let b_0 = |promise: &Sha256Promise<Fr>| to_bytes(promise.digest.bytes());
+        assert_eq!(b_0(&promises[0]), b_0(&promises[ell + 1]));
+    })
+}
+
+#[test]
+fn test_bls_signature_msg() {
+    let params = read_params();
+    let msg = b"hello world";
+    let g1 = G1Affine::generator();
+    let msg_hash = native::hash_to_g2(msg, DST_G2);
+    let sks = (0..2).map(|_| Fr::random(OsRng)).collect::<Vec<_>>();
+    let signatures = sks.iter().map(|sk| G2Affine::from(msg_hash * sk)).collect::<Vec<_>>();
+    let pubkeys = sks.iter().map(|sk| G1Affine::from(g1 * sk)).collect::<Vec<_>>();
+
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run(|ctx, range| {
+        let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+        let pairing_chip = PairingChip::new(&fp_chip);
+        let bls_signature_chip = BlsSignatureChip::new(&fp_chip, &pairing_chip);
+        let hash_to_curve_chip = HashToCurveChip::new(&fp_chip, DST_G2);
+        let msg = load_var_len_msg(ctx, range, msg);
+        let result = bls_signature_chip.bls_signature_verify_msg(
+            ctx,
+            g1,
+            &signatures,
+            &pubkeys,
+            &msg,
+            &hash_to_curve_chip,
+        );
+        assert_eq!(*result.value(), Fr::one());
+    })
+}
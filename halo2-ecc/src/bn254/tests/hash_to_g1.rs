@@ -0,0 +1,101 @@
+use std::fs::File;
+
+use super::*;
+use crate::bn254::bls_signature_inverse::BlsSignatureChip2;
+use crate::bn254::hash_to_g1::{native, HashToG1Chip, DST_G1};
+use crate::fields::FieldChip;
+use crate::halo2_proofs::arithmetic::CurveAffine;
+use crate::halo2_proofs::halo2curves::bn256::G2Affine;
+use crate::halo2curves::pairing::group::ff::Field;
+use halo2_base::poseidon::hasher::{spec::OptimizedPoseidonSpec, PoseidonHasher};
+use rand_core::OsRng;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+struct HashToG1CircuitParams {
+    strategy: FpStrategy,
+    degree: u32,
+    num_advice: usize,
+    num_lookup_advice: usize,
+    num_fixed: usize,
+    lookup_bits: usize,
+    limb_bits: usize,
+    num_limbs: usize,
+}
+
+const MAX_MSG_LEN: usize = 8;
+
+fn read_params() -> HashToG1CircuitParams {
+    let path = "configs/bn254/hash_to_g1_circuit.config";
+    serde_json::from_reader(
+        File::open(path).unwrap_or_else(|e| panic!("{path} does not exist: {e:?}")),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_hash_to_g1_native_on_curve() {
+    let msg = [Fr::from(1), Fr::from(2), Fr::from(3)];
+    let point = native::hash_to_g1(&msg, DST_G1);
+    assert!(bool::from(point.is_on_curve()));
+    assert_ne!(point, native::hash_to_g1(&msg[..2], DST_G1));
+}
+
+#[test]
+fn test_hash_to_g1() {
+    let params = read_params();
+    let msg = (0..5).map(|_| Fr::random(OsRng)).collect::<Vec<_>>();
+    let expected = native::hash_to_g1(&msg, DST_G1);
+
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run(|ctx, range| {
+        let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+        let mut poseidon_chip =
+            PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+        poseidon_chip.initialize_consts(ctx, &range.gate);
+        let hash_to_g1_chip = HashToG1Chip::new(&fp_chip, &poseidon_chip, DST_G1);
+
+        let msg_assigned = ctx.assign_witnesses(msg.clone());
+        let hash = hash_to_g1_chip.hash_to_g1(ctx, &msg_assigned);
+        assert_eq!(fp_chip.get_assigned_value(&hash.x.into()), expected.x);
+        assert_eq!(fp_chip.get_assigned_value(&hash.y.into()), expected.y);
+
+        let mut padded = msg.clone();
+        padded.resize(MAX_MSG_LEN, Fr::zero());
+        let padded = ctx.assign_witnesses(padded);
+        let len = ctx.load_witness(Fr::from(msg.len() as u64));
+        let hash = hash_to_g1_chip.hash_to_g1_var_len(ctx, &padded, len);
+        assert_eq!(fp_chip.get_assigned_value(&hash.x.into()), expected.x);
+        assert_eq!(fp_chip.get_assigned_value(&hash.y.into()), expected.y);
+    })
+}
+
+#[test]
+fn test_bls_signature_msg() {
+    let params = read_params();
+    let msg = [Fr::from(42), Fr::from(7)];
+    let g2 = G2Affine::generator();
+    let msg_hash = native::hash_to_g1(&msg, DST_G1);
+    let sks = (0..2).map(|_| Fr::random(OsRng)).collect::<Vec<_>>();
+    let signatures = sks.iter().map(|sk| G1Affine::from(msg_hash * sk)).collect::<Vec<_>>();
+    let pubkeys = sks.iter().map(|sk| G2Affine::from(g2 * sk)).collect::<Vec<_>>();
+
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run(|ctx, range| {
+        let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+        let pairing_chip = PairingChip::new(&fp_chip);
+        let mut poseidon_chip =
+            PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+        poseidon_chip.initialize_consts(ctx, &range.gate);
+        let hash_to_g1_chip = HashToG1Chip::new(&fp_chip, &poseidon_chip, DST_G1);
+        let bls_signature_chip = BlsSignatureChip2::new(&fp_chip, &pairing_chip);
+
+        let msg_assigned = ctx.assign_witnesses(msg);
+        let result = bls_signature_chip.bls_signature_verify_msg(
+            ctx,
+            g2,
+            &signatures,
+            &pubkeys,
+            &msg_assigned,
+            &hash_to_g1_chip,
+        );
+        assert_eq!(*result.value(), Fr::one());
+    })
+}
@@ -10,7 +10,9 @@ use halo2_base::utils::BigPrimeField;
 // use rand::rngs::OsRng;
 use serde::{Serialize, Deserialize};
 use super::*;
-use crate::bn254::{MerkleInfo, merkle_tree::MerkleTreeChip};
+use crate::bn254::{MerkleInfo, MerkleMultiProof, merkle_tree::{native as merkle_native, MerklePadding, MerkleTreeChip}};
+use crate::halo2curves::pairing::group::ff::Field;
+use rand_core::OsRng;
 // use crate::halo2_proofs::halo2curves::bn256::G2Affine;
 use halo2_base::poseidon::hasher::spec::OptimizedPoseidonSpec;
 use std::io::Read;
@@ -119,6 +121,165 @@ fn test_merkle_tree() {
     // })
 }
 
+// Verifies leaves[index] against the root with the leaf index assigned in circuit
+fn merkle_tree_index_test<F: BigPrimeField>(
+    ctx: &mut Context<F>,
+    root: F,
+    leaf: F,
+    path: &[F],
+    index: u64,
+) -> F {
+    let mut poseidon_chip = PoseidonHasher::<F, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+    let gate_chip = GateChip::<F>::default();
+    poseidon_chip.initialize_consts(ctx, &gate_chip);
+    let merkle_tree_chip = MerkleTreeChip::new(&poseidon_chip, gate_chip);
+
+    let leaf = ctx.load_witness(leaf);
+    let leaf_index = ctx.load_witness(F::from(index));
+    let result = merkle_tree_chip.merkle_tree_verify_index(ctx, root, leaf, path, leaf_index);
+    *result.value()
+}
+
+#[test]
+fn test_merkle_tree_index() {
+    let leaves = (0..16).map(|_| Fr::random(OsRng)).collect::<Vec<_>>();
+    let root = merkle_native::merkle_root(&leaves);
+    let merkle_info = merkle_native::merkle_info(&leaves, 5);
+
+    base_test().k(12).run(|ctx, _range| {
+        let result = merkle_tree_index_test(ctx, root, merkle_info.leaf, &merkle_info.path, 5);
+        assert_eq!(result, Fr::one());
+        // the same path with the directions of another position does not reach the root
+        let result = merkle_tree_index_test(ctx, root, merkle_info.leaf, &merkle_info.path, 4);
+        assert_eq!(result, Fr::zero());
+    });
+}
+
+// The circuit does not depend on the position of the leaf, so keys generated for one index prove another
+#[test]
+fn test_merkle_tree_index_any_position() {
+    let leaves = (0..16).map(|_| Fr::random(OsRng)).collect::<Vec<_>>();
+    let root = merkle_native::merkle_root(&leaves);
+    let keygen_info = merkle_native::merkle_info(&leaves, 3);
+    let prover_info = merkle_native::merkle_info(&leaves, 12);
+
+    base_test().k(12).bench_builder(
+        (keygen_info, 3),
+        (prover_info, 12),
+        |pool, _range, (merkle_info, index)| {
+            let result = merkle_tree_index_test(pool.main(), root, merkle_info.leaf, &merkle_info.path, index);
+            assert_eq!(result, Fr::one());
+        },
+    );
+}
+
+fn merkle_multiproof_test<F: BigPrimeField>(
+    ctx: &mut Context<F>,
+    root: F,
+    proof: &MerkleMultiProof<F>,
+) -> F {
+    let mut poseidon_chip = PoseidonHasher::<F, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+    let gate_chip = GateChip::<F>::default();
+    poseidon_chip.initialize_consts(ctx, &gate_chip);
+    let merkle_tree_chip = MerkleTreeChip::new(&poseidon_chip, gate_chip);
+
+    let result = merkle_tree_chip.merkle_tree_verify_multiproof(ctx, root, proof);
+    *result.value()
+}
+
+#[test]
+fn test_merkle_multiproof() {
+    let leaves = (0..32).map(|_| Fr::random(OsRng)).collect::<Vec<_>>();
+    let root = merkle_native::merkle_root(&leaves);
+    let indices = [1, 2, 3, 9, 20, 31];
+    let proof = merkle_native::merkle_multiproof(&leaves, &indices);
+    // shared nodes are neither sent nor hashed twice
+    assert!(proof.siblings.len() < indices.len() * proof.depth);
+
+    let mut wrong_proof = proof.clone();
+    wrong_proof.siblings[0] += Fr::one();
+
+    base_test().k(12).run(|ctx, _range| {
+        assert_eq!(merkle_multiproof_test(ctx, root, &proof), Fr::one());
+        assert_eq!(merkle_multiproof_test(ctx, root, &wrong_proof), Fr::zero());
+    });
+}
+
+#[test]
+fn test_compute_root() {
+    for padding in [MerklePadding::Zero, MerklePadding::DuplicateLast] {
+        for num_leaves in [1, 8, 13] {
+            let leaves = (0..num_leaves).map(|_| Fr::random(OsRng)).collect::<Vec<_>>();
+            let expected = merkle_native::merkle_root_with_padding(&leaves, padding);
+
+            base_test().k(14).run_builder(|pool, _range| {
+                let ctx = pool.main();
+                let gate_chip = GateChip::<Fr>::default();
+                let mut poseidon_chip =
+                    PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+                poseidon_chip.initialize_consts(ctx, &gate_chip);
+                let merkle_tree_chip = MerkleTreeChip::new(&poseidon_chip, gate_chip);
+
+                let leaves = ctx.assign_witnesses(leaves.iter().copied());
+                let root = merkle_tree_chip.compute_root_with_padding(ctx, &leaves, padding);
+                assert_eq!(*root.value(), expected);
+                for subtree_height in [0, 2, 8] {
+                    let root =
+                        merkle_tree_chip.compute_root_parallel(pool, &leaves, padding, subtree_height);
+                    assert_eq!(*root.value(), expected);
+                }
+            });
+        }
+    }
+}
+
+const MAX_DEPTH: usize = 6;
+
+// Verifies leaves[index] of a tree with `num_leaves` leaves against a path padded to MAX_DEPTH
+fn merkle_tree_var_depth_test(
+    ctx: &mut Context<Fr>,
+    num_leaves: usize,
+    index: usize,
+    claimed_index: u64,
+    claimed_depth: u64,
+) -> Fr {
+    let leaves = (0..num_leaves).map(|_| Fr::random(OsRng)).collect::<Vec<_>>();
+    let root = merkle_native::merkle_root(&leaves);
+    let mut merkle_info = merkle_native::merkle_info(&leaves, index);
+    merkle_info.path.resize_with(MAX_DEPTH, || Fr::random(OsRng));
+
+    let mut poseidon_chip = PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+    let gate_chip = GateChip::<Fr>::default();
+    poseidon_chip.initialize_consts(ctx, &gate_chip);
+    let merkle_tree_chip = MerkleTreeChip::new(&poseidon_chip, gate_chip);
+
+    let leaf = ctx.load_witness(merkle_info.leaf);
+    let leaf_index = ctx.load_witness(Fr::from(claimed_index));
+    let depth = ctx.load_witness(Fr::from(claimed_depth));
+    let result = merkle_tree_chip.merkle_tree_verify_var_depth::<MAX_DEPTH>(
+        ctx,
+        root,
+        leaf,
+        &merkle_info.path,
+        leaf_index,
+        depth,
+    );
+    *result.value()
+}
+
+#[test]
+fn test_merkle_tree_var_depth() {
+    base_test().k(14).run(|ctx, _range| {
+        assert_eq!(merkle_tree_var_depth_test(ctx, 8, 5, 5, 3), Fr::one());
+        assert_eq!(merkle_tree_var_depth_test(ctx, 32, 17, 17, 5), Fr::one());
+        assert_eq!(merkle_tree_var_depth_test(ctx, 64, 63, 63, 6), Fr::one());
+        // same leaf under an index that only differs above the depth
+        assert_eq!(merkle_tree_var_depth_test(ctx, 8, 5, 5 + 8, 3), Fr::zero());
+        assert_eq!(merkle_tree_var_depth_test(ctx, 8, 5, 5, 4), Fr::zero());
+        assert_eq!(merkle_tree_var_depth_test(ctx, 8, 5, 5, MAX_DEPTH as u64 + 1), Fr::zero());
+    });
+}
+
 #[test]
 fn bench_merkle_tree() -> Result<(), Box<dyn std::error::Error>> {
     let config_path = "configs/bn254/bench_merkle_tree.config";
@@ -174,4 +335,62 @@ fn bench_merkle_tree() -> Result<(), Box<dyn std::error::Error>> {
         )?;
     }
     Ok(())
-}
\ No newline at end of file
+}
+// The verified root is exposed as a public instance, so a verifier expecting another root rejects the proof
+#[test]
+fn test_merkle_tree_batch_public() {
+    use crate::bn254::instances::{assign_instances, native as instances_native};
+    use halo2_base::gates::circuit::builder::BaseCircuitBuilder;
+    use halo2_base::halo2_proofs::dev::MockProver;
+
+    let xs = (0..8).map(|_| Fr::random(OsRng)).collect::<Vec<_>>();
+    let leaves = xs.iter().map(|x| merkle_native::hash_pair(*x, Fr::zero())).collect::<Vec<_>>();
+    let root = merkle_native::merkle_root(&leaves);
+    let merkle_infos = [1, 6].map(|i| MerkleInfo { leaf: xs[i], ..merkle_native::merkle_info(&leaves, i) });
+
+    let k = 12;
+    let mut builder = BaseCircuitBuilder::<Fr>::new(false).use_k(k).use_instance_columns(1);
+    let ctx = builder.main(0);
+    let mut poseidon_chip = PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+    let gate_chip = GateChip::<Fr>::default();
+    poseidon_chip.initialize_consts(ctx, &gate_chip);
+    let merkle_tree_chip = MerkleTreeChip::new(&poseidon_chip, gate_chip);
+    let statement = merkle_tree_chip.merkle_tree_verify_batch_public(ctx, root, &merkle_infos);
+    assert_eq!(*statement.result.value(), Fr::one());
+    assign_instances(&mut builder.assigned_instances, &[statement]);
+    builder.calculate_params(Some(9));
+
+    let instances = instances_native::expected_instances(&[instances_native::merkle_batch_statement(root)]);
+    MockProver::run(k as u32, &builder, vec![instances]).unwrap().assert_satisfied();
+    let wrong_root = root + Fr::one();
+    let instances = instances_native::expected_instances(&[instances_native::merkle_batch_statement(wrong_root)]);
+    assert!(MockProver::run(k as u32, &builder, vec![instances]).unwrap().verify().is_err());
+}
+
+// The `assert_*` variants constrain the paths, so a wrong root makes the circuit unsatisfiable
+fn assert_merkle_tree_test(valid: bool) {
+    let xs = (0..8).map(|_| Fr::random(OsRng)).collect::<Vec<_>>();
+    let leaves = xs.iter().map(|x| merkle_native::hash_pair(*x, Fr::zero())).collect::<Vec<_>>();
+    let root = merkle_native::merkle_root(&leaves);
+    let root = if valid { root } else { root + Fr::one() };
+    let merkle_infos = [0, 5].map(|i| MerkleInfo { leaf: xs[i], ..merkle_native::merkle_info(&leaves, i) });
+
+    base_test().k(12).expect_satisfied(valid).run(|ctx, _range| {
+        let mut poseidon_chip = PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+        let gate_chip = GateChip::<Fr>::default();
+        poseidon_chip.initialize_consts(ctx, &gate_chip);
+        let merkle_tree_chip = MerkleTreeChip::new(&poseidon_chip, gate_chip);
+        let assigned_root = merkle_tree_chip.assert_merkle_tree_verify_batch(ctx, root, &merkle_infos);
+        assert_eq!(*assigned_root.value(), root);
+    });
+}
+
+#[test]
+fn test_assert_merkle_tree_batch() {
+    assert_merkle_tree_test(true);
+}
+
+#[test]
+fn test_assert_merkle_tree_batch_invalid() {
+    assert_merkle_tree_test(false);
+}
@@ -0,0 +1,70 @@
+use super::*;
+use crate::bn254::merkle_tree::MerkleTreeChip;
+use crate::bn254::mmr::{native::Mmr, MmrChip};
+use crate::halo2curves::pairing::group::ff::Field;
+use halo2_base::gates::GateChip;
+use halo2_base::poseidon::hasher::{spec::OptimizedPoseidonSpec, PoseidonHasher};
+use halo2_base::Context;
+use rand_core::OsRng;
+
+fn random_mmr(size: usize) -> Mmr {
+    let mut mmr = Mmr::new();
+    for _ in 0..size {
+        mmr.append(Fr::random(OsRng));
+    }
+    mmr
+}
+
+#[test]
+fn test_mmr_inclusion() {
+    let mmr = random_mmr(13);
+
+    base_test().k(14).run(|ctx, _range| {
+        let gate_chip = GateChip::<Fr>::default();
+        let mut poseidon_chip =
+            PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+        poseidon_chip.initialize_consts(ctx, &gate_chip);
+        let mmr_chip = MmrChip::new(MerkleTreeChip::new(&poseidon_chip, gate_chip));
+        let root = ctx.load_witness(mmr.root());
+
+        let verify = |ctx: &mut Context<Fr>, leaf: Fr, leaf_index: usize, proof_index: usize| {
+            let proof = mmr.proof(proof_index);
+            let leaf = ctx.load_witness(leaf);
+            let leaf_index = ctx.load_witness(Fr::from(leaf_index as u64));
+            *mmr_chip.verify_inclusion(ctx, root, mmr.size(), leaf, leaf_index, &proof).value()
+        };
+        // one leaf in each of the peaks of height 3, 2 and 0
+        for i in [0, 7, 9, 12] {
+            assert_eq!(verify(ctx, mmr.leaves[i], i, i), Fr::one());
+        }
+        assert_eq!(verify(ctx, Fr::random(OsRng), 9, 9), Fr::zero());
+        // same path under an index outside of the MMR
+        assert_eq!(verify(ctx, mmr.leaves[12], 13, 12), Fr::zero());
+    });
+}
+
+#[test]
+fn test_mmr_append() {
+    let mut mmr = random_mmr(13);
+    let old_root = mmr.root();
+    let old_peaks = mmr.peaks();
+    let new_leaves = (0..6).map(|_| Fr::random(OsRng)).collect::<Vec<_>>();
+    for leaf in new_leaves.iter() {
+        mmr.append(*leaf);
+    }
+
+    base_test().k(14).run(|ctx, _range| {
+        let gate_chip = GateChip::<Fr>::default();
+        let mut poseidon_chip =
+            PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+        poseidon_chip.initialize_consts(ctx, &gate_chip);
+        let mmr_chip = MmrChip::new(MerkleTreeChip::new(&poseidon_chip, gate_chip));
+
+        let old_root = ctx.load_witness(old_root);
+        let old_peaks = ctx.assign_witnesses(old_peaks);
+        let leaves = ctx.assign_witnesses(new_leaves);
+        let (is_valid, new_root) = mmr_chip.verify_append(ctx, old_root, 13, &old_peaks, &leaves);
+        assert_eq!(*is_valid.value(), Fr::one());
+        assert_eq!(*new_root.value(), mmr.root());
+    });
+}
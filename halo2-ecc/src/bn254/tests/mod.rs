@@ -20,15 +20,21 @@ use std::io::Write;
 
 pub mod combine_bls_mt;
 pub mod merkle_tree;
+pub mod bls_batch;
 pub mod bls_signature;
 pub mod bls_signature_inverse;
 pub mod ec_add;
 pub mod fixed_base_msm;
+pub mod hash_to_curve;
+pub mod hash_to_g1;
 pub mod msm;
 pub mod msm_sum_infinity;
 pub mod msm_sum_infinity_fixed_base;
 pub mod pairing;
 pub mod msp;
+pub mod threshold_bls;
+pub mod sparse_merkle_tree;
+pub mod mmr;
 
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
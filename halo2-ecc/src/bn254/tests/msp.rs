@@ -5,6 +5,7 @@ use std::{
 // use env_logger::init;
 use halo2_base::{gates::GateChip, halo2_proofs::{arithmetic::CurveAffine, halo2curves::serde::SerdeObject}, poseidon::hasher::{spec::OptimizedPoseidonSpec, PoseidonHasher}, utils::ScalarField};
 use halo2_base::Context;
+use halo2_base::gates::circuit::builder::BaseCircuitBuilder;
 use halo2_base::utils::BigPrimeField;
 use itertools::Itertools;
 // use rand_core::OsRng;
@@ -14,12 +15,20 @@ use super::*;
 use crate::bn254::{
     MerkleInfo, merkle_tree::MerkleTreeChip, 
     bls_signature::BlsSignatureChip, 
-    msp::MspChip,
+    msp::{native, MspChip, CHALLENGE_BITS},
+    merkle_tree::native as merkle_native,
+    hash_to_curve::{native as hash_to_curve_native, HashToCurveChip, DST_G2},
+    hash_to_g1::{native as hash_to_g1_native, DST_G1},
 };
-use crate::halo2_proofs::halo2curves::bn256::G2Affine;
+use super::hash_to_curve::load_var_len_msg;
+use crate::halo2_proofs::halo2curves::bn256::{G2, G2Affine};
+use crate::group::Group;
+use crate::ff::PrimeField;
 use std::io::Read;
 use rand::seq::SliceRandom; // For random selection
 use poseidon::Poseidon;
+use rand_core::OsRng;
+use crate::halo2curves::pairing::group::ff::Field;
 
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -114,7 +123,7 @@ fn g2_from_string(s:g2_struct) -> G2Affine {
 }
 
 fn msp_test<F: BigPrimeField>(
-    ctx: &mut Context<F>,
+    pool: &mut SinglePhaseCoreManager<F>,
     range: &RangeChip<F>,
     params: CombineBlsMtCircuitParams,
     g1: G1Affine,
@@ -130,15 +139,15 @@ fn msp_test<F: BigPrimeField>(
     let bls_signature_chip = BlsSignatureChip::new(&fp_chip, &pairing_chip);
     let gate_chip = GateChip::<F>::default();
     let mut poseidon_chip = PoseidonHasher::<F, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
-    poseidon_chip.initialize_consts(ctx, &gate_chip);
+    poseidon_chip.initialize_consts(pool.main(), &gate_chip);
     let msp_chip = MspChip::new(&bls_signature_chip, &poseidon_chip);
-    let result = msp_chip.msp_verify(ctx, g1, signatures, pubkeys, msghash,weighting_seed,ivk,isig);
+    let result = msp_chip.msp_verify(pool, g1, signatures, pubkeys, msghash,weighting_seed,ivk,isig);
 
     assert_eq!(*result.value(), F::ONE);
 }
 
 fn msp_test2<F: BigPrimeField>(
-    ctx: &mut Context<F>,
+    pool: &mut SinglePhaseCoreManager<F>,
     range: &RangeChip<F>,
     params: CombineBlsMtCircuitParams,
     g2: G2Affine,
@@ -154,9 +163,9 @@ fn msp_test2<F: BigPrimeField>(
     let bls_signature_chip = BlsSignatureChip::new(&fp_chip, &pairing_chip);
     let gate_chip = GateChip::<F>::default();
     let mut poseidon_chip = PoseidonHasher::<F, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
-    poseidon_chip.initialize_consts(ctx, &gate_chip);
+    poseidon_chip.initialize_consts(pool.main(), &gate_chip);
     let msp_chip = MspChip::new(&bls_signature_chip, &poseidon_chip);
-    let result = msp_chip.msp_verify_2(ctx, g2, signatures, pubkeys, msghash,weighting_seed,ivk,isig);
+    let result = msp_chip.msp_verify_2(pool, g2, signatures, pubkeys, msghash,weighting_seed,ivk,isig);
 
     assert_eq!(*result.value(), F::ONE);
 }
@@ -185,14 +194,162 @@ fn test_msp() {
     let selected_keys = sks.choose_multiple(&mut rng, params.num_aggregation as usize).collect_vec();
     let pubkeys = selected_keys.iter().map(|x| G1Affine::from(G1Affine::generator() * *x)).collect_vec();
     let signatures = json_data.signatures.iter().map(|x| g2_from_string(x.clone())).collect_vec();
-    let weighting_seed = fr_from_string(&json_data.weighting_seed);
-    let ivk = g1_from_string(json_data.ivk);
-    let isig = g2_from_string(json_data.isig);
+    let (weighting_seed, ivk, isig) =
+        native::aggregate(&pubkeys, &signatures, params.limb_bits, params.num_limbs, CHALLENGE_BITS);
 
-    base_test().k(params.degree).lookup_bits(params.lookup_bits).run(|ctx, range| {
-        msp_test(ctx,range,params, G1Affine::generator(), &signatures, &pubkeys, msg_hash, weighting_seed, ivk, isig);
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run_builder(|pool, range| {
+        msp_test(pool,range,params, G1Affine::generator(), &signatures, &pubkeys, msg_hash, weighting_seed, ivk, isig);
+    });
+}
+// Signatures, keys and aggregates of the MSP test data for `params`, as in `test_msp`
+fn load_msp_data(
+    params: CombineBlsMtCircuitParams,
+) -> (Vec<G2Affine>, Vec<G1Affine>, G2Affine, Fr, G1Affine, G2Affine) {
+    let merkle_input_path = "data/data_for_msp_{num}.json".replace("{num}", &params.num_origin.to_string());
+    let mut file = File::open(merkle_input_path).expect("Unable to open file");
+    let mut data = String::new();
+    file.read_to_string(&mut data).expect("Unable to read file");
+    let json_data: MspData = serde_json::from_str(&data).expect("Invalid JSON");
+    let msg_hash = G2Affine::from(G2Affine::generator() * fr_from_string(&json_data.hash_msg));
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0xdeadbeaf);
+    let sks = json_data.sks.iter().map(|x| fr_from_string(&x)).collect_vec();
+    let selected_keys = sks.choose_multiple(&mut rng, params.num_aggregation as usize).collect_vec();
+    let pubkeys = selected_keys.iter().map(|x| G1Affine::from(G1Affine::generator() * *x)).collect_vec();
+    let signatures = json_data.signatures.iter().map(|x| g2_from_string(x.clone())).collect_vec();
+    let (weighting_seed, ivk, isig) =
+        native::aggregate(&pubkeys, &signatures, params.limb_bits, params.num_limbs, CHALLENGE_BITS);
+    (signatures, pubkeys, msg_hash, weighting_seed, ivk, isig)
+}
+
+// `assert_msp_verify` must make the circuit unsatisfiable instead of returning 0 when ivk is not the weighted sum
+fn assert_msp_test(valid: bool) {
+    let path = "configs/bn254/msp.config";
+    let params: CombineBlsMtCircuitParams = serde_json::from_reader(
+        File::open(path).unwrap_or_else(|e| panic!("{path} does not exist: {e:?}")),
+    ).unwrap();
+    let (signatures, pubkeys, msg_hash, weighting_seed, mut ivk, isig) = load_msp_data(params);
+    if !valid {
+        ivk = -ivk;
+    }
+
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).expect_satisfied(valid).run_builder(|pool, range| {
+        let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+        let pairing_chip = PairingChip::new(&fp_chip);
+        let bls_signature_chip = BlsSignatureChip::new(&fp_chip, &pairing_chip);
+        let gate_chip = GateChip::<Fr>::default();
+        let mut poseidon_chip = PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+        poseidon_chip.initialize_consts(pool.main(), &gate_chip);
+        let msp_chip = MspChip::new(&bls_signature_chip, &poseidon_chip);
+        msp_chip.assert_msp_verify(pool, G1Affine::generator(), &signatures, &pubkeys, msg_hash, weighting_seed, ivk, isig);
+    });
+}
+
+#[test]
+fn test_assert_msp() {
+    assert_msp_test(true);
+}
+
+#[test]
+fn test_assert_msp_invalid() {
+    assert_msp_test(false);
+}
+
+// Full width challenges, i.e. the Poseidon outputs themselves, as used before challenges were truncated
+#[test]
+fn test_msp_full_challenges() {
+    let path = "configs/bn254/msp.config";
+    let params: CombineBlsMtCircuitParams = serde_json::from_reader(
+        File::open(path).unwrap_or_else(|e| panic!("{path} does not exist: {e:?}")),
+    ).unwrap();
+    let challenge_bits = Fr::NUM_BITS as usize;
+    let (signatures, pubkeys, msg_hash, _, _, _) = load_msp_data(params);
+    let (weighting_seed, ivk, isig) =
+        native::aggregate(&pubkeys, &signatures, params.limb_bits, params.num_limbs, challenge_bits);
+    assert_eq!(
+        native::challenges(weighting_seed, 1, challenge_bits)[0],
+        merkle_native::hash_pair(Fr::zero(), weighting_seed)
+    );
+
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run_builder(|pool, range| {
+        let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+        let pairing_chip = PairingChip::new(&fp_chip);
+        let bls_signature_chip = BlsSignatureChip::new(&fp_chip, &pairing_chip);
+        let gate_chip = GateChip::<Fr>::default();
+        let mut poseidon_chip = PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+        poseidon_chip.initialize_consts(pool.main(), &gate_chip);
+        let msp_chip = MspChip::new_with_challenge_bits(&bls_signature_chip, &poseidon_chip, challenge_bits);
+        let result = msp_chip.msp_verify(pool, G1Affine::generator(), &signatures, &pubkeys, msg_hash, weighting_seed, ivk, isig);
+        assert_eq!(*result.value(), Fr::one());
     });
 }
+
+// The weighting seed must be Poseidon over the limbs of the aggregated keys and signatures, so aggregates
+// computed from any other seed are rejected even though they are consistent with that seed
+#[test]
+fn test_msp_unbound_weighting_seed() {
+    let path = "configs/bn254/msp.config";
+    let params: CombineBlsMtCircuitParams = serde_json::from_reader(
+        File::open(path).unwrap_or_else(|e| panic!("{path} does not exist: {e:?}")),
+    ).unwrap();
+    let (signatures, pubkeys, msg_hash, weighting_seed, _, _) = load_msp_data(params);
+    let other_seed = weighting_seed + Fr::one();
+    let e_is = native::challenges(other_seed, pubkeys.len(), CHALLENGE_BITS);
+    let ivk = G1Affine::from(pubkeys.iter().zip(e_is.iter()).fold(G1::identity(), |acc, (pk, e_i)| acc + pk * e_i));
+    let isig = G2Affine::from(signatures.iter().zip(e_is.iter()).fold(G2::identity(), |acc, (sig, e_i)| acc + sig * e_i));
+
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run_builder(|pool, range| {
+        let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+        let pairing_chip = PairingChip::new(&fp_chip);
+        let bls_signature_chip = BlsSignatureChip::new(&fp_chip, &pairing_chip);
+        let gate_chip = GateChip::<Fr>::default();
+        let mut poseidon_chip = PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+        poseidon_chip.initialize_consts(pool.main(), &gate_chip);
+        let msp_chip = MspChip::new(&bls_signature_chip, &poseidon_chip);
+        let result = msp_chip.msp_verify(pool, G1Affine::generator(), &signatures, &pubkeys, msg_hash, other_seed, ivk, isig);
+        assert_eq!(*result.value(), Fr::zero());
+    });
+}
+
+// B_4 pairs the same ivk, isig that B_2 and B_3 compare with the aggregates, so a valid BLS pair
+// under another key is rejected, both by `msp_verify` and by `assert_msp_verify`
+fn msp_other_ivk_isig_test(constrain: bool) {
+    let path = "configs/bn254/msp.config";
+    let params: CombineBlsMtCircuitParams = serde_json::from_reader(
+        File::open(path).unwrap_or_else(|e| panic!("{path} does not exist: {e:?}")),
+    ).unwrap();
+    let (signatures, pubkeys, msg_hash, weighting_seed, _, _) = load_msp_data(params);
+    let sk = Fr::random(OsRng);
+    let ivk = G1Affine::from(G1Affine::generator() * *sk);
+    let isig = G2Affine::from(msg_hash * sk);
+
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).expect_satisfied(!constrain).run_builder(|pool, range| {
+        let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+        let pairing_chip = PairingChip::new(&fp_chip);
+        let bls_signature_chip = BlsSignatureChip::new(&fp_chip, &pairing_chip);
+        let gate_chip = GateChip::<Fr>::default();
+        let mut poseidon_chip = PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+        poseidon_chip.initialize_consts(pool.main(), &gate_chip);
+        let msp_chip = MspChip::new(&bls_signature_chip, &poseidon_chip);
+        if constrain {
+            msp_chip.assert_msp_verify(pool, G1Affine::generator(), &signatures, &pubkeys, msg_hash, weighting_seed, ivk, isig);
+        } else {
+            let result = msp_chip.msp_verify(pool, G1Affine::generator(), &signatures, &pubkeys, msg_hash, weighting_seed, ivk, isig);
+            assert_eq!(*result.value(), Fr::zero());
+        }
+    });
+}
+
+#[test]
+fn test_msp_other_ivk_isig() {
+    msp_other_ivk_isig_test(false);
+}
+
+#[test]
+fn test_assert_msp_other_ivk_isig() {
+    msp_other_ivk_isig_test(true);
+}
+
 #[test]
 fn test2_msp(){
     let run_path = "configs/bn254/msp.config";
@@ -223,15 +380,12 @@ fn test2_msp(){
     let mut rng = rand::rngs::StdRng::seed_from_u64(0xdeadbeaf);
     let selected_keys = sk_pks.choose_multiple(&mut rng, params.num_aggregation as usize).collect_vec();
     let signatures: Vec<G1Affine> = json_data.signatures.iter().map(|x| g1_from_string(x.clone())).collect_vec();
-    let weighting_seed = fr_from_string(&json_data.weighting_seed);
-    // let e_is: Vec<Fr> = json_data.e_is.iter().map(|x| fr_from_string(x)).collect_vec();
-    let ivk = g2_from_string(json_data.ivk);
-    let isig = g1_from_string(json_data.isig);
-
     let pubkeys = selected_keys.iter().map(|x| x.1).collect_vec();
+    let (weighting_seed, ivk, isig) =
+        native::aggregate_2(&pubkeys, &signatures, params.limb_bits, params.num_limbs, CHALLENGE_BITS);
 
-    base_test().k(params.degree).lookup_bits(params.lookup_bits).run(|ctx, range| {
-        msp_test2(ctx,range,params, G2Affine::generator(), &signatures, &pubkeys, msg_hash, weighting_seed, ivk, isig);
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run_builder(|pool, range| {
+        msp_test2(pool,range,params, G2Affine::generator(), &signatures, &pubkeys, msg_hash, weighting_seed, ivk, isig);
     });
 
 }
@@ -270,10 +424,10 @@ fn bench_msp() -> Result<(), Box<dyn std::error::Error>> {
         let sks = json_data.sks.iter().map(|x| fr_from_string(&x)).collect_vec();
         let selected_keys = sks.choose_multiple(&mut rng, bench_params.num_aggregation as usize).collect_vec();
         let pubkeys = selected_keys.iter().map(|x| G1Affine::from(G1Affine::generator() * *x)).collect_vec();
-        let signatures = json_data.signatures.iter().map(|x| g2_from_string(x.clone())).collect_vec();
-        let weighting_seed = fr_from_string(&json_data.weighting_seed);
-        let ivk = g1_from_string(json_data.ivk);
-        let isig = g2_from_string(json_data.isig);
+        // signed here rather than read from the data file, so that up to num_origin signers can be benchmarked
+        let signatures = selected_keys.iter().map(|x| G2Affine::from(msg_hash * *x)).collect_vec();
+        let (weighting_seed, ivk, isig) =
+            native::aggregate(&pubkeys, &signatures, bench_params.limb_bits, bench_params.num_limbs, CHALLENGE_BITS);
 
         
         let stats = base_test().k(k).lookup_bits(bench_params.lookup_bits).bench_builder(
@@ -281,7 +435,7 @@ fn bench_msp() -> Result<(), Box<dyn std::error::Error>> {
             (G1Affine::generator(), signatures, pubkeys, msg_hash, weighting_seed, ivk, isig),
             |pool, range, (g1, signatures, pubkeys, msg_hash, weighting_seed, ivk, isig)| {
                 msp_test(
-                    pool.main(),
+                    pool,
                     range,
                     bench_params,
                     g1,
@@ -298,7 +452,8 @@ fn bench_msp() -> Result<(), Box<dyn std::error::Error>> {
         writeln!(
             results_file,
             "{},{},{},{},{},{},{},{:?},{},{:?}",
-            bench_params.num_advice,
+            // number of advice columns the circuit actually needs at this degree, i.e. its rows / 2^degree
+            stats.config_params.num_advice_per_phase[0],
             bench_params.degree,
             bench_params.lookup_bits,
             bench_params.limb_bits,
@@ -352,20 +507,18 @@ fn bench2_msp() -> Result<(), Box<dyn std::error::Error>> {
         // rng is from "deadbeaf"
         let mut rng = rand::rngs::StdRng::seed_from_u64(0xdeadbeaf);
         let selected_keys = sk_pks.choose_multiple(&mut rng, bench_params.num_aggregation as usize).collect_vec();
-        let signatures: Vec<G1Affine> = json_data.signatures.iter().map(|x| g1_from_string(x.clone())).collect_vec();
-        let weighting_seed = fr_from_string(&json_data.weighting_seed);
-        // let e_is: Vec<Fr> = json_data.e_is.iter().map(|x| fr_from_string(x)).collect_vec();
-        let ivk = g2_from_string(json_data.ivk);
-        let isig = g1_from_string(json_data.isig);
-
+        // signed here rather than read from the data file, so that up to num_origin signers can be benchmarked
+        let signatures = selected_keys.iter().map(|x| G1Affine::from(msg_hash * x.0)).collect_vec();
         let pubkeys = selected_keys.iter().map(|x| x.1).collect_vec();
+        let (weighting_seed, ivk, isig) =
+            native::aggregate_2(&pubkeys, &signatures, bench_params.limb_bits, bench_params.num_limbs, CHALLENGE_BITS);
 
         let stats = base_test().k(k).lookup_bits(bench_params.lookup_bits).bench_builder(
             (G2Affine::generator(), signatures.clone(), pubkeys.clone(), msg_hash, weighting_seed, ivk, isig),
             (G2Affine::generator(), signatures, pubkeys, msg_hash, weighting_seed, ivk, isig),
             |pool, range, (g2, signatures, pubkeys, msg_hash, weighting_seed, ivk, isig)| {
                 msp_test2(
-                    pool.main(),
+                    pool,
                     range,
                     bench_params,
                     g2,
@@ -382,7 +535,8 @@ fn bench2_msp() -> Result<(), Box<dyn std::error::Error>> {
         writeln!(
             results_file,
             "{},{},{},{},{},{},{},{:?},{},{:?}",
-            bench_params.num_advice,
+            // number of advice columns the circuit actually needs at this degree, i.e. its rows / 2^degree
+            stats.config_params.num_advice_per_phase[0],
             bench_params.degree,
             bench_params.lookup_bits,
             bench_params.limb_bits,
@@ -397,4 +551,271 @@ fn bench2_msp() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 
-}
\ No newline at end of file
+}
+
+// Advice and lookup advice cells assigned so far in phase 0, over all threads
+fn phase_0_cells(builder: &BaseCircuitBuilder<Fr>) -> (usize, usize) {
+    let stats = builder.statistics();
+    (stats.gate.total_advice_per_phase[0], stats.total_lookup_advice_per_phase[0])
+}
+
+// Cells of \sum_i e_i * P_i for `n` random P_i in G1, or in G2 if `in_g2`, and CHALLENGE_BITS bit e_i.
+// With `msm` it is computed with `variable_base_msm` as `MspChip` aggregates ivk and isig,
+// otherwise with one `scalar_mult` per signer followed by `sum` as `MspChip` did before.
+fn msp_aggregation_cells(params: CombineBlsMtCircuitParams, n: usize, in_g2: bool, msm: bool) -> (usize, usize) {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0xdeadbeaf);
+    let e_is = (0..n).map(|_| native::truncate_challenge(Fr::random(&mut rng), CHALLENGE_BITS)).collect_vec();
+    let sks = (0..n).map(|_| Fr::random(&mut rng)).collect_vec();
+
+    let mut builder = BaseCircuitBuilder::<Fr>::new(false)
+        .use_k(params.degree as usize)
+        .use_lookup_bits(params.lookup_bits);
+    let range = builder.range_chip();
+    let fp_chip = FpChip::<Fr>::new(&range, params.limb_bits, params.num_limbs);
+    let fp2_chip = Fp2Chip::<Fr>::new(&fp_chip);
+    let g1_chip = EccChip::new(&fp_chip);
+    let g2_chip = EccChip::new(&fp2_chip);
+    let e_is = builder.main(0).assign_witnesses(e_is);
+    let scalars = e_is.iter().map(|&e_i| vec![e_i]).collect_vec();
+
+    if in_g2 {
+        let ctx = builder.main(0);
+        let points = sks
+            .iter()
+            .map(|sk| {
+                let pt = G2Affine::from(G2Affine::generator() * *sk);
+                g2_chip.load_private::<G2Affine>(ctx, (pt.x, pt.y))
+            })
+            .collect_vec();
+        let before = phase_0_cells(&builder);
+        if msm {
+            g2_chip.variable_base_msm::<G2Affine>(builder.pool(0), &points, scalars, CHALLENGE_BITS);
+        } else {
+            let ctx = builder.main(0);
+            let products = points
+                .into_iter()
+                .zip(scalars)
+                .map(|(pt, e_i)| g2_chip.scalar_mult::<G2Affine>(ctx, pt, e_i, CHALLENGE_BITS, 4))
+                .collect_vec();
+            g2_chip.sum::<G2Affine>(ctx, products);
+        }
+        let after = phase_0_cells(&builder);
+        (after.0 - before.0, after.1 - before.1)
+    } else {
+        let ctx = builder.main(0);
+        let points = sks
+            .iter()
+            .map(|sk| {
+                let pt = G1Affine::from(G1Affine::generator() * *sk);
+                g1_chip.load_private::<G1Affine>(ctx, (pt.x, pt.y))
+            })
+            .collect_vec();
+        let before = phase_0_cells(&builder);
+        if msm {
+            g1_chip.variable_base_msm::<G1Affine>(builder.pool(0), &points, scalars, CHALLENGE_BITS);
+        } else {
+            let ctx = builder.main(0);
+            let products = points
+                .into_iter()
+                .zip(scalars)
+                .map(|(pt, e_i)| g1_chip.scalar_mult::<G1Affine>(ctx, pt, e_i, CHALLENGE_BITS, 4))
+                .collect_vec();
+            g1_chip.sum::<G1Affine>(ctx, products);
+        }
+        let after = phase_0_cells(&builder);
+        (after.0 - before.0, after.1 - before.1)
+    }
+}
+
+// Compares the cells of the ivk and isig aggregation of `msp_verify` with `variable_base_msm` against
+// one `scalar_mult` per signer, for the signer counts of `bench_msp.config`. Only witness generation is run,
+// the rows of a path are its advice cells divided by the number of advice columns.
+#[test]
+fn bench_msp_aggregation() -> Result<(), Box<dyn std::error::Error>> {
+    let config_path = "configs/bn254/bench_msp.config";
+    let bench_params_file =
+        File::open(config_path).unwrap_or_else(|e| panic!("{config_path} does not exist: {e:?}"));
+    fs::create_dir_all("results/bn254").unwrap();
+
+    let results_path = "results/bn254/msp_aggregation_bench.csv";
+    let mut results_file = File::create(results_path).unwrap();
+    writeln!(results_file, "group,num_aggregation,msm_advice_cells,msm_lookup_cells,scalar_mult_advice_cells,scalar_mult_lookup_cells").unwrap();
+
+    let bench_params_reader = BufReader::new(bench_params_file);
+    for line in bench_params_reader.lines() {
+        let bench_params: CombineBlsMtCircuitParams =
+            serde_json::from_str(line.unwrap().as_str()).unwrap();
+        let n = bench_params.num_aggregation as usize;
+        for (group, in_g2) in [("G1", false), ("G2", true)] {
+            let (msm_advice, msm_lookup) = msp_aggregation_cells(bench_params, n, in_g2, true);
+            let (sm_advice, sm_lookup) = msp_aggregation_cells(bench_params, n, in_g2, false);
+            writeln!(results_file, "{group},{n},{msm_advice},{msm_lookup},{sm_advice},{sm_lookup}")?;
+        }
+    }
+    Ok(())
+}
+
+fn read_pop_params() -> CombineBlsMtCircuitParams {
+    let path = "configs/bn254/msp_pop.config";
+    serde_json::from_reader(
+        File::open(path).unwrap_or_else(|e| panic!("{path} does not exist: {e:?}")),
+    )
+    .unwrap()
+}
+
+fn pop_test(valid: bool) {
+    let params = read_pop_params();
+    let g1 = G1Affine::generator();
+    let sks = (0..params.num_aggregation).map(|_| Fr::random(OsRng)).collect_vec();
+    let pubkeys = sks.iter().map(|sk| G1Affine::from(g1 * sk)).collect_vec();
+    let mut pops =
+        sks.iter().zip(pubkeys.iter()).map(|(sk, pk)| native::pop_prove(sk, pk)).collect_vec();
+    if !valid {
+        // a signature on the wrong key
+        pops[0] = native::pop_prove(&sks[0], &pubkeys[1]);
+    }
+
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run(|ctx, range| {
+        let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+        let pairing_chip = PairingChip::new(&fp_chip);
+        let bls_signature_chip = BlsSignatureChip::new(&fp_chip, &pairing_chip);
+        let mut poseidon_chip =
+            PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+        poseidon_chip.initialize_consts(ctx, &range.gate);
+        let msp_chip = MspChip::new(&bls_signature_chip, &poseidon_chip);
+
+        let mvks = pubkeys.iter().map(|pt| pairing_chip.load_private_g1(ctx, *pt)).collect_vec();
+        let result = msp_chip.pop_verify(ctx, g1, &mvks, &pops);
+        assert_eq!(*result.value(), Fr::from(valid));
+    });
+}
+
+#[test]
+fn test_pop_verify() {
+    pop_test(true);
+}
+
+#[test]
+fn test_pop_verify_invalid() {
+    pop_test(false);
+}
+
+#[test]
+fn test_pop_verify_2() {
+    let params = read_pop_params();
+    let g2 = G2Affine::generator();
+    let sks = (0..params.num_aggregation).map(|_| Fr::random(OsRng)).collect_vec();
+    let pubkeys = sks.iter().map(|sk| G2Affine::from(g2 * sk)).collect_vec();
+    let pops = sks
+        .iter()
+        .zip(pubkeys.iter())
+        .map(|(sk, pk)| native::pop_prove_2(sk, pk, params.limb_bits, params.num_limbs))
+        .collect_vec();
+
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run(|ctx, range| {
+        let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+        let pairing_chip = PairingChip::new(&fp_chip);
+        let bls_signature_chip = BlsSignatureChip::new(&fp_chip, &pairing_chip);
+        let mut poseidon_chip =
+            PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+        poseidon_chip.initialize_consts(ctx, &range.gate);
+        let msp_chip = MspChip::new(&bls_signature_chip, &poseidon_chip);
+
+        let mvks = pubkeys.iter().map(|pt| pairing_chip.load_private_g2(ctx, *pt)).collect_vec();
+        let result = msp_chip.pop_verify_2(ctx, g2, &mvks, &pops);
+        assert_eq!(*result.value(), Fr::one());
+    });
+}
+
+#[test]
+fn test_register_committee() {
+    let params = read_pop_params();
+    let g1 = G1Affine::generator();
+    let sks = (0..params.num_aggregation).map(|_| Fr::random(OsRng)).collect_vec();
+    let pubkeys = sks.iter().map(|sk| G1Affine::from(g1 * sk)).collect_vec();
+    let pops =
+        sks.iter().zip(pubkeys.iter()).map(|(sk, pk)| native::pop_prove(sk, pk)).collect_vec();
+    let leaves = pubkeys
+        .iter()
+        .map(|pk| native::committee_leaf(pk, params.limb_bits, params.num_limbs))
+        .collect_vec();
+    let committee_root = merkle_native::merkle_root(&leaves);
+    let merkle_infos =
+        (0..leaves.len()).map(|i| merkle_native::merkle_info(&leaves, i)).collect_vec();
+
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run(|ctx, range| {
+        let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+        let pairing_chip = PairingChip::new(&fp_chip);
+        let bls_signature_chip = BlsSignatureChip::new(&fp_chip, &pairing_chip);
+        let mut poseidon_chip =
+            PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+        poseidon_chip.initialize_consts(ctx, &range.gate);
+        let msp_chip = MspChip::new(&bls_signature_chip, &poseidon_chip);
+
+        let (is_valid, root) = msp_chip.register_committee(ctx, g1, &pubkeys, &pops);
+        assert_eq!(*is_valid.value(), Fr::one());
+        assert_eq!(*root.value(), committee_root);
+
+        let mvks = pubkeys.iter().map(|pt| pairing_chip.load_private_g1(ctx, *pt)).collect_vec();
+        let leaves = mvks.iter().map(|mvk| msp_chip.committee_leaf(ctx, mvk)).collect_vec();
+        let result = msp_chip.committee_verify(ctx, &leaves, root, &merkle_infos);
+        assert_eq!(*result.value(), Fr::one());
+    });
+}
+
+// H(m) hashed in circuit from the message bytes, with the aggregation over signatures on that hash
+#[test]
+fn test_msp_verify_msg() {
+    let params = read_pop_params();
+    let g1 = G1Affine::generator();
+    let msg = b"hello world";
+    let msg_hash = hash_to_curve_native::hash_to_g2(msg, DST_G2);
+    let sks = (0..params.num_aggregation).map(|_| Fr::random(OsRng)).collect_vec();
+    let pubkeys = sks.iter().map(|sk| G1Affine::from(g1 * sk)).collect_vec();
+    let signatures = sks.iter().map(|sk| G2Affine::from(msg_hash * sk)).collect_vec();
+    let (weighting_seed, ivk, isig) =
+        native::aggregate(&pubkeys, &signatures, params.limb_bits, params.num_limbs, CHALLENGE_BITS);
+
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run_builder(|pool, range| {
+        let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+        let pairing_chip = PairingChip::new(&fp_chip);
+        let bls_signature_chip = BlsSignatureChip::new(&fp_chip, &pairing_chip);
+        let gate_chip = GateChip::<Fr>::default();
+        let mut poseidon_chip = PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+        poseidon_chip.initialize_consts(pool.main(), &gate_chip);
+        let msp_chip = MspChip::new(&bls_signature_chip, &poseidon_chip);
+        let hash_to_curve_chip = HashToCurveChip::new(&fp_chip, DST_G2);
+
+        let msg = load_var_len_msg(pool.main(), range, msg);
+        let result = msp_chip.msp_verify_msg(pool, g1, &signatures, &pubkeys, &msg, &hash_to_curve_chip, weighting_seed, ivk, isig);
+        assert_eq!(*result.value(), Fr::one());
+    });
+}
+
+// Same as `test_msp_verify_msg` for keys in G2, with H(m) hashed to G1 in circuit from field elements
+#[test]
+fn test_msp_verify_2_msg() {
+    let params = read_pop_params();
+    let g2 = G2Affine::generator();
+    let msg = [Fr::from(42), Fr::from(7)];
+    let msg_hash = hash_to_g1_native::hash_to_g1(&msg, DST_G1);
+    let sks = (0..params.num_aggregation).map(|_| Fr::random(OsRng)).collect_vec();
+    let pubkeys = sks.iter().map(|sk| G2Affine::from(g2 * sk)).collect_vec();
+    let signatures = sks.iter().map(|sk| G1Affine::from(msg_hash * sk)).collect_vec();
+    let (weighting_seed, ivk, isig) =
+        native::aggregate_2(&pubkeys, &signatures, params.limb_bits, params.num_limbs, CHALLENGE_BITS);
+
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run_builder(|pool, range| {
+        let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+        let pairing_chip = PairingChip::new(&fp_chip);
+        let bls_signature_chip = BlsSignatureChip::new(&fp_chip, &pairing_chip);
+        let gate_chip = GateChip::<Fr>::default();
+        let mut poseidon_chip = PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+        poseidon_chip.initialize_consts(pool.main(), &gate_chip);
+        let msp_chip = MspChip::new(&bls_signature_chip, &poseidon_chip);
+
+        let msg = pool.main().assign_witnesses(msg);
+        let result = msp_chip.msp_verify_2_msg(pool, g2, &signatures, &pubkeys, &msg, DST_G1, weighting_seed, ivk, isig);
+        assert_eq!(*result.value(), Fr::one());
+    });
+}
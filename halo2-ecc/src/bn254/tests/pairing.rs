@@ -4,8 +4,17 @@ use std::{
 };
 
 use super::*;
+use crate::bn254::pairing::native;
+use crate::ff::Field;
 use crate::fields::FieldChip;
-use crate::{fields::FpStrategy, halo2_proofs::halo2curves::bn256::G2Affine};
+use crate::halo2_proofs::arithmetic::CurveAffine;
+use crate::{
+    fields::FpStrategy,
+    halo2_proofs::halo2curves::bn256::{Fq2, G2Affine},
+};
+use halo2_base::gates::circuit::builder::BaseCircuitBuilder;
+use halo2_base::halo2_proofs::dev::MockProver;
+use halo2_base::poseidon::hasher::{spec::OptimizedPoseidonSpec, PoseidonHasher};
 use halo2_base::{gates::RangeChip, utils::BigPrimeField, Context};
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
@@ -57,6 +66,177 @@ fn test_pairing() {
     });
 }
 
+#[test]
+fn test_miller_loop_fixed_g2() {
+    let path = "configs/bn254/pairing_circuit.config";
+    let params: PairingCircuitParams = serde_json::from_reader(
+        File::open(path).unwrap_or_else(|e| panic!("{path} does not exist: {e:?}")),
+    )
+    .unwrap();
+    let mut rng = StdRng::seed_from_u64(0);
+    let P = G1Affine::random(&mut rng);
+    let Q = G2Affine::random(&mut rng);
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run(|ctx, range| {
+        let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+        let chip = PairingChip::new(&fp_chip);
+        let fp12_chip = Fp12Chip::new(&fp_chip);
+        let P_assigned = chip.load_private_g1(ctx, P);
+        let Q_assigned = chip.load_private_g2(ctx, Q);
+        // same line functions, so the Miller loops agree before the final exponentiation
+        let f_fixed = chip.miller_loop_fixed_g2(ctx, Q, &P_assigned);
+        let f = chip.miller_loop(ctx, &Q_assigned, &P_assigned);
+        assert_eq!(
+            fp12_chip.get_assigned_value(&f_fixed.clone().into()),
+            fp12_chip.get_assigned_value(&f.into())
+        );
+        let paired = chip.final_exp(ctx, f_fixed);
+        assert_eq!(
+            format!("Gt({:?})", fp12_chip.get_assigned_value(&paired.into())),
+            format!("{:?}", pairing(&P, &Q))
+        );
+    });
+}
+
+#[test]
+fn test_multi_miller_loop_mixed() {
+    let path = "configs/bn254/pairing_circuit.config";
+    let params: PairingCircuitParams = serde_json::from_reader(
+        File::open(path).unwrap_or_else(|e| panic!("{path} does not exist: {e:?}")),
+    )
+    .unwrap();
+    let mut rng = StdRng::seed_from_u64(0);
+    let [P_0, P_1, P_2] = [(); 3].map(|_| G1Affine::random(&mut rng));
+    let [Q_0, Q_1, Q_2] = [(); 3].map(|_| G2Affine::random(&mut rng));
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run(|ctx, range| {
+        let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+        let chip = PairingChip::new(&fp_chip);
+        let fp12_chip = Fp12Chip::new(&fp_chip);
+        let [P_0, P_1, P_2] = [P_0, P_1, P_2].map(|P| chip.load_private_g1(ctx, P));
+        let [Q_0_assigned, Q_1_assigned, Q_2_assigned] =
+            [Q_0, Q_1, Q_2].map(|Q| chip.load_private_g2(ctx, Q));
+        // Q_0 as a constant, Q_1 as witness coefficients loaded once and used by both mixed loops
+        let Q_0_lines = chip.load_fixed_g2_lines(ctx, Q_0);
+        let Q_1_lines = chip.load_private_g2_lines(ctx, Q_1);
+
+        let f_mixed = chip.multi_miller_loop_mixed(
+            ctx,
+            vec![(&P_0, &Q_0_lines[..]), (&P_1, &Q_1_lines[..])],
+            vec![(&P_2, &Q_2_assigned)],
+        );
+        let f = chip.multi_miller_loop(
+            ctx,
+            vec![(&P_0, &Q_0_assigned), (&P_1, &Q_1_assigned), (&P_2, &Q_2_assigned)],
+        );
+        assert_eq!(
+            fp12_chip.get_assigned_value(&f_mixed.into()),
+            fp12_chip.get_assigned_value(&f.into())
+        );
+
+        let f_fixed_only = chip.multi_miller_loop_mixed(ctx, vec![(&P_2, &Q_1_lines[..])], vec![]);
+        let f = chip.miller_loop(ctx, &Q_1_assigned, &P_2);
+        assert_eq!(
+            fp12_chip.get_assigned_value(&f_fixed_only.into()),
+            fp12_chip.get_assigned_value(&f.into())
+        );
+    });
+}
+
+// Runs the MockProver on `g2_lines_commitment` of `lines` exposed as an instance, with `expected` as the instance.
+// The lines are loaded as `load_private_g2_lines` does, but with coefficients chosen by the prover.
+fn g2_lines_commitment_test(
+    params: PairingCircuitParams,
+    lines: &[[Fq2; 3]],
+    expected: Fr,
+) -> bool {
+    let k = params.degree as usize;
+    let mut builder = BaseCircuitBuilder::<Fr>::new(false)
+        .use_k(k)
+        .use_lookup_bits(params.lookup_bits)
+        .use_instance_columns(1);
+    let range = builder.range_chip();
+    let fp_chip = FpChip::<Fr>::new(&range, params.limb_bits, params.num_limbs);
+    let fp2_chip = Fp2Chip::<Fr>::new(&fp_chip);
+    let chip = PairingChip::new(&fp_chip);
+    let ctx = builder.main(0);
+    let mut poseidon_chip =
+        PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+    poseidon_chip.initialize_consts(ctx, fp_chip.gate());
+
+    let lines =
+        lines.iter().map(|line| line.map(|c| fp2_chip.load_private(ctx, c))).collect::<Vec<_>>();
+    let commitment = chip.g2_lines_commitment(ctx, &poseidon_chip, &lines);
+    builder.assigned_instances[0].push(commitment);
+    builder.calculate_params(Some(9));
+    MockProver::run(k as u32, &builder, vec![vec![expected]]).unwrap().verify().is_ok()
+}
+
+// Private line coefficients are bound to Q by their commitment, so tampered lines are rejected
+#[test]
+fn test_g2_lines_commitment() {
+    let path = "configs/bn254/pairing_circuit.config";
+    let params: PairingCircuitParams = serde_json::from_reader(
+        File::open(path).unwrap_or_else(|e| panic!("{path} does not exist: {e:?}")),
+    )
+    .unwrap();
+    let mut rng = StdRng::seed_from_u64(0);
+    let [Q, Q_other] = [(); 2].map(|_| G2Affine::random(&mut rng));
+    let expected = native::g2_lines_commitment(Q, params.limb_bits, params.num_limbs);
+    let lines = native::g2_line_coeffs(Q);
+    assert!(g2_lines_commitment_test(params, &lines, expected));
+
+    let mut tampered = lines.clone();
+    tampered[1][0] += Fq2::one();
+    assert!(!g2_lines_commitment_test(params, &tampered, expected));
+    // the lines of another point are well formed, but not those of Q
+    assert!(!g2_lines_commitment_test(params, &native::g2_line_coeffs(Q_other), expected));
+}
+
+// A point of the twist E'(Fp2) which is not in G2 except with probability 1/h, for h the cofactor of G2
+fn random_twist_point(rng: &mut StdRng) -> G2Affine {
+    loop {
+        let x = Fq2::random(&mut *rng);
+        let y_sq = x.square() * x + G2Affine::b();
+        if let Some(y) = Option::<Fq2>::from(y_sq.sqrt()) {
+            return G2Affine::from_xy(x, y).unwrap();
+        }
+    }
+}
+
+#[test]
+fn test_g2_subgroup_check() {
+    let path = "configs/bn254/pairing_circuit.config";
+    let params: PairingCircuitParams = serde_json::from_reader(
+        File::open(path).unwrap_or_else(|e| panic!("{path} does not exist: {e:?}")),
+    )
+    .unwrap();
+    let mut rng = StdRng::seed_from_u64(0);
+    let Q = G2Affine::random(&mut rng);
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run(|ctx, range| {
+        let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+        let chip = PairingChip::new(&fp_chip);
+        chip.load_private_g2_checked(ctx, Q);
+    });
+}
+
+#[test]
+fn test_g2_subgroup_check_twist_point() {
+    let path = "configs/bn254/pairing_circuit.config";
+    let params: PairingCircuitParams = serde_json::from_reader(
+        File::open(path).unwrap_or_else(|e| panic!("{path} does not exist: {e:?}")),
+    )
+    .unwrap();
+    let mut rng = StdRng::seed_from_u64(0);
+    let Q = random_twist_point(&mut rng);
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).expect_satisfied(false).run(
+        |ctx, range| {
+            let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+            let chip = PairingChip::new(&fp_chip);
+            // on the curve, so `load_private_g2` alone accepts it
+            chip.load_private_g2_checked(ctx, Q);
+        },
+    );
+}
+
 #[test]
 fn bench_pairing() -> Result<(), Box<dyn std::error::Error>> {
     let config_path = "configs/bn254/bench_pairing.config";
@@ -0,0 +1,94 @@
+use super::*;
+use crate::bn254::merkle_tree::MerkleTreeChip;
+use crate::bn254::sparse_merkle_tree::{native::SparseMerkleTree, SparseMerkleTreeChip};
+use crate::halo2curves::pairing::group::ff::Field;
+use halo2_base::gates::GateChip;
+use halo2_base::poseidon::hasher::{spec::OptimizedPoseidonSpec, PoseidonHasher};
+use rand_core::OsRng;
+
+const DEPTH: usize = 32;
+
+fn random_key() -> Fr {
+    Fr::from(rand::random::<u32>() as u64)
+}
+
+fn random_tree(num_leaves: usize) -> SparseMerkleTree {
+    let mut tree = SparseMerkleTree::new(DEPTH);
+    for _ in 0..num_leaves {
+        tree.insert(random_key(), Fr::random(OsRng));
+    }
+    tree
+}
+
+#[test]
+fn test_smt_membership() {
+    let mut tree = random_tree(8);
+    let key = random_key();
+    let value = Fr::random(OsRng);
+    tree.insert(key, value);
+    let absent = random_key();
+    assert!(tree.get(&absent).is_none());
+
+    base_test().k(14).run(|ctx, _range| {
+        let gate_chip = GateChip::<Fr>::default();
+        let mut poseidon_chip =
+            PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+        poseidon_chip.initialize_consts(ctx, &gate_chip);
+        let smt_chip =
+            SparseMerkleTreeChip::new(MerkleTreeChip::new(&poseidon_chip, gate_chip), DEPTH);
+
+        let root = ctx.load_witness(tree.root());
+        let key_assigned = ctx.load_witness(key);
+        let value_assigned = ctx.load_witness(value);
+        let siblings = ctx.assign_witnesses(tree.siblings(&key));
+        let result = smt_chip.verify_membership(ctx, root, key_assigned, value_assigned, &siblings);
+        assert_eq!(*result.value(), Fr::one());
+        // an occupied leaf is not empty
+        let result = smt_chip.verify_non_membership(ctx, root, key_assigned, &siblings);
+        assert_eq!(*result.value(), Fr::zero());
+
+        let absent_assigned = ctx.load_witness(absent);
+        let siblings = ctx.assign_witnesses(tree.siblings(&absent));
+        let result = smt_chip.verify_non_membership(ctx, root, absent_assigned, &siblings);
+        assert_eq!(*result.value(), Fr::one());
+    });
+}
+
+#[test]
+fn test_smt_update_batch() {
+    let mut tree = random_tree(8);
+    let old_root = tree.root();
+    let key = random_key();
+    let proofs = vec![
+        tree.insert(key, Fr::random(OsRng)),
+        tree.insert(random_key(), Fr::random(OsRng)),
+        tree.insert(key, Fr::random(OsRng)),
+        tree.remove(key),
+    ];
+    let new_root = tree.root();
+
+    let mut wrong_proofs = proofs.clone();
+    wrong_proofs[1].old_leaf += Fr::one();
+
+    base_test().k(15).run(|ctx, _range| {
+        let gate_chip = GateChip::<Fr>::default();
+        let mut poseidon_chip =
+            PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+        poseidon_chip.initialize_consts(ctx, &gate_chip);
+        let smt_chip =
+            SparseMerkleTreeChip::new(MerkleTreeChip::new(&poseidon_chip, gate_chip), DEPTH);
+
+        let old_root = ctx.load_witness(old_root);
+        let (is_valid, root, updates) = smt_chip.update_batch(ctx, old_root, &proofs);
+        assert_eq!(*is_valid.value(), Fr::one());
+        assert_eq!(*root.value(), new_root);
+        for (update, proof) in updates.iter().zip(proofs.iter()) {
+            assert_eq!(*update.key.value(), proof.key);
+            assert_eq!(*update.old_leaf.value(), proof.old_leaf);
+            assert_eq!(*update.new_leaf.value(), proof.new_leaf);
+        }
+
+        let (is_valid, _, _) = smt_chip.update_batch(ctx, old_root, &wrong_proofs);
+        assert_eq!(*is_valid.value(), Fr::zero());
+    });
+}
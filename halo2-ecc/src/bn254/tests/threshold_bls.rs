@@ -0,0 +1,120 @@
+use std::fs::File;
+
+use super::*;
+use crate::bn254::bls_signature::BlsSignatureChip;
+use crate::bn254::threshold_bls::{native, ThresholdBlsChip};
+use crate::group::Group;
+use crate::halo2_proofs::halo2curves::bn256::{G2Affine, G2};
+use crate::halo2curves::pairing::group::ff::Field;
+use halo2_base::poseidon::hasher::{spec::OptimizedPoseidonSpec, PoseidonHasher};
+use rand_core::OsRng;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+struct ThresholdBlsCircuitParams {
+    strategy: FpStrategy,
+    degree: u32,
+    num_advice: usize,
+    num_lookup_advice: usize,
+    num_fixed: usize,
+    lookup_bits: usize,
+    limb_bits: usize,
+    num_limbs: usize,
+    committee_size: usize,
+}
+
+fn read_params() -> ThresholdBlsCircuitParams {
+    let path = "configs/bn254/threshold_bls_circuit.config";
+    serde_json::from_reader(
+        File::open(path).unwrap_or_else(|e| panic!("{path} does not exist: {e:?}")),
+    )
+    .unwrap()
+}
+
+// Committee members i with `signs(i)` sign; `threshold_offset` is added to the signed stake to get the threshold
+fn threshold_bls_test(signs: impl Fn(usize) -> bool, threshold_offset: i64, expected: bool) {
+    let params = read_params();
+    let g1 = G1Affine::generator();
+    let msg_hash = G2Affine::random(OsRng);
+    let sks = (0..params.committee_size).map(|_| Fr::random(OsRng)).collect::<Vec<_>>();
+    let pubkeys = sks.iter().map(|sk| G1Affine::from(g1 * sk)).collect::<Vec<_>>();
+    let stakes = (0..params.committee_size).map(|i| 1000 * (i as u64 + 1)).collect::<Vec<_>>();
+    let bitmap = (0..params.committee_size).map(signs).collect::<Vec<_>>();
+
+    let signers = (0..params.committee_size).filter(|i| bitmap[*i]).collect::<Vec<_>>();
+    let signature = signers.iter().fold(G2::identity(), |acc, i| acc + msg_hash * sks[*i]);
+    // with no signers the signature is any point, since nothing is aggregated
+    let signature =
+        if signers.is_empty() { G2Affine::random(OsRng) } else { G2Affine::from(signature) };
+    let signed_stake = signers.iter().map(|i| stakes[*i]).sum::<u64>();
+    let threshold = (signed_stake as i64 + threshold_offset) as u64;
+    let commitment =
+        native::committee_commitment(&pubkeys, &stakes, params.limb_bits, params.num_limbs);
+
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run(|ctx, range| {
+        let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+        let pairing_chip = PairingChip::new(&fp_chip);
+        let bls_signature_chip = BlsSignatureChip::new(&fp_chip, &pairing_chip);
+        let mut poseidon_chip =
+            PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+        poseidon_chip.initialize_consts(ctx, &range.gate);
+        let threshold_bls_chip = ThresholdBlsChip::new(&bls_signature_chip, &poseidon_chip);
+        let threshold = ctx.load_witness(Fr::from(threshold));
+        let (result, committee_commitment) = threshold_bls_chip
+            .threshold_verify(ctx, g1, signature, &pubkeys, &stakes, &bitmap, threshold, msg_hash);
+        assert_eq!(*result.value(), Fr::from(expected));
+        assert_eq!(*committee_commitment.value(), commitment);
+    })
+}
+
+#[test]
+fn test_threshold_bls() {
+    threshold_bls_test(|i| i % 2 == 0, 0, true);
+    threshold_bls_test(|i| i % 2 == 0, -1, true);
+}
+
+#[test]
+fn test_threshold_bls_below_threshold() {
+    threshold_bls_test(|i| i % 2 == 0, 1, false);
+}
+
+// A zero threshold is met by an empty bitmap, which must still be rejected instead of making the circuit unsatisfiable
+#[test]
+fn test_threshold_bls_no_signers() {
+    threshold_bls_test(|_| false, 0, false);
+}
+
+#[test]
+fn test_threshold_bls_2() {
+    let params = read_params();
+    let g2 = G2Affine::generator();
+    let msg_hash = G1Affine::random(OsRng);
+    let sks = (0..params.committee_size).map(|_| Fr::random(OsRng)).collect::<Vec<_>>();
+    let pubkeys = sks.iter().map(|sk| G2Affine::from(g2 * sk)).collect::<Vec<_>>();
+    let stakes = vec![1u64; params.committee_size];
+    // the first two thirds of the committee sign
+    let num_signers = 2 * params.committee_size / 3 + 1;
+    let bitmap = (0..params.committee_size).map(|i| i < num_signers).collect::<Vec<_>>();
+    let mut signature = msg_hash * sks[0];
+    for sk in sks.iter().take(num_signers).skip(1) {
+        signature += msg_hash * sk;
+    }
+    let signature = G1Affine::from(signature);
+    let commitment =
+        native::committee_commitment_2(&pubkeys, &stakes, params.limb_bits, params.num_limbs);
+
+    base_test().k(params.degree).lookup_bits(params.lookup_bits).run(|ctx, range| {
+        let fp_chip = FpChip::<Fr>::new(range, params.limb_bits, params.num_limbs);
+        let pairing_chip = PairingChip::new(&fp_chip);
+        let bls_signature_chip = BlsSignatureChip::new(&fp_chip, &pairing_chip);
+        let mut poseidon_chip =
+            PoseidonHasher::<Fr, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+        poseidon_chip.initialize_consts(ctx, &range.gate);
+        let threshold_bls_chip = ThresholdBlsChip::new(&bls_signature_chip, &poseidon_chip);
+        let threshold = ctx.load_witness(Fr::from(num_signers as u64));
+        let (result, committee_commitment) = threshold_bls_chip.threshold_verify_2(
+            ctx, g2, signature, &pubkeys, &stakes, &bitmap, threshold, msg_hash,
+        );
+        assert_eq!(*result.value(), Fr::one());
+        assert_eq!(*committee_commitment.value(), commitment);
+    })
+}
@@ -0,0 +1,275 @@
+#![allow(non_snake_case)]
+// Stake-weighted threshold BLS verification for committee based light clients.
+// The full committee of (pubkey, stake) pairs is loaded together with a participation bitmap; only the keys selected
+// by the bitmap are aggregated, and the selected stakes must add up to at least the threshold T.
+// The committee is bound by a Poseidon commitment to its (pubkey, stake) list, and the threshold is taken assigned,
+// so that both can be exposed as public instances or checked against other parts of the circuit.
+use super::bls_signature::BlsSignatureChip;
+use super::bls_signature_inverse::BlsSignatureChip2;
+use super::merkle_tree::{g1_limbs, g2_limbs};
+use super::{Fp2Chip, FpPoint, FqPoint};
+use crate::ecc::{EcPoint, EccChip};
+use crate::fields::{FieldChip, Selectable};
+use crate::halo2_proofs::halo2curves::bn256::{G1Affine, G2Affine};
+use halo2_base::gates::{GateInstructions, RangeInstructions};
+use halo2_base::poseidon::hasher::PoseidonHasher;
+use halo2_base::utils::{bit_length, BigPrimeField, CurveAffineExt};
+use halo2_base::{AssignedValue, Context, QuantumCell::Existing};
+
+// Stakes are range checked to this many bits
+pub const STAKE_BITS: usize = 64;
+
+pub struct ThresholdBlsChip<'chip, F: BigPrimeField> {
+    pub bls_signature_chip: &'chip BlsSignatureChip<'chip, F>,
+    pub poseidon_chip: &'chip PoseidonHasher<F, 3, 2>,
+}
+
+impl<'chip, F: BigPrimeField> ThresholdBlsChip<'chip, F> {
+    pub fn new(
+        bls_signature_chip: &'chip BlsSignatureChip<F>,
+        poseidon_chip: &'chip PoseidonHasher<F, 3, 2>,
+    ) -> Self {
+        Self { bls_signature_chip, poseidon_chip }
+    }
+
+    // Loads the participation bitmap, constraining every entry to be a bit
+    pub fn load_bitmap(&self, ctx: &mut Context<F>, bitmap: &[bool]) -> Vec<AssignedValue<F>> {
+        let gate = self.bls_signature_chip.fp_chip.gate();
+        bitmap
+            .iter()
+            .map(|bit| {
+                let bit = ctx.load_witness(F::from(*bit));
+                gate.assert_bit(ctx, bit);
+                bit
+            })
+            .collect()
+    }
+
+    // Loads the stakes, range checked to STAKE_BITS bits
+    pub fn load_stakes(&self, ctx: &mut Context<F>, stakes: &[u64]) -> Vec<AssignedValue<F>> {
+        let range = self.bls_signature_chip.fp_chip.range();
+        stakes
+            .iter()
+            .map(|stake| {
+                let stake = ctx.load_witness(F::from(*stake));
+                range.range_check(ctx, stake, STAKE_BITS);
+                stake
+            })
+            .collect()
+    }
+
+    // Returns 1 iff the stakes selected by `bitmap` add up to at least `threshold`.
+    // `stakes` must be range checked to STAKE_BITS bits and `bitmap` constrained to bits, so the selected sum of n stakes
+    // has at most STAKE_BITS + bit_length(n) bits and cannot overflow; both sides are range checked to that many bits for the comparison.
+    pub fn stake_threshold_check(
+        &self,
+        ctx: &mut Context<F>,
+        stakes: &[AssignedValue<F>],
+        bitmap: &[AssignedValue<F>],
+        threshold: AssignedValue<F>,
+    ) -> AssignedValue<F> {
+        assert!(stakes.len() == bitmap.len(), "stakes and bitmap must be the same length");
+        let range = self.bls_signature_chip.fp_chip.range();
+        let gate = range.gate();
+        let selected_stake = gate.inner_product(
+            ctx,
+            stakes.iter().copied(),
+            bitmap.iter().map(|bit| Existing(*bit)),
+        );
+        let num_bits = STAKE_BITS + bit_length(stakes.len() as u64);
+        range.range_check(ctx, selected_stake, num_bits);
+        range.range_check(ctx, threshold, num_bits);
+        let is_below = range.is_less_than(ctx, selected_stake, threshold, num_bits);
+        gate.not(ctx, is_below)
+    }
+
+    // Poseidon commitment to the committee: the CRT limbs of every pubkey followed by its stake, see `native::committee_commitment`
+    pub fn committee_commitment(
+        &self,
+        ctx: &mut Context<F>,
+        pubkeys: &[EcPoint<F, FpPoint<F>>],
+        stakes: &[AssignedValue<F>],
+    ) -> AssignedValue<F> {
+        let fp_chip = self.bls_signature_chip.fp_chip;
+        let mut inputs = vec![];
+        for (pubkey, stake) in pubkeys.iter().zip(stakes.iter()) {
+            inputs.extend(g1_limbs(fp_chip, ctx, pubkey));
+            inputs.push(*stake);
+        }
+        self.poseidon_chip.hash_fix_len_array(ctx, fp_chip.gate(), &inputs)
+    }
+
+    // Same as `committee_commitment` for pubkeys in G2, see `native::committee_commitment_2`
+    pub fn committee_commitment_2(
+        &self,
+        ctx: &mut Context<F>,
+        pubkeys: &[EcPoint<F, FqPoint<F>>],
+        stakes: &[AssignedValue<F>],
+    ) -> AssignedValue<F> {
+        let fp_chip = self.bls_signature_chip.fp_chip;
+        let mut inputs = vec![];
+        for (pubkey, stake) in pubkeys.iter().zip(stakes.iter()) {
+            inputs.extend(g2_limbs(fp_chip, ctx, pubkey));
+            inputs.push(*stake);
+        }
+        self.poseidon_chip.hash_fix_len_array(ctx, fp_chip.gate(), &inputs)
+    }
+
+    // Sum of the points selected by `bitmap`, accumulated from a random point as in `EccChip::sum`,
+    // together with a flag that is 1 iff at least one point is selected.
+    // The sum is meaningless when the flag is 0, in which case points[0] is returned instead so that the circuit stays satisfiable.
+    fn aggregate_selected<FC, C>(
+        ecc_chip: &EccChip<F, FC>,
+        ctx: &mut Context<F>,
+        points: Vec<EcPoint<F, FC::FieldPoint>>,
+        bitmap: &[AssignedValue<F>],
+    ) -> (EcPoint<F, FC::FieldPoint>, AssignedValue<F>)
+    where
+        FC: FieldChip<F> + Selectable<F, FC::FieldPoint>,
+        C: CurveAffineExt<Base = FC::FieldType>,
+    {
+        assert!(points.len() == bitmap.len(), "pubkeys and bitmap must be the same length");
+        assert!(!points.is_empty(), "pubkeys must not be empty");
+        let gate = ecc_chip.field_chip.gate();
+        let num_selected = gate.sum(ctx, bitmap.iter().copied());
+        let none_selected = gate.is_zero(ctx, num_selected);
+
+        let rand_point = ecc_chip.load_random_point::<C>(ctx);
+        // with no point selected the accumulator would still be the random point, which cannot be subtracted from itself
+        let fallback = ecc_chip.add_unequal(ctx, rand_point.clone(), points[0].clone(), true);
+        let mut acc = rand_point.clone();
+        for (point, bit) in points.into_iter().zip(bitmap.iter()) {
+            let sum = ecc_chip.add_unequal(ctx, acc.clone(), point, true);
+            acc = ecc_chip.select(ctx, sum, acc, *bit);
+        }
+        let acc = ecc_chip.select(ctx, fallback, acc, none_selected);
+        (ecc_chip.sub_unequal(ctx, acc, rand_point, true), gate.not(ctx, none_selected))
+    }
+
+    // Verifies e(g1, signature) = e(apk, H(m)) where apk is the sum of the pubkeys selected by `bitmap`,
+    // and that the selected stakes add up to at least `threshold`. Returns (is_valid, committee_commitment),
+    // where is_valid is 1 iff both hold and at least one pubkey is selected, and committee_commitment is
+    // the `committee_commitment` of the (pubkey, stake) list
+    // G1: {g1, pubkey}, G2: {signature, message}
+    pub fn threshold_verify(
+        &self,
+        ctx: &mut Context<F>,
+        g1: G1Affine,
+        signature: G2Affine,
+        pubkeys: &[G1Affine],
+        stakes: &[u64],
+        bitmap: &[bool],
+        threshold: AssignedValue<F>,
+        msghash: G2Affine,
+    ) -> (AssignedValue<F>, AssignedValue<F>) {
+        assert!(pubkeys.len() == stakes.len(), "pubkeys and stakes must be the same length");
+        let fp_chip = self.bls_signature_chip.fp_chip;
+        let pairing_chip = self.bls_signature_chip.pairing_chip;
+        let g1_chip = EccChip::new(fp_chip);
+
+        let bitmap = self.load_bitmap(ctx, bitmap);
+        let stakes = self.load_stakes(ctx, stakes);
+        let verify_stake = self.stake_threshold_check(ctx, &stakes, &bitmap, threshold);
+
+        let pubkeys =
+            pubkeys.iter().map(|pt| pairing_chip.load_private_g1(ctx, *pt)).collect::<Vec<_>>();
+        let commitment = self.committee_commitment(ctx, &pubkeys, &stakes);
+        let (apk, any_selected) =
+            Self::aggregate_selected::<_, G1Affine>(&g1_chip, ctx, pubkeys, &bitmap);
+        let signature = pairing_chip.load_private_g2_checked(ctx, signature);
+        let hash_m = pairing_chip.load_private_g2_checked(ctx, msghash);
+        let verify_signature = self.bls_signature_chip.bls_aggregate_verify_points(
+            ctx,
+            g1,
+            signature,
+            &[apk],
+            &[hash_m],
+        );
+        let gate = fp_chip.gate();
+        let result = gate.and(ctx, verify_stake, verify_signature);
+        (gate.and(ctx, result, any_selected), commitment)
+    }
+
+    // Same as `threshold_verify` with signatures in G1 and pubkeys in G2, committing to the list with `committee_commitment_2`
+    // G1: {signature, message}, G2: {g2, pubkey}
+    pub fn threshold_verify_2(
+        &self,
+        ctx: &mut Context<F>,
+        g2: G2Affine,
+        signature: G1Affine,
+        pubkeys: &[G2Affine],
+        stakes: &[u64],
+        bitmap: &[bool],
+        threshold: AssignedValue<F>,
+        msghash: G1Affine,
+    ) -> (AssignedValue<F>, AssignedValue<F>) {
+        assert!(pubkeys.len() == stakes.len(), "pubkeys and stakes must be the same length");
+        let fp_chip = self.bls_signature_chip.fp_chip;
+        let pairing_chip = self.bls_signature_chip.pairing_chip;
+        let g1_chip = EccChip::new(fp_chip);
+        let fp2_chip = Fp2Chip::<F>::new(fp_chip);
+        let g2_chip = EccChip::new(&fp2_chip);
+
+        let bitmap = self.load_bitmap(ctx, bitmap);
+        let stakes = self.load_stakes(ctx, stakes);
+        let verify_stake = self.stake_threshold_check(ctx, &stakes, &bitmap, threshold);
+
+        let pubkeys = pubkeys
+            .iter()
+            .map(|pt| pairing_chip.load_private_g2_checked(ctx, *pt))
+            .collect::<Vec<_>>();
+        let commitment = self.committee_commitment_2(ctx, &pubkeys, &stakes);
+        let (apk, any_selected) =
+            Self::aggregate_selected::<_, G2Affine>(&g2_chip, ctx, pubkeys, &bitmap);
+        let signature = g1_chip.load_private::<G1Affine>(ctx, (signature.x, signature.y));
+        let hash_m = pairing_chip.load_private_g1(ctx, msghash);
+        let bls_signature_chip_2 = BlsSignatureChip2::new(fp_chip, pairing_chip);
+        let verify_signature =
+            bls_signature_chip_2.bls_aggregate_verify_points(ctx, g2, signature, &[apk], &[hash_m]);
+        let gate = fp_chip.gate();
+        let result = gate.and(ctx, verify_stake, verify_signature);
+        (gate.and(ctx, result, any_selected), commitment)
+    }
+}
+
+// Native counterparts of the committee commitments
+pub mod native {
+    use crate::bn254::merkle_tree::native::{g1_limbs, g2_limbs, poseidon_hash};
+    use crate::halo2_proofs::halo2curves::bn256::{Fr, G1Affine, G2Affine};
+
+    pub fn committee_commitment(
+        pubkeys: &[G1Affine],
+        stakes: &[u64],
+        limb_bits: usize,
+        num_limbs: usize,
+    ) -> Fr {
+        let inputs = pubkeys
+            .iter()
+            .zip(stakes.iter())
+            .flat_map(|(pk, stake)| {
+                let mut limbs = g1_limbs(pk, limb_bits, num_limbs);
+                limbs.push(Fr::from(*stake));
+                limbs
+            })
+            .collect::<Vec<_>>();
+        poseidon_hash(&inputs)
+    }
+
+    pub fn committee_commitment_2(
+        pubkeys: &[G2Affine],
+        stakes: &[u64],
+        limb_bits: usize,
+        num_limbs: usize,
+    ) -> Fr {
+        let inputs = pubkeys
+            .iter()
+            .zip(stakes.iter())
+            .flat_map(|(pk, stake)| {
+                let mut limbs = g2_limbs(pk, limb_bits, num_limbs);
+                limbs.push(Fr::from(*stake));
+                limbs
+            })
+            .collect::<Vec<_>>();
+        poseidon_hash(&inputs)
+    }
+}
@@ -0,0 +1,95 @@
+use halo2_base::utils::{BigPrimeField, CurveAffineExt};
+use halo2_base::{AssignedValue, Context};
+
+use crate::fields::{FieldChip, Selectable};
+
+use super::{EcPoint, EccChip};
+
+// ElGamal ciphertext of a message point M under the public key PK = sk * G with randomness r:
+//   (c1, c2) = (r * G, M + r * PK)
+// The plaintext is recovered as M = c2 - sk * c1.
+// Adding the encryption r' * (G, PK) of the identity re-randomizes a ciphertext without changing its plaintext.
+#[derive(Clone, Debug)]
+pub struct ElGamalCiphertext<F: BigPrimeField, FieldPoint> {
+    pub c1: EcPoint<F, FieldPoint>,
+    pub c2: EcPoint<F, FieldPoint>,
+}
+
+impl<F: BigPrimeField, FieldPoint> ElGamalCiphertext<F, FieldPoint> {
+    pub fn new(c1: EcPoint<F, FieldPoint>, c2: EcPoint<F, FieldPoint>) -> Self {
+        Self { c1, c2 }
+    }
+}
+
+impl<'chip, F: BigPrimeField, FC: FieldChip<F>> EccChip<'chip, F, FC>
+where
+    FC: Selectable<F, FC::FieldPoint>,
+{
+    /// Load ciphertext as private witness. Constrains both points to lie on curve.
+    pub fn load_ciphertext<C>(
+        &self,
+        ctx: &mut Context<F>,
+        (c1, c2): (C, C),
+    ) -> ElGamalCiphertext<F, FC::FieldPoint>
+    where
+        C: CurveAffineExt<Base = FC::FieldType>,
+    {
+        let c1 = self.load_private::<C>(ctx, c1.into_coordinates());
+        let c2 = self.load_private::<C>(ctx, c2.into_coordinates());
+        ElGamalCiphertext::new(c1, c2)
+    }
+
+    // Encryption r * (G, PK) of the identity, where `scalar` holds the limbs of r as in `scalar_mult`
+    pub fn encrypt_identity<C>(
+        &self,
+        ctx: &mut Context<F>,
+        pubkey: &EcPoint<F, FC::FieldPoint>,
+        scalar: Vec<AssignedValue<F>>,
+        max_bits: usize,
+        window_bits: usize,
+    ) -> ElGamalCiphertext<F, FC::FieldPoint>
+    where
+        C: CurveAffineExt<Base = FC::FieldType>,
+    {
+        let c1 = self.fixed_base_scalar_mult(
+            ctx,
+            &C::generator(),
+            scalar.clone(),
+            max_bits,
+            window_bits,
+        );
+        let c2 = self.scalar_mult::<C>(ctx, pubkey.clone(), scalar, max_bits, window_bits);
+        ElGamalCiphertext::new(c1, c2)
+    }
+
+    // Re-randomizes `ciphertext` into (c1 + r * G, c2 + r * PK)
+    // The additions are strict, so this fails (with negligible probability for a random r) if one of the
+    // points of the ciphertext has the same x-coordinate as the corresponding point of r * (G, PK)
+    pub fn re_encrypt<C>(
+        &self,
+        ctx: &mut Context<F>,
+        ciphertext: &ElGamalCiphertext<F, FC::FieldPoint>,
+        pubkey: &EcPoint<F, FC::FieldPoint>,
+        scalar: Vec<AssignedValue<F>>,
+        max_bits: usize,
+        window_bits: usize,
+    ) -> ElGamalCiphertext<F, FC::FieldPoint>
+    where
+        C: CurveAffineExt<Base = FC::FieldType>,
+    {
+        let mask = self.encrypt_identity::<C>(ctx, pubkey, scalar, max_bits, window_bits);
+        let c1 = self.add_unequal(ctx, ciphertext.c1.clone(), mask.c1, true);
+        let c2 = self.add_unequal(ctx, ciphertext.c2.clone(), mask.c2, true);
+        ElGamalCiphertext::new(c1, c2)
+    }
+
+    pub fn assert_ciphertext_equal(
+        &self,
+        ctx: &mut Context<F>,
+        a: &ElGamalCiphertext<F, FC::FieldPoint>,
+        b: &ElGamalCiphertext<F, FC::FieldPoint>,
+    ) {
+        self.assert_equal(ctx, a.c1.clone(), b.c1.clone());
+        self.assert_equal(ctx, a.c2.clone(), b.c2.clone());
+    }
+}
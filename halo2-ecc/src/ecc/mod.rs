@@ -16,6 +16,7 @@ use rand_chacha::ChaCha20Rng;
 use std::marker::PhantomData;
 
 pub mod ecdsa;
+pub mod elgamal;
 pub mod fixed_base;
 pub mod schnorr_signature;
 // pub mod fixed_base_pippenger;
@@ -5,6 +5,7 @@ use crate::ff::PrimeField as _;
 use crate::impl_field_ext_chip_common;
 
 use super::{
+    fp::Reduced,
     vector::{FieldVector, FieldVectorChip},
     BigPrimeField, FieldChip, FieldExtConstructor, PrimeFieldChip,
     Selectable,
@@ -162,6 +163,43 @@ where
     }
 }
 
+// Coordinatewise selection of reduced Fp2 points, needed by `EccChip::variable_base_msm` over G2
+impl<'a, F, FpChip, Fp2, Fp> Selectable<F, FieldVector<Reduced<ProperCrtUint<F>, Fp>>>
+    for Fp2Chip<'a, F, FpChip, Fp2>
+where
+    F: BigPrimeField,
+    FpChip: PrimeFieldChip<F> + Selectable<F, ProperCrtUint<F>>,
+    FpChip::FieldType: BigPrimeField,
+    Fp2: crate::ff::Field,
+{
+    fn select(
+        &self,
+        ctx: &mut Context<F>,
+        a: FieldVector<Reduced<ProperCrtUint<F>, Fp>>,
+        b: FieldVector<Reduced<ProperCrtUint<F>, Fp>>,
+        sel: AssignedValue<F>,
+    ) -> FieldVector<Reduced<ProperCrtUint<F>, Fp>> {
+        self.0.select(ctx, a, b, sel)
+    }
+
+    fn select_by_indicator(
+        &self,
+        ctx: &mut Context<F>,
+        a: &impl AsRef<[FieldVector<Reduced<ProperCrtUint<F>, Fp>>]>,
+        coeffs: &[AssignedValue<F>],
+    ) -> FieldVector<Reduced<ProperCrtUint<F>, Fp>> {
+        let a = a.as_ref();
+        FieldVector(
+            (0..2)
+                .map(|i| {
+                    let coordinates = a.iter().map(|x| x.0[i].clone()).collect::<Vec<_>>();
+                    self.fp_chip().select_by_indicator(ctx, &coordinates, coeffs)
+                })
+                .collect(),
+        )
+    }
+}
+
 
 
 mod bn254 {
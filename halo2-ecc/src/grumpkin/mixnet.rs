@@ -0,0 +1,209 @@
+use halo2_base::gates::circuit::builder::BaseCircuitBuilder;
+use halo2_base::gates::GateChip;
+use halo2_base::poseidon::hasher::PoseidonHasher;
+use halo2_base::{AssignedValue, Context};
+
+use crate::ecc::elgamal::ElGamalCiphertext;
+use crate::ecc::{EcPoint, EccChip};
+use crate::fields::native_fp::NativeFieldChip;
+use crate::halo2_proofs::halo2curves::grumpkin::{Fq, G1Affine};
+
+// Grumpkin is defined over the BN254 scalar field, so its points have native coordinates in a BN254 circuit
+pub type GrumpkinChip<'chip> = EccChip<'chip, Fq, NativeFieldChip<'chip, Fq>>;
+pub type Ciphertext = ElGamalCiphertext<Fq, AssignedValue<Fq>>;
+
+// Re-encryption randomness is a Grumpkin scalar, i.e. an element of the BN254 base field, loaded as 2 limbs
+pub const SCALAR_LIMB_BITS: usize = 128;
+pub const NUM_SCALAR_LIMBS: usize = 2;
+
+// Verifiable re-encryption shuffle (mix) of ElGamal ciphertexts over Grumpkin:
+//   shuffled[i] = original[pi(i)] + r_i * (G, PK)
+// for a private permutation pi, committed to as Poseidon([salt, pi(0), ..., pi(n - 1)]).
+// The permutation is checked with the shuffle argument of `BaseCircuitBuilder`, on the tuples
+// (i, original[i]) and (pi(i), original[pi(i)]): since the indices i are distinct constants, the multisets
+// only agree if pi is a permutation and the permuted ciphertexts are the originals.
+pub struct MixnetChip<'chip> {
+    pub ecc_chip: &'chip GrumpkinChip<'chip>,
+    pub poseidon_chip: &'chip PoseidonHasher<Fq, 3, 2>,
+    pub window_bits: usize,
+}
+
+impl<'chip> MixnetChip<'chip> {
+    pub fn new(
+        ecc_chip: &'chip GrumpkinChip<'chip>,
+        poseidon_chip: &'chip PoseidonHasher<Fq, 3, 2>,
+        window_bits: usize,
+    ) -> Self {
+        Self { ecc_chip, poseidon_chip, window_bits }
+    }
+
+    pub fn load_ciphertext(
+        &self,
+        ctx: &mut Context<Fq>,
+        ciphertext: &native::Ciphertext,
+    ) -> Ciphertext {
+        self.ecc_chip.load_ciphertext::<G1Affine>(ctx, (ciphertext.c1, ciphertext.c2))
+    }
+
+    // Constrains `shuffled` to be a re-encryption shuffle of `original` under `pubkey`, using the private `witness`
+    // produced by `native::shuffle`, and returns the commitment to the permutation.
+    // The permutation is only constrained when `builder` is synthesized, so its parameters need `use_shuffle`.
+    pub fn verify_shuffle(
+        &self,
+        builder: &mut BaseCircuitBuilder<Fq>,
+        pubkey: &EcPoint<Fq, AssignedValue<Fq>>,
+        original: &[Ciphertext],
+        shuffled: &[Ciphertext],
+        witness: &native::ShuffleWitness,
+    ) -> AssignedValue<Fq> {
+        let n = original.len();
+        assert_eq!(
+            shuffled.len(),
+            n,
+            "shuffled list must have the same length as the original list"
+        );
+        assert_eq!(
+            witness.permutation.len(),
+            n,
+            "permutation must have the same length as the lists"
+        );
+        assert_eq!(
+            witness.randomness.len(),
+            n,
+            "randomness must have the same length as the lists"
+        );
+        assert!(witness.permutation.iter().all(|&j| j < n), "permutation index out of range");
+
+        let ctx = builder.main(0);
+        let salt = ctx.load_witness(witness.salt);
+        let indices = ctx.assign_witnesses(witness.permutation.iter().map(|&j| Fq::from(j as u64)));
+        let mut permuted_rows = Vec::with_capacity(n);
+        for ((index, &j), (r, target)) in
+            indices.iter().zip(&witness.permutation).zip(witness.randomness.iter().zip(shuffled))
+        {
+            // original[pi(i)] as a fresh witness, tied to `original` by the shuffle argument
+            let permuted = ElGamalCiphertext::new(
+                self.load_point_unchecked(ctx, &original[j].c1),
+                self.load_point_unchecked(ctx, &original[j].c2),
+            );
+            let r = ctx.assign_witnesses(native::scalar_limbs(r));
+            let re_encrypted = self.ecc_chip.re_encrypt::<G1Affine>(
+                ctx,
+                &permuted,
+                pubkey,
+                r,
+                SCALAR_LIMB_BITS,
+                self.window_bits,
+            );
+            self.ecc_chip.assert_ciphertext_equal(ctx, &re_encrypted, target);
+            permuted_rows.push(ciphertext_row(*index, &permuted));
+        }
+        let original_rows = original
+            .iter()
+            .enumerate()
+            .map(|(i, ciphertext)| {
+                ciphertext_row(ctx.load_constant(Fq::from(i as u64)), ciphertext)
+            })
+            .collect();
+        let commitment = self.poseidon_chip.hash_fix_len_array(
+            ctx,
+            &GateChip::default(),
+            &[&[salt], &indices[..]].concat(),
+        );
+        builder.assert_shuffle(original_rows, permuted_rows);
+        commitment
+    }
+
+    fn load_point_unchecked(
+        &self,
+        ctx: &mut Context<Fq>,
+        point: &EcPoint<Fq, AssignedValue<Fq>>,
+    ) -> EcPoint<Fq, AssignedValue<Fq>> {
+        self.ecc_chip.load_private_unchecked(ctx, (*point.x.value(), *point.y.value()))
+    }
+}
+
+fn ciphertext_row(index: AssignedValue<Fq>, ciphertext: &Ciphertext) -> Vec<AssignedValue<Fq>> {
+    vec![index, ciphertext.c1.x, ciphertext.c1.y, ciphertext.c2.x, ciphertext.c2.y]
+}
+
+// Native ElGamal over Grumpkin and the prover of the re-encryption shuffle
+pub mod native {
+    use super::{NUM_SCALAR_LIMBS, SCALAR_LIMB_BITS};
+    use crate::ff::Field;
+    use crate::group::Curve;
+    use crate::halo2_proofs::arithmetic::CurveAffine;
+    use crate::halo2_proofs::halo2curves::grumpkin::{Fq, Fr, G1Affine, G1};
+    use halo2_base::utils::{decompose_biguint, fe_to_biguint};
+    use poseidon::Poseidon;
+    use rand::seq::SliceRandom;
+    use rand::RngCore;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Ciphertext {
+        pub c1: G1Affine,
+        pub c2: G1Affine,
+    }
+
+    pub fn encrypt(pubkey: &G1Affine, message: &G1Affine, r: Fr) -> Ciphertext {
+        Ciphertext {
+            c1: (G1Affine::generator() * r).to_affine(),
+            c2: (G1::from(*message) + *pubkey * r).to_affine(),
+        }
+    }
+
+    pub fn decrypt(secret_key: Fr, ciphertext: &Ciphertext) -> G1Affine {
+        (G1::from(ciphertext.c2) - ciphertext.c1 * secret_key).to_affine()
+    }
+
+    pub fn re_encrypt(pubkey: &G1Affine, ciphertext: &Ciphertext, r: Fr) -> Ciphertext {
+        Ciphertext {
+            c1: (G1::from(ciphertext.c1) + G1Affine::generator() * r).to_affine(),
+            c2: (G1::from(ciphertext.c2) + *pubkey * r).to_affine(),
+        }
+    }
+
+    // Private witness of a re-encryption shuffle: shuffled[i] = re_encrypt(original[permutation[i]], randomness[i])
+    #[derive(Clone, Debug)]
+    pub struct ShuffleWitness {
+        pub permutation: Vec<usize>,
+        pub randomness: Vec<Fr>,
+        pub salt: Fq,
+    }
+
+    impl ShuffleWitness {
+        // Poseidon([salt, pi(0), ..., pi(n - 1)]), as returned by `MixnetChip::verify_shuffle`
+        pub fn commitment(&self) -> Fq {
+            let inputs = [self.salt]
+                .into_iter()
+                .chain(self.permutation.iter().map(|&j| Fq::from(j as u64)))
+                .collect::<Vec<_>>();
+            let mut sponge = Poseidon::<Fq, 3, 2>::new(8, 57);
+            sponge.update(&inputs);
+            sponge.squeeze()
+        }
+    }
+
+    // Shuffles and re-randomizes `original` with a random permutation and fresh randomness
+    pub fn shuffle(
+        pubkey: &G1Affine,
+        original: &[Ciphertext],
+        rng: &mut impl RngCore,
+    ) -> (Vec<Ciphertext>, ShuffleWitness) {
+        let mut permutation = (0..original.len()).collect::<Vec<_>>();
+        permutation.shuffle(rng);
+        let randomness = (0..original.len()).map(|_| Fr::random(&mut *rng)).collect::<Vec<_>>();
+        let salt = Fq::random(&mut *rng);
+        let shuffled = permutation
+            .iter()
+            .zip(&randomness)
+            .map(|(&j, r)| re_encrypt(pubkey, &original[j], *r))
+            .collect();
+        (shuffled, ShuffleWitness { permutation, randomness, salt })
+    }
+
+    // Limbs of a Grumpkin scalar as loaded by `MixnetChip::verify_shuffle`
+    pub fn scalar_limbs(r: &Fr) -> Vec<Fq> {
+        decompose_biguint::<Fq>(&fe_to_biguint(r), NUM_SCALAR_LIMBS, SCALAR_LIMB_BITS)
+    }
+}
@@ -0,0 +1,101 @@
+use crate::ff::Field;
+use crate::group::Curve;
+use crate::grumpkin::mixnet::{native, GrumpkinChip, MixnetChip};
+use crate::halo2_proofs::arithmetic::CurveAffine;
+use crate::halo2_proofs::dev::MockProver;
+use halo2_base::{
+    gates::circuit::builder::BaseCircuitBuilder,
+    gates::GateChip,
+    halo2_proofs::halo2curves::grumpkin::{Fq, Fr, G1Affine},
+    poseidon::hasher::{spec::OptimizedPoseidonSpec, PoseidonHasher},
+};
+use rand::rngs::StdRng;
+use rand_core::SeedableRng;
+
+use crate::fields::native_fp::NativeFieldChip;
+
+const K: usize = 17;
+const NUM_CIPHERTEXTS: usize = 4;
+
+fn random_ciphertexts(
+    pubkey: &G1Affine,
+    rng: &mut StdRng,
+) -> (Vec<G1Affine>, Vec<native::Ciphertext>) {
+    let messages = (0..NUM_CIPHERTEXTS).map(|_| G1Affine::random(&mut *rng)).collect::<Vec<_>>();
+    let ciphertexts =
+        messages.iter().map(|m| native::encrypt(pubkey, m, Fr::random(&mut *rng))).collect();
+    (messages, ciphertexts)
+}
+
+#[test]
+fn test_elgamal_native() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let secret_key = Fr::random(&mut rng);
+    let pubkey = (G1Affine::generator() * secret_key).to_affine();
+    let (messages, original) = random_ciphertexts(&pubkey, &mut rng);
+    let (shuffled, witness) = native::shuffle(&pubkey, &original, &mut rng);
+    for (i, ciphertext) in shuffled.iter().enumerate() {
+        assert_ne!(*ciphertext, original[witness.permutation[i]]);
+        assert_eq!(native::decrypt(secret_key, ciphertext), messages[witness.permutation[i]]);
+    }
+}
+
+// Runs the MockProver on the shuffle of `original` into `shuffled` with `witness`, returns whether it is satisfied
+fn mixnet_test(
+    pubkey: &G1Affine,
+    original: &[native::Ciphertext],
+    shuffled: &[native::Ciphertext],
+    witness: &native::ShuffleWitness,
+) -> bool {
+    let mut builder = BaseCircuitBuilder::<Fq>::new(false).use_k(K).use_lookup_bits(8);
+    let range = builder.range_chip();
+    let fp_chip = NativeFieldChip::<Fq>::new(&range);
+    let ecc_chip = GrumpkinChip::new(&fp_chip);
+    let ctx = builder.main(0);
+    let mut poseidon_chip =
+        PoseidonHasher::<Fq, 3, 2>::new(OptimizedPoseidonSpec::new::<8, 57, 0>());
+    poseidon_chip.initialize_consts(ctx, &GateChip::default());
+    let mixnet_chip = MixnetChip::new(&ecc_chip, &poseidon_chip, 4);
+
+    let pubkey = ecc_chip.load_private::<G1Affine>(ctx, (pubkey.x, pubkey.y));
+    let original = original.iter().map(|c| mixnet_chip.load_ciphertext(ctx, c)).collect::<Vec<_>>();
+    let shuffled = shuffled.iter().map(|c| mixnet_chip.load_ciphertext(ctx, c)).collect::<Vec<_>>();
+    let commitment =
+        mixnet_chip.verify_shuffle(&mut builder, &pubkey, &original, &shuffled, witness);
+    assert_eq!(*commitment.value(), witness.commitment());
+
+    builder.calculate_params(Some(9));
+    MockProver::run(K as u32, &builder, vec![]).unwrap().verify().is_ok()
+}
+
+#[test]
+fn test_mixnet_shuffle() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let pubkey = G1Affine::random(&mut rng);
+    let (_, original) = random_ciphertexts(&pubkey, &mut rng);
+    let (shuffled, witness) = native::shuffle(&pubkey, &original, &mut rng);
+    assert!(mixnet_test(&pubkey, &original, &shuffled, &witness));
+}
+
+#[test]
+fn test_mixnet_wrong_randomness() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let pubkey = G1Affine::random(&mut rng);
+    let (_, original) = random_ciphertexts(&pubkey, &mut rng);
+    let (shuffled, mut witness) = native::shuffle(&pubkey, &original, &mut rng);
+    witness.randomness[0] += Fr::ONE;
+    assert!(!mixnet_test(&pubkey, &original, &shuffled, &witness));
+}
+
+#[test]
+fn test_mixnet_not_permutation() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let pubkey = G1Affine::random(&mut rng);
+    let (_, original) = random_ciphertexts(&pubkey, &mut rng);
+    let (mut shuffled, mut witness) = native::shuffle(&pubkey, &original, &mut rng);
+    // re-encrypt the first ciphertext twice and drop the second one
+    let second = witness.permutation.iter().position(|&j| j == 1).unwrap();
+    witness.permutation[second] = 0;
+    shuffled[second] = native::re_encrypt(&pubkey, &original[0], witness.randomness[second]);
+    assert!(!mixnet_test(&pubkey, &original, &shuffled, &witness));
+}
@@ -19,6 +19,8 @@ use crate::{
     fields::{fp::FpChip, native_fp::NativeFieldChip, FieldChip, FpStrategy},
 };
 
+mod mixnet;
+
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 struct CircuitParams {
     strategy: FpStrategy,
@@ -0,0 +1,306 @@
+use std::cell::RefCell;
+
+use halo2_base::{
+    gates::{GateInstructions, RangeChip, RangeInstructions},
+    poseidon::hasher::PoseidonHasher,
+    safe_types::{FixLenBytes, FixLenBytesVec, SafeByte, SafeTypeChip},
+    AssignedValue, Context,
+    QuantumCell::Constant,
+};
+use itertools::Itertools;
+use sha3::{Digest, Keccak256};
+
+use crate::{
+    keccak::{
+        component::{
+            encode::encode_fix_len_bytes_vec,
+            output::{dummy_circuit_output, KeccakCircuitOutput},
+            param::{POSEIDON_RATE, POSEIDON_T},
+        },
+        vanilla::keccak_packed_multi::get_num_keccak_f,
+    },
+    util::eth_types::Field,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Number of bytes of a Keccak-256 digest, and of every leaf and node of the tree.
+pub const NUM_BYTES_NODE: usize = 32;
+
+/// A leaf or node of a Keccak Merkle tree.
+pub type KeccakNode<F> = FixLenBytes<F, NUM_BYTES_NODE>;
+
+/// How the two children of an internal node are ordered before hashing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeccakMerkleConvention {
+    /// `keccak256(min(a, b) || max(a, b))`, comparing nodes as big-endian integers. This is the convention of
+    /// OpenZeppelin's `MerkleProof`, where proofs do not carry the position of the leaf.
+    SortedPair,
+    /// `keccak256(left || right)`, where the position of the leaf decides the side of each sibling.
+    Positional,
+}
+
+/// Chip verifying Merkle proofs of Keccak-256 trees, as used by Ethereum contracts.
+///
+/// Keccak is not computed in this circuit: every hash is witnessed and recorded as a promise
+/// `(key, hash_lo, hash_hi)` in the output format of the Keccak component circuit, where `key` is the lookup key
+/// of the preimage computed with [encode_fix_len_bytes_vec]. The caller must check [Self::promise_commit] against
+/// the output commitment published by a Keccak component circuit.
+#[derive(Debug)]
+pub struct KeccakMerkleChip<'a, F: Field> {
+    range: &'a RangeChip<F>,
+    initialized_hasher: &'a PoseidonHasher<F, POSEIDON_T, POSEIDON_RATE>,
+    promises: RefCell<Vec<KeccakCircuitOutput<AssignedValue<F>>>>,
+}
+
+impl<'a, F: Field> KeccakMerkleChip<'a, F> {
+    /// Creates a chip from a range chip and a Poseidon hasher with initialized constants.
+    pub fn new(
+        range: &'a RangeChip<F>,
+        initialized_hasher: &'a PoseidonHasher<F, POSEIDON_T, POSEIDON_RATE>,
+    ) -> Self {
+        Self { range, initialized_hasher, promises: RefCell::new(vec![]) }
+    }
+
+    /// Keccak promises recorded so far, in the order of the hashes.
+    pub fn promises(&self) -> Vec<KeccakCircuitOutput<AssignedValue<F>>> {
+        self.promises.borrow().clone()
+    }
+
+    /// Keccak-256 of fixed length bytes. The digest is witnessed and a promise is recorded for it.
+    pub fn keccak_fix_len(&self, ctx: &mut Context<F>, bytes: &FixLenBytesVec<F>) -> KeccakNode<F> {
+        let preimage =
+            bytes.bytes().iter().map(|b| b.value().get_lower_32() as u8).collect::<Vec<_>>();
+        let digest = Keccak256::digest(&preimage);
+        let digest = ctx.assign_witnesses(digest.iter().map(|b| F::from(*b as u64)));
+        let digest = SafeTypeChip::new(self.range)
+            .raw_to_fix_len_bytes::<NUM_BYTES_NODE>(ctx, digest.try_into().unwrap());
+
+        let key = encode_fix_len_bytes_vec(ctx, self.range.gate(), self.initialized_hasher, bytes);
+        let (hash_hi, hash_lo) = self.to_hi_lo(ctx, &digest);
+        // The component circuit outputs one dummy result for every keccak_f but the last one of an input.
+        let dummy = dummy_circuit_output::<F>();
+        let mut promises = self.promises.borrow_mut();
+        for _ in 1..get_num_keccak_f(bytes.len()) {
+            promises.push(KeccakCircuitOutput {
+                key: ctx.load_constant(dummy.key),
+                hash_lo: ctx.load_constant(dummy.hash_lo),
+                hash_hi: ctx.load_constant(dummy.hash_hi),
+            });
+        }
+        promises.push(KeccakCircuitOutput { key, hash_lo, hash_hi });
+        digest
+    }
+
+    /// Splits 32 bytes into their big-endian (hi, lo) 128-bit limbs, as in the component circuit outputs.
+    pub fn to_hi_lo(
+        &self,
+        ctx: &mut Context<F>,
+        node: &KeccakNode<F>,
+    ) -> (AssignedValue<F>, AssignedValue<F>) {
+        let gate = self.range.gate();
+        let multipliers = (0..16).rev().map(|i| Constant(F::from_u128(1u128 << (8 * i))));
+        let hi = gate.inner_product(ctx, node.bytes()[..16].iter().copied(), multipliers.clone());
+        let lo = gate.inner_product(ctx, node.bytes()[16..].iter().copied(), multipliers);
+        (hi, lo)
+    }
+
+    /// `keccak256(left || right)`
+    pub fn hash_pair(
+        &self,
+        ctx: &mut Context<F>,
+        left: &KeccakNode<F>,
+        right: &KeccakNode<F>,
+    ) -> KeccakNode<F> {
+        let bytes = left.bytes().iter().chain(right.bytes().iter()).copied().collect_vec();
+        self.keccak_fix_len(ctx, &FixLenBytesVec::new(bytes, 2 * NUM_BYTES_NODE))
+    }
+
+    /// `keccak256(min(a, b) || max(a, b))` with nodes compared as 256-bit big-endian integers.
+    pub fn hash_sorted_pair(
+        &self,
+        ctx: &mut Context<F>,
+        a: &KeccakNode<F>,
+        b: &KeccakNode<F>,
+    ) -> KeccakNode<F> {
+        let gate = self.range.gate();
+        let (a_hi, a_lo) = self.to_hi_lo(ctx, a);
+        let (b_hi, b_lo) = self.to_hi_lo(ctx, b);
+        let hi_lt = self.range.is_less_than(ctx, a_hi, b_hi, 128);
+        let hi_eq = gate.is_equal(ctx, a_hi, b_hi);
+        let lo_lt = self.range.is_less_than(ctx, a_lo, b_lo, 128);
+        let lo_lt = gate.and(ctx, hi_eq, lo_lt);
+        let a_first = gate.or(ctx, hi_lt, lo_lt);
+        let (left, right) = self.order_pair(ctx, a, b, a_first);
+        self.hash_pair(ctx, &left, &right)
+    }
+
+    // Returns (a, b) if `a_first` is 1, otherwise (b, a)
+    fn order_pair(
+        &self,
+        ctx: &mut Context<F>,
+        a: &KeccakNode<F>,
+        b: &KeccakNode<F>,
+        a_first: AssignedValue<F>,
+    ) -> (KeccakNode<F>, KeccakNode<F>) {
+        let gate = self.range.gate();
+        let (left, right): (Vec<SafeByte<F>>, Vec<SafeByte<F>>) = a
+            .bytes()
+            .iter()
+            .zip(b.bytes().iter())
+            .map(|(a, b)| {
+                // selecting between two bytes gives a byte
+                let left = gate.select(ctx, *a, *b, a_first);
+                let right = gate.select(ctx, *b, *a, a_first);
+                (SafeTypeChip::unsafe_to_byte(left), SafeTypeChip::unsafe_to_byte(right))
+            })
+            .unzip();
+        (KeccakNode::new(left.try_into().unwrap()), KeccakNode::new(right.try_into().unwrap()))
+    }
+
+    /// Root of the tree from `leaf` and its proof, with the sorted-pair convention.
+    pub fn compute_root_sorted(
+        &self,
+        ctx: &mut Context<F>,
+        leaf: &KeccakNode<F>,
+        proof: &[KeccakNode<F>],
+    ) -> KeccakNode<F> {
+        proof.iter().fold(leaf.clone(), |node, sibling| self.hash_sorted_pair(ctx, &node, sibling))
+    }
+
+    /// Root of the tree from `leaf` at position `leaf_index` and its proof, with the positional convention.
+    /// `proof` goes from the leaf level up and `leaf_index` is constrained to `proof.len()` bits.
+    pub fn compute_root_positional(
+        &self,
+        ctx: &mut Context<F>,
+        leaf: &KeccakNode<F>,
+        proof: &[KeccakNode<F>],
+        leaf_index: AssignedValue<F>,
+    ) -> KeccakNode<F> {
+        let bits = self.range.gate().num_to_bits(ctx, leaf_index, proof.len());
+        proof.iter().zip(bits).fold(leaf.clone(), |node, (sibling, is_right)| {
+            let (left, right) = self.order_pair(ctx, sibling, &node, is_right);
+            self.hash_pair(ctx, &left, &right)
+        })
+    }
+
+    /// Returns 1 iff `leaf` is in the tree whose root has big-endian 128-bit limbs `(root_hi, root_lo)`,
+    /// with the sorted-pair convention.
+    pub fn verify_sorted(
+        &self,
+        ctx: &mut Context<F>,
+        root: (AssignedValue<F>, AssignedValue<F>),
+        leaf: &KeccakNode<F>,
+        proof: &[KeccakNode<F>],
+    ) -> AssignedValue<F> {
+        let computed_root = self.compute_root_sorted(ctx, leaf, proof);
+        self.is_root(ctx, root, &computed_root)
+    }
+
+    /// Returns 1 iff `leaf` is at position `leaf_index` in the tree whose root has big-endian 128-bit limbs
+    /// `(root_hi, root_lo)`, with the positional convention.
+    pub fn verify_positional(
+        &self,
+        ctx: &mut Context<F>,
+        root: (AssignedValue<F>, AssignedValue<F>),
+        leaf: &KeccakNode<F>,
+        proof: &[KeccakNode<F>],
+        leaf_index: AssignedValue<F>,
+    ) -> AssignedValue<F> {
+        let computed_root = self.compute_root_positional(ctx, leaf, proof, leaf_index);
+        self.is_root(ctx, root, &computed_root)
+    }
+
+    fn is_root(
+        &self,
+        ctx: &mut Context<F>,
+        (root_hi, root_lo): (AssignedValue<F>, AssignedValue<F>),
+        computed_root: &KeccakNode<F>,
+    ) -> AssignedValue<F> {
+        let gate = self.range.gate();
+        let (hi, lo) = self.to_hi_lo(ctx, computed_root);
+        let hi_eq = gate.is_equal(ctx, hi, root_hi);
+        let lo_eq = gate.is_equal(ctx, lo, root_lo);
+        gate.and(ctx, hi_eq, lo_eq)
+    }
+
+    /// Poseidon commitment of the promises padded with dummy outputs to `capacity`, computed as the output
+    /// commitment of a Keccak component circuit with that capacity. The promises are sound once this equals
+    /// the commitment published by such a circuit.
+    pub fn promise_commit(&self, ctx: &mut Context<F>, capacity: usize) -> AssignedValue<F> {
+        let promises = self.promises.borrow();
+        assert!(promises.len() <= capacity, "more keccak promises than the capacity");
+        let dummy = dummy_circuit_output::<F>();
+        let mut inputs = promises
+            .iter()
+            .flat_map(|promise| [promise.key, promise.hash_lo, promise.hash_hi])
+            .collect_vec();
+        for _ in promises.len()..capacity {
+            inputs.extend([dummy.key, dummy.hash_lo, dummy.hash_hi].map(|x| ctx.load_constant(x)));
+        }
+        self.initialized_hasher.hash_fix_len_array(ctx, self.range.gate(), &inputs)
+    }
+}
+
+/// Native Keccak Merkle trees. Leaves are padded with zero nodes to a power of two.
+pub mod native {
+    use sha3::{Digest, Keccak256};
+
+    use super::KeccakMerkleConvention;
+
+    /// Hash of an internal node under `convention`.
+    pub fn hash_pair(a: &[u8; 32], b: &[u8; 32], convention: KeccakMerkleConvention) -> [u8; 32] {
+        let (left, right) = match convention {
+            KeccakMerkleConvention::SortedPair if b < a => (b, a),
+            _ => (a, b),
+        };
+        Keccak256::new().chain_update(left).chain_update(right).finalize().into()
+    }
+
+    /// Splits a node into its big-endian (hi, lo) 128-bit limbs.
+    pub fn to_hi_lo(node: &[u8; 32]) -> (u128, u128) {
+        (
+            u128::from_be_bytes(node[..16].try_into().unwrap()),
+            u128::from_be_bytes(node[16..].try_into().unwrap()),
+        )
+    }
+
+    // All levels of the tree, from the padded leaves to the root
+    fn levels(leaves: &[[u8; 32]], convention: KeccakMerkleConvention) -> Vec<Vec<[u8; 32]>> {
+        assert!(!leaves.is_empty(), "leaves must not be empty");
+        let mut level = leaves.to_vec();
+        level.resize(leaves.len().next_power_of_two(), [0; 32]);
+        let mut levels = vec![level];
+        while levels.last().unwrap().len() > 1 {
+            let level = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| hash_pair(&pair[0], &pair[1], convention))
+                .collect();
+            levels.push(level);
+        }
+        levels
+    }
+
+    /// Root of the tree with `leaves`.
+    pub fn merkle_root(leaves: &[[u8; 32]], convention: KeccakMerkleConvention) -> [u8; 32] {
+        levels(leaves, convention).last().unwrap()[0]
+    }
+
+    /// Siblings of the leaf at `index`, from the leaf level up.
+    pub fn merkle_proof(
+        leaves: &[[u8; 32]],
+        index: usize,
+        convention: KeccakMerkleConvention,
+    ) -> Vec<[u8; 32]> {
+        assert!(index < leaves.len(), "leaf index out of range");
+        let levels = levels(leaves, convention);
+        levels[..levels.len() - 1]
+            .iter()
+            .enumerate()
+            .map(|(height, level)| level[(index >> height) ^ 1])
+            .collect()
+    }
+}
@@ -0,0 +1,119 @@
+use halo2_base::{
+    gates::{RangeChip, RangeInstructions},
+    halo2_proofs::halo2curves::{
+        bn256::Fr,
+        ff::{Field, PrimeField},
+    },
+    safe_types::SafeTypeChip,
+    utils::testing::base_test,
+    Context,
+};
+use rand::Rng;
+
+use super::{
+    native::{hash_pair, merkle_proof, merkle_root, to_hi_lo},
+    KeccakMerkleChip, KeccakMerkleConvention, KeccakNode, NUM_BYTES_NODE,
+};
+use crate::keccak::component::{
+    circuit::shard::create_hasher,
+    output::{calculate_circuit_outputs_commit, multi_inputs_to_circuit_outputs},
+};
+
+fn load_node(ctx: &mut Context<Fr>, range: &RangeChip<Fr>, node: &[u8; 32]) -> KeccakNode<Fr> {
+    let bytes = ctx.assign_witnesses(node.iter().map(|b| Fr::from(*b as u64)));
+    SafeTypeChip::new(range).raw_to_fix_len_bytes::<NUM_BYTES_NODE>(ctx, bytes.try_into().unwrap())
+}
+
+fn root_hi_lo(root: &[u8; 32]) -> (Fr, Fr) {
+    let (hi, lo) = to_hi_lo(root);
+    (Fr::from_u128(hi), Fr::from_u128(lo))
+}
+
+// Preimages of the internal nodes hashed along the path of `index`, in the order the chip hashes them
+fn path_preimages(
+    leaf: [u8; 32],
+    proof: &[[u8; 32]],
+    index: usize,
+    convention: KeccakMerkleConvention,
+) -> Vec<Vec<u8>> {
+    let mut node = leaf;
+    let mut preimages = vec![];
+    for (height, sibling) in proof.iter().enumerate() {
+        let (left, right) = match convention {
+            KeccakMerkleConvention::SortedPair if *sibling < node => (*sibling, node),
+            KeccakMerkleConvention::SortedPair => (node, *sibling),
+            KeccakMerkleConvention::Positional if (index >> height) & 1 == 1 => (*sibling, node),
+            KeccakMerkleConvention::Positional => (node, *sibling),
+        };
+        let preimage = [left, right].concat();
+        node = hash_pair(&left, &right, KeccakMerkleConvention::Positional);
+        preimages.push(preimage);
+    }
+    preimages
+}
+
+fn test_keccak_merkle(convention: KeccakMerkleConvention) {
+    let mut rng = rand::thread_rng();
+    let leaves = (0..11).map(|_| rng.gen::<[u8; 32]>()).collect::<Vec<_>>();
+    let root = merkle_root(&leaves, convention);
+    let index = 6;
+    let proof = merkle_proof(&leaves, index, convention);
+    let preimages = path_preimages(leaves[index], &proof, index, convention);
+    let capacity = 8;
+
+    base_test().k(18).lookup_bits(8).run(|ctx: &mut Context<Fr>, range: &RangeChip<Fr>| {
+        let mut hasher = create_hasher();
+        hasher.initialize_consts(ctx, range.gate());
+        let chip = KeccakMerkleChip::new(range, &hasher);
+
+        let (root_hi, root_lo) = root_hi_lo(&root);
+        let root_assigned = (ctx.load_witness(root_hi), ctx.load_witness(root_lo));
+        let leaf = load_node(ctx, range, &leaves[index]);
+        let proof = proof.iter().map(|node| load_node(ctx, range, node)).collect::<Vec<_>>();
+        let leaf_index = ctx.load_witness(Fr::from(index as u64));
+
+        let (is_valid, computed_root) = match convention {
+            KeccakMerkleConvention::SortedPair => (
+                chip.verify_sorted(ctx, root_assigned, &leaf, &proof),
+                chip.compute_root_sorted(ctx, &leaf, &proof),
+            ),
+            KeccakMerkleConvention::Positional => (
+                chip.verify_positional(ctx, root_assigned, &leaf, &proof, leaf_index),
+                chip.compute_root_positional(ctx, &leaf, &proof, leaf_index),
+            ),
+        };
+        assert_eq!(*is_valid.value(), Fr::ONE);
+        let (hi, lo) = chip.to_hi_lo(ctx, &computed_root);
+        assert_eq!((*hi.value(), *lo.value()), (root_hi, root_lo));
+
+        // both root computations hashed the same path
+        let expected_commit =
+            calculate_circuit_outputs_commit(&multi_inputs_to_circuit_outputs::<Fr>(
+                &[preimages.clone(), preimages.clone()].concat(),
+                capacity,
+            ));
+        let commit = chip.promise_commit(ctx, capacity);
+        assert_eq!(*commit.value(), expected_commit);
+
+        let wrong_leaf = load_node(ctx, range, &leaves[index + 1]);
+        let is_valid = match convention {
+            KeccakMerkleConvention::SortedPair => {
+                chip.verify_sorted(ctx, root_assigned, &wrong_leaf, &proof)
+            }
+            KeccakMerkleConvention::Positional => {
+                chip.verify_positional(ctx, root_assigned, &wrong_leaf, &proof, leaf_index)
+            }
+        };
+        assert_eq!(*is_valid.value(), Fr::ZERO);
+    });
+}
+
+#[test]
+fn mock_keccak_merkle_sorted() {
+    test_keccak_merkle(KeccakMerkleConvention::SortedPair);
+}
+
+#[test]
+fn mock_keccak_merkle_positional() {
+    test_keccak_merkle(KeccakMerkleConvention::Positional);
+}
@@ -7,5 +7,7 @@
 
 /// Module for component circuits.
 pub mod component;
+/// Module for Merkle proofs over Keccak-256 checked against the Keccak component circuit.
+pub mod merkle;
 /// Module for Keccak circuits in vanilla halo2.
 pub mod vanilla;
@@ -0,0 +1,3 @@
+pub mod shard;
+#[cfg(test)]
+mod tests;
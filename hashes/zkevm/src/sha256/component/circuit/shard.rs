@@ -0,0 +1,512 @@
+use std::cell::RefCell;
+
+use crate::{
+    keccak::component::circuit::shard::create_hasher,
+    sha256::{
+        component::{
+            encode::{
+                get_words_to_witness_multipliers, num_poseidon_absorb_per_block,
+                num_word_per_witness,
+            },
+            output::{
+                calculate_circuit_outputs_commit, dummy_circuit_output,
+                multi_inputs_to_circuit_outputs, Sha256CircuitOutput,
+            },
+            param::*,
+        },
+        vanilla::{
+            columns::Sha256CircuitConfig,
+            param::{NUM_WORDS_TO_ABSORB, SHA256_NUM_ROWS},
+            util::get_num_sha2_blocks,
+            witness::AssignedSha256Block,
+        },
+    },
+    util::eth_types::Field,
+};
+use getset::{CopyGetters, Getters, MutGetters};
+use halo2_base::{
+    gates::{
+        circuit::{builder::BaseCircuitBuilder, BaseCircuitParams, BaseConfig},
+        flex_gate::MultiPhaseThreadBreakPoints,
+        GateChip, GateInstructions,
+    },
+    halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner},
+        plonk::{Circuit, ConstraintSystem, Error},
+    },
+    poseidon::hasher::{PoseidonCompactChunkInput, PoseidonCompactOutput, PoseidonHasher},
+    safe_types::{SafeBool, SafeTypeChip},
+    virtual_region::copy_constraints::SharedCopyConstraintManager,
+    AssignedValue, Context,
+    QuantumCell::Constant,
+};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use snark_verifier_sdk::CircuitExt;
+
+/// SHA-256 Component Shard Circuit
+#[derive(Getters, MutGetters)]
+pub struct Sha256ComponentShardCircuit<F: Field> {
+    /// The multiple inputs to be hashed.
+    #[getset(get = "pub")]
+    inputs: RefCell<Vec<Vec<u8>>>,
+
+    /// Parameters of this circuit. The same parameters always construct the same circuit.
+    #[getset(get_mut = "pub")]
+    params: Sha256ComponentShardCircuitParams,
+    #[getset(get = "pub")]
+    base_circuit_builder: RefCell<BaseCircuitBuilder<F>>,
+    /// Poseidon hasher. Stateless once initialized.
+    #[getset(get = "pub")]
+    hasher: RefCell<PoseidonHasher<F, POSEIDON_T, POSEIDON_RATE>>,
+    /// Stateless gate chip
+    #[getset(get = "pub")]
+    gate_chip: GateChip<F>,
+}
+
+/// Parameters of Sha256ComponentShardCircuit.
+#[derive(Default, Clone, CopyGetters, Serialize, Deserialize)]
+pub struct Sha256ComponentShardCircuitParams {
+    /// This circuit has 2^k rows.
+    #[getset(get_copy = "pub")]
+    k: usize,
+    // Number of unusable rows withhold by Halo2.
+    #[getset(get_copy = "pub")]
+    num_unusable_row: usize,
+    /// Max SHA-256 blocks this circuits can accept. The circuit can at most process `capacity` of inputs
+    /// with < RATE - NUM_BYTES_PADDING_LENGTH bytes or an input with `capacity * RATE - NUM_BYTES_PADDING_LENGTH - 1` bytes.
+    #[getset(get_copy = "pub")]
+    capacity: usize,
+    // If true, publish raw outputs. Otherwise, publish Poseidon commitment of raw outputs.
+    #[getset(get_copy = "pub")]
+    publish_raw_outputs: bool,
+
+    // Derived parameters of sub-circuits.
+    pub base_circuit_params: BaseCircuitParams,
+}
+
+impl Sha256ComponentShardCircuitParams {
+    /// Create a new Sha256ComponentShardCircuitParams.
+    pub fn new(
+        k: usize,
+        num_unusable_row: usize,
+        capacity: usize,
+        publish_raw_outputs: bool,
+    ) -> Self {
+        assert!(1 << k > num_unusable_row, "Number of unusable rows must be less than 2^k");
+        let max_rows = (1 << k) - num_unusable_row;
+        // Derived from [crate::sha256::vanilla::util::get_sha2_capacity].
+        assert!(
+            capacity * SHA256_NUM_ROWS <= max_rows,
+            "No enough rows for the specified capacity"
+        );
+        let base_circuit_params = BaseCircuitParams {
+            k,
+            lookup_bits: None,
+            num_instance_columns: if publish_raw_outputs {
+                OUTPUT_NUM_COL_RAW
+            } else {
+                OUTPUT_NUM_COL_COMMIT
+            },
+            ..Default::default()
+        };
+        Self { k, num_unusable_row, capacity, publish_raw_outputs, base_circuit_params }
+    }
+}
+
+/// Circuit::Config for SHA-256 Component Shard Circuit.
+#[derive(Clone)]
+pub struct Sha256ComponentShardConfig<F: Field> {
+    pub base_circuit_config: BaseConfig<F>,
+    pub sha256_circuit_config: Sha256CircuitConfig<F>,
+}
+
+impl<F: Field> Circuit<F> for Sha256ComponentShardCircuit<F> {
+    type Config = Sha256ComponentShardConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+    type Params = Sha256ComponentShardCircuitParams;
+
+    fn params(&self) -> Self::Params {
+        self.params.clone()
+    }
+
+    /// Creates a new instance of the [Sha256ComponentShardCircuit] without witnesses by setting the witness_gen_only flag to false
+    fn without_witnesses(&self) -> Self {
+        unimplemented!()
+    }
+
+    /// Configures a new circuit using [`BaseCircuitParams`]
+    fn configure_with_params(meta: &mut ConstraintSystem<F>, params: Self::Params) -> Self::Config {
+        let sha256_circuit_config = Sha256CircuitConfig::new(meta);
+        let base_circuit_params = params.base_circuit_params;
+        // BaseCircuitBuilder::configure_with_params must be called in the end in order to get the correct
+        // unusable_rows.
+        let base_circuit_config =
+            BaseCircuitBuilder::configure_with_params(meta, base_circuit_params.clone());
+        Self::Config { base_circuit_config, sha256_circuit_config }
+    }
+
+    fn configure(_: &mut ConstraintSystem<F>) -> Self::Config {
+        unreachable!("You must use configure_with_params");
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let mut sha256_assigned_blocks: Vec<AssignedSha256Block<'_, F>> = Vec::default();
+        layouter.assign_region(
+            || "sha256 circuit",
+            |mut region| {
+                sha256_assigned_blocks = config.sha256_circuit_config.multi_sha256(
+                    &mut region,
+                    self.inputs.borrow().clone(),
+                    Some(self.params.capacity),
+                );
+                Ok(())
+            },
+        )?;
+
+        // Base circuit witness generation.
+        let loaded_sha256_blocks = self.load_sha256_assigned_blocks(sha256_assigned_blocks);
+        self.generate_base_circuit_witnesses(&loaded_sha256_blocks);
+
+        self.base_circuit_builder.borrow().synthesize(config.base_circuit_config, layouter)?;
+
+        // Reset the circuit to the initial state so synthesize could be called multiple times.
+        self.base_circuit_builder.borrow_mut().clear();
+        self.hasher.borrow_mut().clear();
+        Ok(())
+    }
+}
+
+/// Witnesses of a SHA-256 block which are necessary to be loaded into halo2-lib.
+#[derive(Clone, Copy, Debug, CopyGetters, Getters)]
+pub struct LoadedSha256Block<F: Field> {
+    /// Length in bytes of the input processed up to the end of this block, excluding padding.
+    #[getset(get_copy = "pub")]
+    pub(crate) length: AssignedValue<F>,
+    /// Input words (u32, little-endian) of this block, with the padding bytes set to 0.
+    #[getset(get = "pub")]
+    pub(crate) word_values: [AssignedValue<F>; NUM_WORDS_TO_ABSORB],
+    /// Whether this block is the last one of an input.
+    #[getset(get_copy = "pub")]
+    pub(crate) is_final: SafeBool<F>,
+    /// The lower 16 bytes (in big-endian, 16..) of the output of this block.
+    #[getset(get_copy = "pub")]
+    pub(crate) hash_lo: AssignedValue<F>,
+    /// The high 16 bytes (in big-endian, ..16) of the output of this block.
+    #[getset(get_copy = "pub")]
+    pub(crate) hash_hi: AssignedValue<F>,
+}
+
+impl<F: Field> LoadedSha256Block<F> {
+    pub fn new(
+        length: AssignedValue<F>,
+        word_values: [AssignedValue<F>; NUM_WORDS_TO_ABSORB],
+        is_final: SafeBool<F>,
+        hash_lo: AssignedValue<F>,
+        hash_hi: AssignedValue<F>,
+    ) -> Self {
+        Self { length, word_values, is_final, hash_lo, hash_hi }
+    }
+}
+
+impl<F: Field> Sha256ComponentShardCircuit<F> {
+    /// Create a new Sha256ComponentShardCircuit.
+    pub fn new(
+        inputs: Vec<Vec<u8>>,
+        params: Sha256ComponentShardCircuitParams,
+        witness_gen_only: bool,
+    ) -> Self {
+        let input_size = inputs.iter().map(|input| get_num_sha2_blocks(input.len())).sum::<usize>();
+        assert!(input_size <= params.capacity, "Input size exceeds capacity");
+        let mut base_circuit_builder = BaseCircuitBuilder::new(witness_gen_only);
+        base_circuit_builder.set_params(params.base_circuit_params.clone());
+        Self {
+            inputs: RefCell::new(inputs),
+            params,
+            base_circuit_builder: RefCell::new(base_circuit_builder),
+            hasher: RefCell::new(create_hasher()),
+            gate_chip: GateChip::new(),
+        }
+    }
+
+    /// Get break points of BaseCircuitBuilder.
+    pub fn base_circuit_break_points(&self) -> MultiPhaseThreadBreakPoints {
+        self.base_circuit_builder.borrow().break_points()
+    }
+
+    /// Set break points of BaseCircuitBuilder.
+    pub fn set_base_circuit_break_points(&self, break_points: MultiPhaseThreadBreakPoints) {
+        self.base_circuit_builder.borrow_mut().set_break_points(break_points);
+    }
+
+    pub fn update_base_circuit_params(&mut self, params: &BaseCircuitParams) {
+        self.params.base_circuit_params = params.clone();
+        self.base_circuit_builder.borrow_mut().set_params(params.clone());
+    }
+
+    /// Simulate witness generation of the base circuit to determine BaseCircuitParams because the number of columns
+    /// of the base circuit can only be known after witness generation.
+    pub fn calculate_base_circuit_params(
+        params: &Sha256ComponentShardCircuitParams,
+    ) -> BaseCircuitParams {
+        // Create a simulation circuit to calculate base circuit parameters.
+        let simulation_circuit = Self::new(vec![], params.clone(), false);
+        let loaded_sha256_blocks = simulation_circuit.mock_load_sha256_assigned_blocks();
+        simulation_circuit.generate_base_circuit_witnesses(&loaded_sha256_blocks);
+
+        let base_circuit_params = simulation_circuit
+            .base_circuit_builder
+            .borrow_mut()
+            .calculate_params(Some(params.num_unusable_row));
+        // prevent drop warnings
+        simulation_circuit.base_circuit_builder.borrow_mut().clear();
+
+        base_circuit_params
+    }
+
+    /// Mock loading SHA-256 assigned blocks from SHA-256 circuit. This function doesn't create any witnesses/constraints.
+    fn mock_load_sha256_assigned_blocks(&self) -> Vec<LoadedSha256Block<F>> {
+        let base_circuit_builder = self.base_circuit_builder.borrow();
+        let mut copy_manager = base_circuit_builder.core().copy_manager.lock().unwrap();
+        (0..self.params.capacity)
+            .map(|_| LoadedSha256Block {
+                length: copy_manager.mock_external_assigned(F::ZERO),
+                word_values: core::array::from_fn(|_| copy_manager.mock_external_assigned(F::ZERO)),
+                is_final: SafeTypeChip::unsafe_to_bool(
+                    copy_manager.mock_external_assigned(F::ZERO),
+                ),
+                hash_lo: copy_manager.mock_external_assigned(F::ZERO),
+                hash_hi: copy_manager.mock_external_assigned(F::ZERO),
+            })
+            .collect_vec()
+    }
+
+    /// Load needed witnesses into halo2-lib from SHA-256 assigned blocks. This function doesn't create any witnesses/constraints.
+    fn load_sha256_assigned_blocks(
+        &self,
+        assigned_blocks: Vec<AssignedSha256Block<'_, F>>,
+    ) -> Vec<LoadedSha256Block<F>> {
+        let base_circuit_builder = self.base_circuit_builder.borrow();
+        transmute_sha256_assigned_to_virtual(
+            &base_circuit_builder.core().copy_manager,
+            assigned_blocks,
+        )
+    }
+
+    /// Generate witnesses of the base circuit.
+    fn generate_base_circuit_witnesses(&self, loaded_sha256_blocks: &[LoadedSha256Block<F>]) {
+        let gate = &self.gate_chip;
+        let circuit_final_outputs = {
+            let mut base_circuit_builder_mut = self.base_circuit_builder.borrow_mut();
+            let ctx = base_circuit_builder_mut.main(0);
+            let mut hasher = self.hasher.borrow_mut();
+            hasher.initialize_consts(ctx, gate);
+
+            let lookup_key_per_block =
+                encode_inputs_from_sha256_blocks(ctx, gate, &hasher, loaded_sha256_blocks);
+            Self::generate_circuit_final_outputs(
+                ctx,
+                gate,
+                &lookup_key_per_block,
+                loaded_sha256_blocks,
+            )
+        };
+        self.publish_outputs(&circuit_final_outputs);
+    }
+
+    /// Combine lookup keys and SHA-256 results to generate final outputs of the circuit.
+    pub fn generate_circuit_final_outputs(
+        ctx: &mut Context<F>,
+        gate: &impl GateInstructions<F>,
+        lookup_key_per_block: &[PoseidonCompactOutput<F>],
+        loaded_sha256_blocks: &[LoadedSha256Block<F>],
+    ) -> Vec<Sha256CircuitOutput<AssignedValue<F>>> {
+        let Sha256CircuitOutput {
+            key: dummy_key_val,
+            hash_lo: dummy_sha256_val_lo,
+            hash_hi: dummy_sha256_val_hi,
+        } = dummy_circuit_output::<F>();
+
+        // Dummy row for blocks with is_final = false. The corresponding logical input is empty.
+        let dummy_key_witness = ctx.load_constant(dummy_key_val);
+        let dummy_sha256_lo_witness = ctx.load_constant(dummy_sha256_val_lo);
+        let dummy_sha256_hi_witness = ctx.load_constant(dummy_sha256_val_hi);
+
+        let mut circuit_final_outputs = Vec::with_capacity(loaded_sha256_blocks.len());
+        for (compact_output, loaded_sha256_block) in
+            lookup_key_per_block.iter().zip_eq(loaded_sha256_blocks)
+        {
+            let is_final = AssignedValue::from(loaded_sha256_block.is_final);
+            let key = gate.select(ctx, compact_output.hash(), dummy_key_witness, is_final);
+            let hash_lo =
+                gate.select(ctx, loaded_sha256_block.hash_lo, dummy_sha256_lo_witness, is_final);
+            let hash_hi =
+                gate.select(ctx, loaded_sha256_block.hash_hi, dummy_sha256_hi_witness, is_final);
+            circuit_final_outputs.push(Sha256CircuitOutput { key, hash_lo, hash_hi });
+        }
+        circuit_final_outputs
+    }
+
+    /// Publish outputs of the circuit as public instances.
+    fn publish_outputs(&self, outputs: &[Sha256CircuitOutput<AssignedValue<F>>]) {
+        // The length of outputs should always equal to params.capacity.
+        assert_eq!(outputs.len(), self.params.capacity);
+        if !self.params.publish_raw_outputs {
+            let gate = &self.gate_chip;
+            let mut base_circuit_builder_mut = self.base_circuit_builder.borrow_mut();
+            let ctx = base_circuit_builder_mut.main(0);
+
+            let output_commitment = self.hasher.borrow().hash_fix_len_array(
+                ctx,
+                gate,
+                &outputs
+                    .iter()
+                    .flat_map(|output| [output.key, output.hash_lo, output.hash_hi])
+                    .collect_vec(),
+            );
+
+            let assigned_instances = &mut base_circuit_builder_mut.assigned_instances;
+            // The commitment should be in the first row.
+            assert!(assigned_instances[OUTPUT_COL_IDX_COMMIT].is_empty());
+            assigned_instances[OUTPUT_COL_IDX_COMMIT].push(output_commitment);
+        } else {
+            let assigned_instances = &mut self.base_circuit_builder.borrow_mut().assigned_instances;
+
+            // Outputs should be in the top of instance columns.
+            assert!(assigned_instances[OUTPUT_COL_IDX_KEY].is_empty());
+            assert!(assigned_instances[OUTPUT_COL_IDX_HASH_LO].is_empty());
+            assert!(assigned_instances[OUTPUT_COL_IDX_HASH_HI].is_empty());
+            for output in outputs {
+                assigned_instances[OUTPUT_COL_IDX_KEY].push(output.key);
+                assigned_instances[OUTPUT_COL_IDX_HASH_LO].push(output.hash_lo);
+                assigned_instances[OUTPUT_COL_IDX_HASH_HI].push(output.hash_hi);
+            }
+        }
+    }
+}
+
+/// Packs raw inputs from SHA-256 circuit witnesses into fewer field elements for the purpose of creating lookup keys.
+///
+/// Each element in the return value corresponds to a SHA-256 block. If is_final = true, this element is the lookup key of the corresponding logical input.
+pub fn pack_inputs_from_sha256_blocks<F: Field>(
+    ctx: &mut Context<F>,
+    gate: &impl GateInstructions<F>,
+    loaded_sha256_blocks: &[LoadedSha256Block<F>],
+) -> Vec<PoseidonCompactChunkInput<F, POSEIDON_RATE>> {
+    // Circuit parameters
+    let num_poseidon_absorb_per_block = num_poseidon_absorb_per_block::<F>();
+    let num_word_per_witness = num_word_per_witness::<F>();
+    let num_witness_per_block = POSEIDON_RATE * num_poseidon_absorb_per_block;
+
+    // Constant witnesses
+    let zero_const = ctx.load_zero();
+    let multipliers_val =
+        get_words_to_witness_multipliers::<F>().into_iter().map(Constant).collect_vec();
+
+    loaded_sha256_blocks
+        .iter()
+        .map(|loaded_sha256_block| {
+            // Words of a block: [<length>, word_values[0], word_values[1], ...]
+            let mut words = Vec::with_capacity(NUM_WORDS_TO_ABSORB + 1);
+            words.push(loaded_sha256_block.length);
+            words.extend_from_slice(&loaded_sha256_block.word_values);
+
+            // Turn every num_word_per_witness words into a witness.
+            let mut poseidon_absorb_data = words
+                .chunks(num_word_per_witness)
+                .map(|words| {
+                    let mut words = words.to_vec();
+                    words.resize(num_word_per_witness, zero_const);
+                    gate.inner_product(ctx, words, multipliers_val.clone())
+                })
+                .collect_vec();
+            // Pad 0s to make sure poseidon_absorb_data.len() % RATE == 0.
+            poseidon_absorb_data.resize(num_witness_per_block, zero_const);
+            let compact_inputs = poseidon_absorb_data
+                .chunks_exact(POSEIDON_RATE)
+                .map(|chunk| chunk.to_vec().try_into().unwrap())
+                .collect_vec();
+            debug_assert_eq!(compact_inputs.len(), num_poseidon_absorb_per_block);
+            PoseidonCompactChunkInput::new(compact_inputs, loaded_sha256_block.is_final)
+        })
+        .collect()
+}
+
+/// Encode raw inputs from SHA-256 circuit witnesses into lookup keys.
+///
+/// Each element in the return value corresponds to a SHA-256 block. If is_final = true, this element is the lookup key of the corresponding logical input.
+pub fn encode_inputs_from_sha256_blocks<F: Field>(
+    ctx: &mut Context<F>,
+    gate: &impl GateInstructions<F>,
+    initialized_hasher: &PoseidonHasher<F, POSEIDON_T, POSEIDON_RATE>,
+    loaded_sha256_blocks: &[LoadedSha256Block<F>],
+) -> Vec<PoseidonCompactOutput<F>> {
+    let compact_chunk_inputs = pack_inputs_from_sha256_blocks(ctx, gate, loaded_sha256_blocks);
+    initialized_hasher.hash_compact_chunk_inputs(ctx, gate, &compact_chunk_inputs)
+}
+
+/// Converts the pertinent raw assigned cells of the SHA-256 blocks into virtual `halo2-lib` cells so they can be used
+/// by [halo2_base]. This function doesn't create any new witnesses/constraints.
+///
+/// ## Assumptions
+/// - `assigned_blocks` **must** be all the blocks returned by [Sha256CircuitConfig::multi_sha256], in order.
+pub fn transmute_sha256_assigned_to_virtual<F: Field>(
+    copy_manager: &SharedCopyConstraintManager<F>,
+    assigned_blocks: Vec<AssignedSha256Block<'_, F>>,
+) -> Vec<LoadedSha256Block<F>> {
+    let mut copy_manager = copy_manager.lock().unwrap();
+    assigned_blocks
+        .into_iter()
+        .map(|block| {
+            let length = copy_manager.load_external_assigned(block.length);
+            let word_values =
+                block.word_values.map(|word| copy_manager.load_external_assigned(word));
+            let is_final =
+                SafeTypeChip::unsafe_to_bool(copy_manager.load_external_assigned(block.is_final));
+            let hash_lo = copy_manager.load_external_assigned(block.output.lo());
+            let hash_hi = copy_manager.load_external_assigned(block.output.hi());
+            LoadedSha256Block { length, word_values, is_final, hash_lo, hash_hi }
+        })
+        .collect()
+}
+
+impl<F: Field> CircuitExt<F> for Sha256ComponentShardCircuit<F> {
+    fn instances(&self) -> Vec<Vec<F>> {
+        let circuit_outputs =
+            multi_inputs_to_circuit_outputs(&self.inputs.borrow(), self.params.capacity);
+        if self.params.publish_raw_outputs {
+            vec![
+                circuit_outputs.iter().map(|o| o.key).collect(),
+                circuit_outputs.iter().map(|o| o.hash_lo).collect(),
+                circuit_outputs.iter().map(|o| o.hash_hi).collect(),
+            ]
+        } else {
+            vec![vec![calculate_circuit_outputs_commit(&circuit_outputs)]]
+        }
+    }
+
+    fn num_instance(&self) -> Vec<usize> {
+        if self.params.publish_raw_outputs {
+            vec![self.params.capacity; OUTPUT_NUM_COL_RAW]
+        } else {
+            vec![1; OUTPUT_NUM_COL_COMMIT]
+        }
+    }
+
+    fn accumulator_indices() -> Option<Vec<(usize, usize)>> {
+        None
+    }
+
+    fn selectors(config: &Self::Config) -> Vec<halo2_base::halo2_proofs::plonk::Selector> {
+        // the vanilla SHA-256 circuit does not use selectors
+        // this is from the BaseCircuitBuilder
+        config.base_circuit_config.gate().basic_gates[0]
+            .iter()
+            .map(|basic| basic.q_enable)
+            .collect()
+    }
+}
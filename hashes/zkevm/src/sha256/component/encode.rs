@@ -0,0 +1,238 @@
+use halo2_base::{
+    gates::{GateInstructions, RangeInstructions},
+    poseidon::hasher::{PoseidonCompactChunkInput, PoseidonHasher},
+    safe_types::{FixLenBytesVec, SafeByte, SafeTypeChip, VarLenBytesVec},
+    utils::bit_length,
+    AssignedValue, Context,
+    QuantumCell::Constant,
+};
+use itertools::Itertools;
+use num_bigint::BigUint;
+
+use crate::{
+    keccak::component::create_native_poseidon_sponge,
+    sha256::vanilla::{
+        param::{
+            NUM_BITS_PER_WORD, NUM_BYTES_PADDING_LENGTH, NUM_BYTES_PER_WORD, NUM_WORDS_TO_ABSORB,
+            RATE,
+        },
+        util::get_num_sha2_blocks,
+    },
+    util::eth_types::Field,
+};
+
+use super::param::*;
+
+/// Module to encode raw inputs into lookup keys for looking up SHA-256 results.
+///
+/// Every SHA-256 block of an input contributes `[length, w_0, ..., w_15]`, where `length` is the number of input
+/// bytes up to the end of the block and `w_j` are the input bytes of the block as little-endian u32 words, with the
+/// SHA-256 padding replaced by zeros. These are the `length` and `word_values` cells of a block of the vanilla
+/// circuit, so the component circuit computes keys without any byte decomposition.
+
+/// Encode a native input bytes into its corresponding lookup key. This function can be considered as the spec of the encoding.
+pub fn encode_native_input<F: Field>(bytes: &[u8]) -> F {
+    let witnesses_per_block = pack_native_input(bytes);
+    // Absorb witnesses block by block.
+    let mut native_poseidon_sponge = create_native_poseidon_sponge();
+    for witnesses in witnesses_per_block {
+        for absorbing in witnesses.chunks(POSEIDON_RATE) {
+            native_poseidon_sponge.update(absorbing);
+        }
+    }
+    native_poseidon_sponge.squeeze()
+}
+
+/// Pack native input bytes into num_word_per_witness field elements per block which are more poseidon friendly.
+pub fn pack_native_input<F: Field>(bytes: &[u8]) -> Vec<Vec<F>> {
+    let multipliers = get_words_to_witness_multipliers::<F>();
+    let num_word_per_witness = num_word_per_witness::<F>();
+    let num_witness_per_block = num_witness_per_block::<F>();
+    let len = bytes.len();
+
+    let mut padded_bytes = bytes.to_vec();
+    padded_bytes.resize(get_num_sha2_blocks(len) * RATE, 0);
+    padded_bytes
+        .chunks(RATE)
+        .enumerate()
+        .map(|(i, block)| {
+            let mut words = vec![len.min((i + 1) * RATE) as u64];
+            words.extend(
+                block
+                    .chunks(NUM_BYTES_PER_WORD)
+                    .map(|word| u32::from_le_bytes(word.try_into().unwrap()) as u64),
+            );
+            let mut witnesses = words
+                .chunks(num_word_per_witness)
+                .map(|c| {
+                    c.iter()
+                        .zip(multipliers.iter())
+                        .fold(F::ZERO, |acc, (word, multiplier)| acc + F::from(*word) * multiplier)
+                })
+                .collect_vec();
+            // Pad 0s so that witnesses of different blocks are never absorbed together.
+            witnesses.resize(num_witness_per_block, F::ZERO);
+            witnesses
+        })
+        .collect()
+}
+
+/// Encode a VarLenBytesVec into its corresponding lookup key.
+pub fn encode_var_len_bytes_vec<F: Field>(
+    ctx: &mut Context<F>,
+    range_chip: &impl RangeInstructions<F>,
+    initialized_hasher: &PoseidonHasher<F, POSEIDON_T, POSEIDON_RATE>,
+    bytes: &VarLenBytesVec<F>,
+) -> AssignedValue<F> {
+    let gate = range_chip.gate();
+    let max_num_blocks = get_num_sha2_blocks(bytes.max_len());
+    let len = *bytes.len();
+    let num_bits = bit_length((max_num_blocks * RATE) as u64);
+    let block_indicator = last_block_indicator(ctx, range_chip, len, bytes.max_len());
+
+    // length up to the end of the i-th block is min(len, (i + 1) * RATE)
+    let block_lengths = (1..=max_num_blocks)
+        .map(|i| {
+            let end = Constant(F::from((i * RATE) as u64));
+            let is_lt = range_chip.is_less_than(ctx, len, end, num_bits);
+            gate.select(ctx, len, end, is_lt)
+        })
+        .collect_vec();
+
+    let bytes = bytes.ensure_0_padding(ctx, gate);
+    let chunk_input_per_block = format_input(ctx, gate, bytes.bytes(), &block_lengths);
+
+    let chunk_inputs = chunk_input_per_block
+        .into_iter()
+        .zip(&block_indicator)
+        .map(|(chunk_input, is_final)| {
+            let is_final = SafeTypeChip::unsafe_to_bool(*is_final);
+            PoseidonCompactChunkInput::new(chunk_input, is_final)
+        })
+        .collect_vec();
+
+    let compact_outputs = initialized_hasher.hash_compact_chunk_inputs(ctx, gate, &chunk_inputs);
+    gate.select_by_indicator(ctx, compact_outputs.into_iter().map(|o| o.hash()), block_indicator)
+}
+
+/// Encode a FixLenBytesVec into its corresponding lookup key.
+pub fn encode_fix_len_bytes_vec<F: Field>(
+    ctx: &mut Context<F>,
+    gate_chip: &impl GateInstructions<F>,
+    initialized_hasher: &PoseidonHasher<F, POSEIDON_T, POSEIDON_RATE>,
+    bytes: &FixLenBytesVec<F>,
+) -> AssignedValue<F> {
+    let len = bytes.len();
+    // Constant witnesses
+    let block_lengths = (1..=get_num_sha2_blocks(len))
+        .map(|i| ctx.load_constant(F::from(len.min(i * RATE) as u64)))
+        .collect_vec();
+
+    let chunk_input_per_block = format_input(ctx, gate_chip, bytes.bytes(), &block_lengths);
+    let flatten_inputs = chunk_input_per_block
+        .into_iter()
+        .flat_map(|chunk_input| chunk_input.into_iter().flatten())
+        .collect_vec();
+
+    initialized_hasher.hash_fix_len_array(ctx, gate_chip, &flatten_inputs)
+}
+
+/// Indicator of the last SHA-256 block of an input of length `len`, among the blocks of an input of length `max_len`.
+pub fn last_block_indicator<F: Field>(
+    ctx: &mut Context<F>,
+    range_chip: &impl RangeInstructions<F>,
+    len: AssignedValue<F>,
+    max_len: usize,
+) -> Vec<AssignedValue<F>> {
+    let gate = range_chip.gate();
+    let max_num_blocks = get_num_sha2_blocks(max_len);
+    // num_blocks - 1 = (len + NUM_BYTES_PADDING_LENGTH) / RATE
+    let num_bits = bit_length((max_num_blocks * RATE) as u64);
+    let padded_len = gate.add(ctx, len, Constant(F::from(NUM_BYTES_PADDING_LENGTH as u64)));
+    let (last_block, _) = range_chip.div_mod(ctx, padded_len, BigUint::from(RATE), num_bits);
+    gate.idx_to_indicator(ctx, last_block, max_num_blocks)
+}
+
+// For reference, when F is bn254::Fr:
+// num_word_per_witness = 7
+// num_witness_per_block = 4
+// num_poseidon_absorb_per_block = 2
+
+/// Number of SHA-256 words in each encoded input for Poseidon.
+/// When `F` is `bn254::Fr`, this is 7.
+pub const fn num_word_per_witness<F: Field>() -> usize {
+    (F::CAPACITY as usize) / NUM_BITS_PER_WORD
+}
+
+/// Number of witnesses to represent a block, padded to a multiple of `POSEIDON_RATE`.
+///
+/// When `F` is `bn254::Fr`, this is 4.
+pub const fn num_witness_per_block<F: Field>() -> usize {
+    POSEIDON_RATE * num_poseidon_absorb_per_block::<F>()
+}
+
+/// Number of Poseidon absorb rounds per block.
+///
+/// When `F` is `bn254::Fr`, with our fixed `POSEIDON_RATE = 2`, this is 2.
+pub const fn num_poseidon_absorb_per_block<F: Field>() -> usize {
+    // With <length>, a block has NUM_WORDS_TO_ABSORB + 1 words.
+    // ceil(ceil((NUM_WORDS_TO_ABSORB + 1) / num_word_per_witness) / RATE)
+    let num_witness = NUM_WORDS_TO_ABSORB / num_word_per_witness::<F>() + 1;
+    (num_witness - 1) / POSEIDON_RATE + 1
+}
+
+pub(crate) fn get_words_to_witness_multipliers<F: Field>() -> Vec<F> {
+    let base_f = F::from(1u64 << NUM_BITS_PER_WORD);
+    let mut multiplier_f = F::ONE;
+    (0..num_word_per_witness::<F>())
+        .map(|_| {
+            let multiplier = multiplier_f;
+            multiplier_f *= base_f;
+            multiplier
+        })
+        .collect()
+}
+
+/// Packs the bytes of each block, right padded with 0s to whole blocks, together with the number of input bytes up
+/// to the end of the block given by `block_lengths`, into the Poseidon inputs of the block.
+pub fn format_input<F: Field>(
+    ctx: &mut Context<F>,
+    gate: &impl GateInstructions<F>,
+    bytes: &[SafeByte<F>],
+    block_lengths: &[AssignedValue<F>],
+) -> Vec<Vec<[AssignedValue<F>; POSEIDON_RATE]>> {
+    assert!(bytes.len() <= block_lengths.len() * RATE, "bytes do not fit in the blocks");
+    // Constant witnesses
+    let zero_const = ctx.load_zero();
+    let bytes_to_words_multipliers_val =
+        (0..NUM_BYTES_PER_WORD).map(|i| Constant(F::from(1u64 << (8 * i)))).collect_vec();
+    let words_to_witness_multipliers_val =
+        get_words_to_witness_multipliers::<F>().into_iter().map(Constant).collect_vec();
+
+    let mut bytes_witnesses = bytes.iter().map(|b| *b.as_ref()).collect_vec();
+    bytes_witnesses.resize(block_lengths.len() * RATE, zero_const);
+    let words = bytes_witnesses
+        .chunks(NUM_BYTES_PER_WORD)
+        .map(|c| gate.inner_product(ctx, c.to_vec(), bytes_to_words_multipliers_val.clone()))
+        .collect_vec();
+
+    words
+        .chunks(NUM_WORDS_TO_ABSORB)
+        .zip(block_lengths)
+        .map(|(words, length)| {
+            let words = [&[*length][..], words].concat();
+            let mut witnesses = words
+                .chunks(num_word_per_witness::<F>())
+                .map(|c| {
+                    gate.inner_product(
+                        ctx,
+                        c.to_vec(),
+                        words_to_witness_multipliers_val[..c.len()].to_vec(),
+                    )
+                })
+                .collect_vec();
+            witnesses.resize(num_witness_per_block::<F>(), zero_const);
+            witnesses.chunks(POSEIDON_RATE).map(|c| c.try_into().unwrap()).collect_vec()
+        })
+        .collect()
+}
@@ -0,0 +1,19 @@
+//! SHA-256 component circuit, the counterpart of [crate::keccak::component] for SHA-256.
+//!
+//! The component circuit computes SHA-256 of many inputs with the vanilla circuit and outputs
+//! `(key, hash_lo, hash_hi)` for every SHA-256 block, where `key` is the Poseidon encoding of the input.
+//! App circuits witness digests, record them as promises in the same format and check the Poseidon commitment
+//! of their promises against the one published by a component circuit.
+
+/// Module of SHA-256 component circuit(s).
+pub mod circuit;
+/// Module of encoding raw inputs to component circuit lookup keys.
+pub mod encode;
+/// Module of SHA-256 component circuit output.
+pub mod output;
+/// Module of SHA-256 component circuit constant parameters.
+pub mod param;
+/// Module of the chip recording SHA-256 promises in App circuits.
+pub mod promise;
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,103 @@
+use std::sync::RwLock;
+
+use super::encode::encode_native_input;
+use crate::{
+    keccak::component::create_native_poseidon_sponge, sha256::vanilla::util::get_num_sha2_blocks,
+    util::eth_types::Field,
+};
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use sha2::{Digest, Sha256};
+use type_map::concurrent::TypeMap;
+
+/// Witnesses to be exposed as circuit outputs.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Sha256CircuitOutput<E> {
+    /// Key for App circuits to lookup SHA-256 hash.
+    pub key: E,
+    /// Low 128 bits of SHA-256 hash.
+    pub hash_lo: E,
+    /// High 128 bits of SHA-256 hash.
+    pub hash_hi: E,
+}
+
+/// Return circuit outputs of the specified SHA-256 component circuit for a specified input.
+pub fn multi_inputs_to_circuit_outputs<F: Field>(
+    inputs: &[Vec<u8>],
+    capacity: usize,
+) -> Vec<Sha256CircuitOutput<F>> {
+    assert!(u128::BITS <= F::CAPACITY);
+    let mut outputs: Vec<_> =
+        inputs.par_iter().flat_map(|input| input_to_circuit_outputs::<F>(input)).collect();
+    assert!(outputs.len() <= capacity);
+    outputs.resize(capacity, dummy_circuit_output());
+    outputs
+}
+
+/// Return corresponding circuit outputs of a native input in bytes. An logical input could produce multiple
+/// outputs. The last one is the lookup key and hash of the input. Other outputs are paddings which are the lookup
+/// key and hash of an empty input.
+pub fn input_to_circuit_outputs<F: Field>(bytes: &[u8]) -> Vec<Sha256CircuitOutput<F>> {
+    assert!(u128::BITS <= F::CAPACITY);
+    let num_blocks = get_num_sha2_blocks(bytes.len());
+
+    let mut output = Vec::with_capacity(num_blocks);
+    output.resize(num_blocks - 1, dummy_circuit_output());
+
+    let key = encode_native_input(bytes);
+    let (hash_hi, hash_lo) = hash_to_hi_lo(bytes);
+    output.push(Sha256CircuitOutput { key, hash_lo, hash_hi });
+
+    output
+}
+
+lazy_static! {
+    static ref DUMMY_CIRCUIT_OUTPUT_CACHE: RwLock<TypeMap> = Default::default();
+}
+
+/// Return the dummy circuit output for padding.
+pub fn dummy_circuit_output<F: Field>() -> Sha256CircuitOutput<F> {
+    let output = DUMMY_CIRCUIT_OUTPUT_CACHE
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get::<Sha256CircuitOutput<F>>()
+        .cloned();
+    if let Some(output) = output {
+        return output;
+    }
+    let output = {
+        let mut to_write = DUMMY_CIRCUIT_OUTPUT_CACHE.write().unwrap_or_else(|e| e.into_inner());
+        let output = dummy_circuit_output_impl();
+        to_write.insert(output);
+        output
+    };
+    output
+}
+
+fn dummy_circuit_output_impl<F: Field>() -> Sha256CircuitOutput<F> {
+    assert!(u128::BITS <= F::CAPACITY);
+    let key = encode_native_input(&[]);
+    let (hash_hi, hash_lo) = hash_to_hi_lo(&[]);
+    Sha256CircuitOutput { key, hash_lo, hash_hi }
+}
+
+// Output of Sha256::digest is big endian.
+fn hash_to_hi_lo<F: Field>(bytes: &[u8]) -> (F, F) {
+    let hash = Sha256::digest(bytes);
+    let hash_hi = F::from_u128(u128::from_be_bytes(hash[..16].try_into().unwrap()));
+    let hash_lo = F::from_u128(u128::from_be_bytes(hash[16..].try_into().unwrap()));
+    (hash_hi, hash_lo)
+}
+
+/// Calculate the commitment of circuit outputs.
+pub fn calculate_circuit_outputs_commit<F: Field>(outputs: &[Sha256CircuitOutput<F>]) -> F {
+    let mut native_poseidon_sponge = create_native_poseidon_sponge();
+    native_poseidon_sponge.update(
+        &outputs
+            .iter()
+            .flat_map(|output| [output.key, output.hash_lo, output.hash_hi])
+            .collect_vec(),
+    );
+    native_poseidon_sponge.squeeze()
+}
@@ -0,0 +1,3 @@
+//! The SHA-256 component circuit publishes its outputs in the same layout and with the same Poseidon
+//! parameters as the Keccak component circuit.
+pub use crate::keccak::component::param::*;
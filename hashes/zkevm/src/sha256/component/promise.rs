@@ -0,0 +1,172 @@
+use std::cell::RefCell;
+
+use halo2_base::{
+    gates::{GateInstructions, RangeChip, RangeInstructions},
+    poseidon::hasher::PoseidonHasher,
+    safe_types::{FixLenBytes, FixLenBytesVec, SafeTypeChip, VarLenBytesVec},
+    AssignedValue, Context,
+    QuantumCell::Constant,
+};
+use itertools::Itertools;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    sha256::vanilla::{param::NUM_BYTES_TO_SQUEEZE, util::get_num_sha2_blocks},
+    util::eth_types::Field,
+};
+
+use super::{
+    encode::{encode_fix_len_bytes_vec, encode_var_len_bytes_vec, last_block_indicator},
+    output::{dummy_circuit_output, Sha256CircuitOutput},
+    param::{POSEIDON_RATE, POSEIDON_T},
+};
+
+/// A SHA-256 digest.
+pub type Sha256Digest<F> = FixLenBytes<F, NUM_BYTES_TO_SQUEEZE>;
+
+/// Chip recording SHA-256 results as promises to be checked against a SHA-256 component circuit.
+///
+/// SHA-256 is not computed in this circuit: every digest is either witnessed or given by the caller, and recorded
+/// as a promise `(key, hash_lo, hash_hi)` in the output format of the SHA-256 component circuit, where `key` is the
+/// lookup key of the preimage. The caller must check [Self::promise_commit] against the output commitment published
+/// by a SHA-256 component circuit.
+#[derive(Debug)]
+pub struct Sha256PromiseChip<'a, F: Field> {
+    range: &'a RangeChip<F>,
+    initialized_hasher: &'a PoseidonHasher<F, POSEIDON_T, POSEIDON_RATE>,
+    promises: RefCell<Vec<Sha256CircuitOutput<AssignedValue<F>>>>,
+}
+
+impl<'a, F: Field> Sha256PromiseChip<'a, F> {
+    /// Creates a chip from a range chip and a Poseidon hasher with initialized constants.
+    pub fn new(
+        range: &'a RangeChip<F>,
+        initialized_hasher: &'a PoseidonHasher<F, POSEIDON_T, POSEIDON_RATE>,
+    ) -> Self {
+        Self { range, initialized_hasher, promises: RefCell::new(vec![]) }
+    }
+
+    /// The range chip of this chip.
+    pub fn range(&self) -> &'a RangeChip<F> {
+        self.range
+    }
+
+    /// SHA-256 promises recorded so far, in the order of the hashes.
+    pub fn promises(&self) -> Vec<Sha256CircuitOutput<AssignedValue<F>>> {
+        self.promises.borrow().clone()
+    }
+
+    /// SHA-256 of fixed length bytes. The digest is witnessed and a promise is recorded for it.
+    pub fn sha256_fix_len(
+        &self,
+        ctx: &mut Context<F>,
+        bytes: &FixLenBytesVec<F>,
+    ) -> Sha256Digest<F> {
+        let preimage =
+            bytes.bytes().iter().map(|b| b.value().get_lower_32() as u8).collect::<Vec<_>>();
+        let digest = self.load_digest(ctx, &preimage);
+        self.promise_fix_len(ctx, bytes, &digest);
+        digest
+    }
+
+    /// SHA-256 of variable length bytes. The digest is witnessed and a promise is recorded for it.
+    pub fn sha256_var_len(
+        &self,
+        ctx: &mut Context<F>,
+        bytes: &VarLenBytesVec<F>,
+    ) -> Sha256Digest<F> {
+        let len = bytes.len().value().get_lower_64() as usize;
+        let preimage =
+            bytes.bytes()[..len].iter().map(|b| b.value().get_lower_32() as u8).collect::<Vec<_>>();
+        let digest = self.load_digest(ctx, &preimage);
+        self.promise_var_len(ctx, bytes, &digest);
+        digest
+    }
+
+    // Witnesses the SHA-256 digest of `preimage` as bytes
+    fn load_digest(&self, ctx: &mut Context<F>, preimage: &[u8]) -> Sha256Digest<F> {
+        let digest = Sha256::digest(preimage);
+        let digest = ctx.assign_witnesses(digest.iter().map(|b| F::from(*b as u64)));
+        SafeTypeChip::new(self.range)
+            .raw_to_fix_len_bytes::<NUM_BYTES_TO_SQUEEZE>(ctx, digest.try_into().unwrap())
+    }
+
+    /// Records the promise `sha256(bytes) == digest`.
+    pub fn promise_fix_len(
+        &self,
+        ctx: &mut Context<F>,
+        bytes: &FixLenBytesVec<F>,
+        digest: &Sha256Digest<F>,
+    ) {
+        let key = encode_fix_len_bytes_vec(ctx, self.range.gate(), self.initialized_hasher, bytes);
+        let (hash_hi, hash_lo) = self.to_hi_lo(ctx, digest);
+        // The component circuit outputs one dummy result for every block but the last one of an input.
+        let dummy = dummy_circuit_output::<F>();
+        let mut promises = self.promises.borrow_mut();
+        for _ in 1..get_num_sha2_blocks(bytes.len()) {
+            promises.push(Sha256CircuitOutput {
+                key: ctx.load_constant(dummy.key),
+                hash_lo: ctx.load_constant(dummy.hash_lo),
+                hash_hi: ctx.load_constant(dummy.hash_hi),
+            });
+        }
+        promises.push(Sha256CircuitOutput { key, hash_lo, hash_hi });
+    }
+
+    /// Records the promise `sha256(bytes) == digest`.
+    ///
+    /// The number of promises only depends on `bytes.max_len()`: the result of the input is at the position of its
+    /// last block and every other position holds the dummy output. The component circuit must hash an empty input
+    /// after the input for each of the `get_num_sha2_blocks(max_len) - get_num_sha2_blocks(len)` trailing positions.
+    pub fn promise_var_len(
+        &self,
+        ctx: &mut Context<F>,
+        bytes: &VarLenBytesVec<F>,
+        digest: &Sha256Digest<F>,
+    ) {
+        let gate = self.range.gate();
+        let key = encode_var_len_bytes_vec(ctx, self.range, self.initialized_hasher, bytes);
+        let (hash_hi, hash_lo) = self.to_hi_lo(ctx, digest);
+
+        let block_indicator = last_block_indicator(ctx, self.range, *bytes.len(), bytes.max_len());
+
+        let dummy = dummy_circuit_output::<F>();
+        let mut promises = self.promises.borrow_mut();
+        for is_last in block_indicator {
+            let [key, hash_lo, hash_hi] =
+                [(key, dummy.key), (hash_lo, dummy.hash_lo), (hash_hi, dummy.hash_hi)]
+                    .map(|(value, dummy)| gate.select(ctx, value, Constant(dummy), is_last));
+            promises.push(Sha256CircuitOutput { key, hash_lo, hash_hi });
+        }
+    }
+
+    /// Splits 32 bytes into their big-endian (hi, lo) 128-bit limbs, as in the component circuit outputs.
+    pub fn to_hi_lo(
+        &self,
+        ctx: &mut Context<F>,
+        digest: &Sha256Digest<F>,
+    ) -> (AssignedValue<F>, AssignedValue<F>) {
+        let gate = self.range.gate();
+        let multipliers = (0..16).rev().map(|i| Constant(F::from_u128(1u128 << (8 * i))));
+        let hi = gate.inner_product(ctx, digest.bytes()[..16].iter().copied(), multipliers.clone());
+        let lo = gate.inner_product(ctx, digest.bytes()[16..].iter().copied(), multipliers);
+        (hi, lo)
+    }
+
+    /// Poseidon commitment of the promises padded with dummy outputs to `capacity`, computed as the output
+    /// commitment of a SHA-256 component circuit with that capacity. The promises are sound once this equals
+    /// the commitment published by such a circuit.
+    pub fn promise_commit(&self, ctx: &mut Context<F>, capacity: usize) -> AssignedValue<F> {
+        let promises = self.promises.borrow();
+        assert!(promises.len() <= capacity, "more sha256 promises than the capacity");
+        let dummy = dummy_circuit_output::<F>();
+        let mut inputs = promises
+            .iter()
+            .flat_map(|promise| [promise.key, promise.hash_lo, promise.hash_hi])
+            .collect_vec();
+        for _ in promises.len()..capacity {
+            inputs.extend([dummy.key, dummy.hash_lo, dummy.hash_hi].map(|x| ctx.load_constant(x)));
+        }
+        self.initialized_hasher.hash_fix_len_array(ctx, self.range.gate(), &inputs)
+    }
+}
@@ -0,0 +1,6 @@
+#[cfg(test)]
+mod encode;
+#[cfg(test)]
+mod output;
+#[cfg(test)]
+mod promise;
@@ -0,0 +1,75 @@
+use crate::sha256::component::{
+    encode::encode_native_input,
+    output::{
+        dummy_circuit_output, input_to_circuit_outputs, multi_inputs_to_circuit_outputs,
+        Sha256CircuitOutput,
+    },
+};
+use halo2_base::halo2_proofs::halo2curves::{bn256::Fr, ff::PrimeField};
+use itertools::Itertools;
+
+// SHA-256 of the empty input
+const EMPTY_HASH_HI: u128 = 0xe3b0c44298fc1c149afbf4c8996fb924;
+const EMPTY_HASH_LO: u128 = 0x27ae41e4649b934ca495991b7852b855;
+// SHA-256 of "abc"
+const ABC_HASH_HI: u128 = 0xba7816bf8f01cfea414140de5dae2223;
+const ABC_HASH_LO: u128 = 0xb00361a396177a9cb410ff61f20015ad;
+
+fn output_empty() -> Sha256CircuitOutput<Fr> {
+    Sha256CircuitOutput {
+        key: encode_native_input(&[]),
+        hash_lo: Fr::from_u128(EMPTY_HASH_LO),
+        hash_hi: Fr::from_u128(EMPTY_HASH_HI),
+    }
+}
+
+#[test]
+fn test_dummy_circuit_output() {
+    assert_eq!(dummy_circuit_output::<Fr>(), output_empty());
+}
+
+#[test]
+fn test_input_to_circuit_outputs_1_block() {
+    let result = input_to_circuit_outputs::<Fr>(b"abc");
+    assert_eq!(
+        result,
+        vec![Sha256CircuitOutput {
+            key: encode_native_input(b"abc"),
+            hash_lo: Fr::from_u128(ABC_HASH_LO),
+            hash_hi: Fr::from_u128(ABC_HASH_HI),
+        }]
+    );
+}
+
+#[test]
+fn test_input_to_circuit_outputs_num_blocks() {
+    // 55 bytes fit in one block with the padding, 56 bytes do not.
+    for (len, num_blocks) in [(0, 1), (55, 1), (56, 2), (64, 2), (119, 2), (120, 3)] {
+        let input = (0..len as u8).collect_vec();
+        let result = input_to_circuit_outputs::<Fr>(&input);
+        assert_eq!(result.len(), num_blocks);
+        assert!(result[..num_blocks - 1].iter().all(|output| *output == output_empty()));
+        assert_eq!(result.last().unwrap().key, encode_native_input(&input));
+    }
+}
+
+#[test]
+fn test_multi_input_to_circuit_outputs() {
+    let inputs = [(0..56).collect_vec(), vec![], b"abc".to_vec()];
+    let results = multi_inputs_to_circuit_outputs::<Fr>(&inputs, 6);
+    let expected = [
+        input_to_circuit_outputs::<Fr>(&inputs[0]),
+        input_to_circuit_outputs::<Fr>(&inputs[1]),
+        input_to_circuit_outputs::<Fr>(&inputs[2]),
+        // Padding
+        vec![output_empty(); 2],
+    ]
+    .concat();
+    assert_eq!(results, expected);
+}
+
+#[test]
+#[should_panic]
+fn test_multi_input_to_circuit_outputs_exceed_capacity() {
+    let _ = multi_inputs_to_circuit_outputs::<Fr>(&[(0..56).collect_vec(), vec![]], 2);
+}
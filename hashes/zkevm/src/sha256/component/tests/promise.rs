@@ -0,0 +1,70 @@
+use halo2_base::{
+    gates::{RangeChip, RangeInstructions},
+    halo2_proofs::halo2curves::bn256::Fr,
+    safe_types::SafeTypeChip,
+    utils::{testing::base_test, ScalarField},
+    Context,
+};
+use itertools::Itertools;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    keccak::component::circuit::shard::create_hasher,
+    sha256::component::{
+        output::{calculate_circuit_outputs_commit, multi_inputs_to_circuit_outputs},
+        promise::Sha256PromiseChip,
+    },
+};
+
+fn load_bytes(ctx: &mut Context<Fr>, bytes: &[u8]) -> Vec<halo2_base::AssignedValue<Fr>> {
+    ctx.assign_witnesses(bytes.iter().map(|b| Fr::from(*b as u64)))
+}
+
+#[test]
+fn mock_sha256_promises() {
+    let fix_len_inputs = [vec![], b"abc".to_vec(), (0u8..64).collect_vec()];
+    // (input, max_len)
+    let var_len_inputs = [(b"abc".to_vec(), 128), ((0u8..100).collect_vec(), 120)];
+    let capacity = 16;
+
+    base_test().k(18).lookup_bits(8).run(|ctx: &mut Context<Fr>, range: &RangeChip<Fr>| {
+        let mut hasher = create_hasher();
+        hasher.initialize_consts(ctx, range.gate());
+        let chip = Sha256PromiseChip::new(range, &hasher);
+        let safe = SafeTypeChip::new(range);
+
+        // The component circuit hashes an empty input for each trailing block of a variable length input.
+        let mut component_inputs = vec![];
+        for input in &fix_len_inputs {
+            let bytes = load_bytes(ctx, input);
+            let bytes = safe.raw_to_fix_len_bytes_vec(ctx, bytes, input.len());
+            let digest = chip.sha256_fix_len(ctx, &bytes);
+            let digest =
+                digest.bytes().iter().map(|b| b.value().get_lower_32() as u8).collect_vec();
+            assert_eq!(digest, Sha256::digest(input).to_vec());
+            component_inputs.push(input.clone());
+        }
+        for (input, max_len) in &var_len_inputs {
+            let mut padded = input.clone();
+            padded.resize(*max_len, 0);
+            let bytes = load_bytes(ctx, &padded);
+            let len = ctx.load_witness(Fr::from(input.len() as u64));
+            let bytes = safe.raw_to_var_len_bytes_vec(ctx, bytes, len, *max_len);
+            let digest = chip.sha256_var_len(ctx, &bytes);
+            let digest =
+                digest.bytes().iter().map(|b| b.value().get_lower_32() as u8).collect_vec();
+            assert_eq!(digest, Sha256::digest(input).to_vec());
+            component_inputs.push(input.clone());
+            let num_trailing = (max_len + 8) / 64 - (input.len() + 8) / 64;
+            component_inputs.extend(vec![vec![]; num_trailing]);
+        }
+
+        let expected_commit = calculate_circuit_outputs_commit(&multi_inputs_to_circuit_outputs::<
+            Fr,
+        >(
+            &component_inputs, capacity
+        ));
+        let commit = chip.promise_commit(ctx, capacity);
+        assert_eq!(*commit.value(), expected_commit);
+    });
+}
@@ -7,4 +7,8 @@
 //! Note: this circuit only supports SHA256 of a bit array of length up to 2^32 - 1, unlike the spec which supports up
 //! to 2^64 - 1.
 
+/// Module of SHA-256 component circuit(s) and of the chip recording SHA-256 promises in App circuits.
+pub mod component;
+/// Module of SSZ Merkleization with SHA-256 promises.
+pub mod ssz;
 pub mod vanilla;
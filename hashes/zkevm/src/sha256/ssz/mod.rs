@@ -0,0 +1,378 @@
+//! SSZ Merkleization with SHA-256 as in the Ethereum consensus specs: values are split into 32 byte chunks,
+//! the chunks are padded with zero chunks to a power of two and internal nodes are `sha256(left || right)`.
+//! A node of the tree is addressed by its generalized index: the root is 1 and the children of node `i` are
+//! `2i` and `2i + 1`.
+//!
+//! SHA-256 is not computed in this circuit: every internal node is recorded as a promise of a
+//! [Sha256PromiseChip], which must be checked against a SHA-256 component circuit.
+
+use halo2_base::{
+    gates::{GateInstructions, RangeChip, RangeInstructions},
+    safe_types::{FixLenBytes, FixLenBytesVec, SafeByte, SafeTypeChip},
+    AssignedValue, Context,
+    QuantumCell::Constant,
+};
+use itertools::Itertools;
+
+use crate::{sha256::component::promise::Sha256PromiseChip, util::eth_types::Field};
+
+#[cfg(test)]
+mod tests;
+
+/// Number of bytes of a chunk, and of every node of the tree.
+pub const NUM_BYTES_CHUNK: usize = 32;
+
+/// A chunk or node of an SSZ Merkle tree.
+pub type SszChunk<F> = FixLenBytes<F, NUM_BYTES_CHUNK>;
+
+/// Generalized index of the `index`-th chunk of a tree with `num_chunks` chunks before padding.
+pub fn chunk_gindex(num_chunks: usize, index: usize) -> u64 {
+    assert!(index < num_chunks, "chunk index out of range");
+    num_chunks.next_power_of_two() as u64 + index as u64
+}
+
+/// Generalized index of a node reached by following each generalized index from the root of the previous subtree,
+/// e.g. a field of a container nested in another container.
+pub fn concat_gindices(gindices: &[u64]) -> u64 {
+    gindices.iter().fold(1, |acc, gindex| {
+        assert!(*gindex > 0, "generalized indices start at 1");
+        let depth = 63 - gindex.leading_zeros();
+        (acc << depth) | (gindex - (1 << depth))
+    })
+}
+
+/// Chip computing and verifying SSZ hash tree roots and Merkle branches.
+#[derive(Debug)]
+pub struct SszChip<'a, F: Field> {
+    sha256_chip: &'a Sha256PromiseChip<'a, F>,
+}
+
+impl<'a, F: Field> SszChip<'a, F> {
+    /// Creates a chip recording its SHA-256 promises in `sha256_chip`.
+    pub fn new(sha256_chip: &'a Sha256PromiseChip<'a, F>) -> Self {
+        Self { sha256_chip }
+    }
+
+    /// The range chip of this chip.
+    pub fn range(&self) -> &'a RangeChip<F> {
+        self.sha256_chip.range()
+    }
+
+    /// Witnesses a chunk.
+    pub fn load_chunk(&self, ctx: &mut Context<F>, chunk: [u8; 32]) -> SszChunk<F> {
+        let bytes = ctx.assign_witnesses(chunk.map(|b| F::from(b as u64)));
+        SafeTypeChip::new(self.range()).raw_to_fix_len_bytes(ctx, bytes.try_into().unwrap())
+    }
+
+    /// Loads a constant chunk.
+    pub fn load_constant_chunk(&self, ctx: &mut Context<F>, chunk: [u8; 32]) -> SszChunk<F> {
+        SszChunk::new(
+            chunk.map(|b| SafeTypeChip::unsafe_to_byte(ctx.load_constant(F::from(b as u64)))),
+        )
+    }
+
+    /// `sha256(left || right)`
+    pub fn hash_pair(
+        &self,
+        ctx: &mut Context<F>,
+        left: &SszChunk<F>,
+        right: &SszChunk<F>,
+    ) -> SszChunk<F> {
+        let bytes = left.bytes().iter().chain(right.bytes().iter()).copied().collect_vec();
+        self.sha256_chip.sha256_fix_len(ctx, &FixLenBytesVec::new(bytes, 2 * NUM_BYTES_CHUNK))
+    }
+
+    /// Chunk of a uint64, serialized in little endian and right padded with zeros.
+    /// Also constrains `value` to 64 bits.
+    pub fn uint64_chunk(&self, ctx: &mut Context<F>, value: AssignedValue<F>) -> SszChunk<F> {
+        let mut bytes = self.range().decompose_le(ctx, value, 8, 8);
+        bytes.resize_with(NUM_BYTES_CHUNK, || ctx.load_zero());
+        SszChunk::new(
+            bytes.into_iter().map(SafeTypeChip::unsafe_to_byte).collect_vec().try_into().unwrap(),
+        )
+    }
+
+    /// Packs serialized basic values into chunks, right padding the last chunk with zeros.
+    pub fn pack(&self, ctx: &mut Context<F>, bytes: &[SafeByte<F>]) -> Vec<SszChunk<F>> {
+        bytes
+            .chunks(NUM_BYTES_CHUNK)
+            .map(|chunk| {
+                let mut chunk = chunk.to_vec();
+                chunk
+                    .resize_with(NUM_BYTES_CHUNK, || SafeTypeChip::unsafe_to_byte(ctx.load_zero()));
+                SszChunk::new(chunk.try_into().unwrap())
+            })
+            .collect()
+    }
+
+    /// Root of `chunks` padded with zero chunks to `limit.next_power_of_two()` leaves.
+    /// Padding only hashes the actual chunks: the roots of all-zero subtrees are constants.
+    pub fn merkleize(
+        &self,
+        ctx: &mut Context<F>,
+        chunks: &[SszChunk<F>],
+        limit: usize,
+    ) -> SszChunk<F> {
+        assert!(chunks.len() <= limit, "more chunks than the limit");
+        let depth = limit.next_power_of_two().trailing_zeros() as usize;
+        let zero_hashes = native::zero_hashes(depth);
+        if chunks.is_empty() {
+            return self.load_constant_chunk(ctx, zero_hashes[depth]);
+        }
+        let mut nodes = chunks.to_vec();
+        for zero_hash in zero_hashes.iter().take(depth) {
+            if nodes.len() % 2 == 1 {
+                nodes.push(self.load_constant_chunk(ctx, *zero_hash));
+            }
+            nodes = nodes.chunks(2).map(|pair| self.hash_pair(ctx, &pair[0], &pair[1])).collect();
+        }
+        nodes.pop().unwrap()
+    }
+
+    /// `hash_tree_root` of a fixed-size container from the `hash_tree_root` of each of its fields.
+    pub fn container_root(&self, ctx: &mut Context<F>, field_roots: &[SszChunk<F>]) -> SszChunk<F> {
+        assert!(!field_roots.is_empty(), "containers must have at least one field");
+        self.merkleize(ctx, field_roots, field_roots.len())
+    }
+
+    /// `hash_tree_root` of a vector of composite values (e.g. `Vector[Bytes32, N]`) from the root of each element.
+    pub fn composite_vector_root(
+        &self,
+        ctx: &mut Context<F>,
+        element_roots: &[SszChunk<F>],
+    ) -> SszChunk<F> {
+        assert!(!element_roots.is_empty(), "vectors must not be empty");
+        self.merkleize(ctx, element_roots, element_roots.len())
+    }
+
+    /// `hash_tree_root` of a vector of basic values from its serialization,
+    /// e.g. a BLS public key as `Vector[uint8, 48]`.
+    pub fn basic_vector_root(&self, ctx: &mut Context<F>, bytes: &[SafeByte<F>]) -> SszChunk<F> {
+        assert!(!bytes.is_empty(), "vectors must not be empty");
+        let chunks = self.pack(ctx, bytes);
+        self.merkleize(ctx, &chunks, chunks.len())
+    }
+
+    /// Root of the tree in which `leaf` is the node at generalized index `gindex` and `branch` holds
+    /// the siblings of the path from the leaf up to the root.
+    pub fn branch_root(
+        &self,
+        ctx: &mut Context<F>,
+        leaf: &SszChunk<F>,
+        branch: &[SszChunk<F>],
+        gindex: u64,
+    ) -> SszChunk<F> {
+        assert!(gindex > 0, "generalized indices start at 1");
+        let depth = 63 - gindex.leading_zeros() as usize;
+        assert_eq!(branch.len(), depth, "branch length must be the depth of the generalized index");
+        branch.iter().enumerate().fold(leaf.clone(), |node, (i, sibling)| {
+            if (gindex >> i) & 1 == 1 {
+                self.hash_pair(ctx, sibling, &node)
+            } else {
+                self.hash_pair(ctx, &node, sibling)
+            }
+        })
+    }
+
+    /// Same as [Self::branch_root] for a leaf at the assigned position `index` among the `2^branch.len()` nodes of
+    /// its level, i.e. at generalized index `2^branch.len() + index`. Constrains `index` to `branch.len()` bits.
+    pub fn branch_root_at_index(
+        &self,
+        ctx: &mut Context<F>,
+        leaf: &SszChunk<F>,
+        branch: &[SszChunk<F>],
+        index: AssignedValue<F>,
+    ) -> SszChunk<F> {
+        let gate = self.range().gate();
+        let bits = gate.num_to_bits(ctx, index, branch.len());
+        branch.iter().zip(bits).fold(leaf.clone(), |node, (sibling, is_right)| {
+            let (left, right): (Vec<_>, Vec<_>) = node
+                .bytes()
+                .iter()
+                .zip(sibling.bytes().iter())
+                .map(|(node, sibling)| {
+                    // selecting between two bytes gives a byte
+                    let left = gate.select(ctx, *sibling, *node, is_right);
+                    let right = gate.select(ctx, *node, *sibling, is_right);
+                    (SafeTypeChip::unsafe_to_byte(left), SafeTypeChip::unsafe_to_byte(right))
+                })
+                .unzip();
+            let left = SszChunk::new(left.try_into().unwrap());
+            let right = SszChunk::new(right.try_into().unwrap());
+            self.hash_pair(ctx, &left, &right)
+        })
+    }
+
+    /// Returns 1 iff the two chunks are equal.
+    pub fn is_equal_chunk(
+        &self,
+        ctx: &mut Context<F>,
+        a: &SszChunk<F>,
+        b: &SszChunk<F>,
+    ) -> AssignedValue<F> {
+        let gate = self.range().gate();
+        // bytes are range checked, so 16 of them pack into a field element without overflow
+        let pack = |ctx: &mut Context<F>, bytes: &[SafeByte<F>]| {
+            gate.inner_product(
+                ctx,
+                bytes.iter().copied(),
+                (0..16).map(|i| Constant(F::from_u128(1u128 << (8 * i)))),
+            )
+        };
+        let a_lo = pack(ctx, &a.bytes()[..16]);
+        let a_hi = pack(ctx, &a.bytes()[16..]);
+        let b_lo = pack(ctx, &b.bytes()[..16]);
+        let b_hi = pack(ctx, &b.bytes()[16..]);
+        let lo_eq = gate.is_equal(ctx, a_lo, b_lo);
+        let hi_eq = gate.is_equal(ctx, a_hi, b_hi);
+        gate.and(ctx, lo_eq, hi_eq)
+    }
+
+    /// Returns 1 iff `leaf` is the node at generalized index `gindex` of the tree with `root`.
+    pub fn verify_branch(
+        &self,
+        ctx: &mut Context<F>,
+        root: &SszChunk<F>,
+        leaf: &SszChunk<F>,
+        branch: &[SszChunk<F>],
+        gindex: u64,
+    ) -> AssignedValue<F> {
+        let computed_root = self.branch_root(ctx, leaf, branch, gindex);
+        self.is_equal_chunk(ctx, &computed_root, root)
+    }
+
+    /// Returns 1 iff `leaf` is the `index`-th node at depth `branch.len()` of the tree with `root`.
+    pub fn verify_branch_at_index(
+        &self,
+        ctx: &mut Context<F>,
+        root: &SszChunk<F>,
+        leaf: &SszChunk<F>,
+        branch: &[SszChunk<F>],
+        index: AssignedValue<F>,
+    ) -> AssignedValue<F> {
+        let computed_root = self.branch_root_at_index(ctx, leaf, branch, index);
+        self.is_equal_chunk(ctx, &computed_root, root)
+    }
+}
+
+/// Native SSZ Merkleization.
+pub mod native {
+    use sha2::{Digest, Sha256};
+
+    /// `sha256(left || right)`
+    pub fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+        Sha256::new().chain_update(left).chain_update(right).finalize().into()
+    }
+
+    /// Roots of the all-zero trees of height `0..=depth`.
+    pub fn zero_hashes(depth: usize) -> Vec<[u8; 32]> {
+        let mut zero_hashes = vec![[0u8; 32]];
+        for i in 0..depth {
+            zero_hashes.push(hash_pair(&zero_hashes[i], &zero_hashes[i]));
+        }
+        zero_hashes
+    }
+
+    /// Chunk of a uint64.
+    pub fn uint64_chunk(value: u64) -> [u8; 32] {
+        let mut chunk = [0u8; 32];
+        chunk[..8].copy_from_slice(&value.to_le_bytes());
+        chunk
+    }
+
+    /// Packs serialized basic values into chunks.
+    pub fn pack(bytes: &[u8]) -> Vec<[u8; 32]> {
+        bytes
+            .chunks(32)
+            .map(|chunk| {
+                let mut padded = [0u8; 32];
+                padded[..chunk.len()].copy_from_slice(chunk);
+                padded
+            })
+            .collect()
+    }
+
+    // All levels of the tree of `chunks` padded to `limit.next_power_of_two()` leaves, from the leaves to the root
+    fn levels(chunks: &[[u8; 32]], limit: usize) -> Vec<Vec<[u8; 32]>> {
+        assert!(chunks.len() <= limit, "more chunks than the limit");
+        let depth = limit.next_power_of_two().trailing_zeros() as usize;
+        let mut level = chunks.to_vec();
+        level.resize(1 << depth, [0u8; 32]);
+        let mut levels = vec![level];
+        for _ in 0..depth {
+            let level = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| hash_pair(&pair[0], &pair[1]))
+                .collect();
+            levels.push(level);
+        }
+        levels
+    }
+
+    /// Root of `chunks` padded with zero chunks to `limit.next_power_of_two()` leaves.
+    pub fn merkleize(chunks: &[[u8; 32]], limit: usize) -> [u8; 32] {
+        levels(chunks, limit).last().unwrap()[0]
+    }
+
+    /// Siblings of the path from the `index`-th chunk up to the root.
+    pub fn branch(chunks: &[[u8; 32]], limit: usize, index: usize) -> Vec<[u8; 32]> {
+        assert!(index < chunks.len(), "chunk index out of range");
+        let levels = levels(chunks, limit);
+        levels[..levels.len() - 1]
+            .iter()
+            .enumerate()
+            .map(|(height, level)| level[(index >> height) ^ 1])
+            .collect()
+    }
+
+    /// Root of the tree in which `leaf` is the node at generalized index `gindex`.
+    pub fn branch_root(leaf: &[u8; 32], branch: &[[u8; 32]], gindex: u64) -> [u8; 32] {
+        branch.iter().enumerate().fold(*leaf, |node, (i, sibling)| {
+            if (gindex >> i) & 1 == 1 {
+                hash_pair(sibling, &node)
+            } else {
+                hash_pair(&node, sibling)
+            }
+        })
+    }
+
+    /// `BeaconBlockHeader` of the Ethereum consensus specs.
+    #[derive(Clone, Debug, Default)]
+    pub struct BeaconBlockHeader {
+        pub slot: u64,
+        pub proposer_index: u64,
+        pub parent_root: [u8; 32],
+        pub state_root: [u8; 32],
+        pub body_root: [u8; 32],
+    }
+
+    impl BeaconBlockHeader {
+        /// Number of fields of the container.
+        pub const NUM_FIELDS: usize = 5;
+        /// Position of `state_root` among the fields.
+        pub const STATE_ROOT_INDEX: usize = 3;
+
+        /// `hash_tree_root` of each field.
+        pub fn field_roots(&self) -> Vec<[u8; 32]> {
+            vec![
+                uint64_chunk(self.slot),
+                uint64_chunk(self.proposer_index),
+                self.parent_root,
+                self.state_root,
+                self.body_root,
+            ]
+        }
+
+        /// `hash_tree_root` of the header.
+        pub fn hash_tree_root(&self) -> [u8; 32] {
+            merkleize(&self.field_roots(), Self::NUM_FIELDS)
+        }
+
+        /// Branch of the `field`-th field, at generalized index `chunk_gindex(NUM_FIELDS, field)`.
+        pub fn field_branch(&self, field: usize) -> Vec<[u8; 32]> {
+            branch(&self.field_roots(), Self::NUM_FIELDS, field)
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use halo2_base::{
+    gates::{RangeChip, RangeInstructions},
+    halo2_proofs::halo2curves::{bn256::Fr, ff::Field},
+    safe_types::SafeTypeChip,
+    utils::{testing::base_test, ScalarField},
+    Context,
+};
+use rand::Rng;
+
+use super::{chunk_gindex, concat_gindices, native, SszChip, SszChunk};
+use crate::{
+    keccak::component::circuit::shard::create_hasher,
+    sha256::component::{
+        output::{calculate_circuit_outputs_commit, multi_inputs_to_circuit_outputs},
+        promise::Sha256PromiseChip,
+    },
+};
+
+type BeaconBlockHeader = native::BeaconBlockHeader;
+
+fn random_header() -> BeaconBlockHeader {
+    let mut rng = rand::thread_rng();
+    BeaconBlockHeader {
+        slot: rng.gen(),
+        proposer_index: rng.gen(),
+        parent_root: rng.gen(),
+        state_root: rng.gen(),
+        body_root: rng.gen(),
+    }
+}
+
+fn chunk_value(chunk: &SszChunk<Fr>) -> [u8; 32] {
+    core::array::from_fn(|i| chunk.bytes()[i].value().get_lower_32() as u8)
+}
+
+// Preimages of the nodes hashed along the branch of `leaf` at `gindex`, in the order the chip hashes them
+fn branch_preimages(leaf: [u8; 32], branch: &[[u8; 32]], gindex: u64) -> Vec<Vec<u8>> {
+    let mut node = leaf;
+    let mut preimages = vec![];
+    for (i, sibling) in branch.iter().enumerate() {
+        let (left, right) =
+            if (gindex >> i) & 1 == 1 { (*sibling, node) } else { (node, *sibling) };
+        preimages.push([left, right].concat());
+        node = native::hash_pair(&left, &right);
+    }
+    preimages
+}
+
+#[test]
+fn test_ssz_native() {
+    // sha256 of 64 zero bytes
+    let zero_hash_1 = "f5a5fd42d16a20302798ef6ed309979b43003d2320d9f0e8ea9831a92759fb4b";
+    assert_eq!(hex::encode(native::zero_hashes(1)[1]), zero_hash_1);
+
+    assert_eq!(chunk_gindex(5, 3), 11);
+    // field 3 of a container at field 2 of a container with 5 fields
+    assert_eq!(concat_gindices(&[chunk_gindex(5, 2), chunk_gindex(5, 3)]), 10 * 8 + 3);
+
+    let header = random_header();
+    let branch = header.field_branch(BeaconBlockHeader::STATE_ROOT_INDEX);
+    let gindex = chunk_gindex(BeaconBlockHeader::NUM_FIELDS, BeaconBlockHeader::STATE_ROOT_INDEX);
+    let root = native::branch_root(&header.state_root, &branch, gindex);
+    assert_eq!(root, header.hash_tree_root());
+}
+
+#[test]
+fn test_ssz_beacon_block_header() {
+    let header = random_header();
+    let root = header.hash_tree_root();
+
+    base_test().k(18).lookup_bits(8).run(|ctx: &mut Context<Fr>, range: &RangeChip<Fr>| {
+        let mut hasher = create_hasher();
+        hasher.initialize_consts(ctx, range.gate());
+        let sha256_chip = Sha256PromiseChip::new(range, &hasher);
+        let ssz_chip = SszChip::new(&sha256_chip);
+
+        let slot = ctx.load_witness(Fr::from(header.slot));
+        let proposer_index = ctx.load_witness(Fr::from(header.proposer_index));
+        let field_roots = vec![
+            ssz_chip.uint64_chunk(ctx, slot),
+            ssz_chip.uint64_chunk(ctx, proposer_index),
+            ssz_chip.load_chunk(ctx, header.parent_root),
+            ssz_chip.load_chunk(ctx, header.state_root),
+            ssz_chip.load_chunk(ctx, header.body_root),
+        ];
+        let computed_root = ssz_chip.container_root(ctx, &field_roots);
+        assert_eq!(chunk_value(&computed_root), root);
+
+        let root = ssz_chip.load_chunk(ctx, root);
+        let branch = header
+            .field_branch(BeaconBlockHeader::STATE_ROOT_INDEX)
+            .into_iter()
+            .map(|node| ssz_chip.load_chunk(ctx, node))
+            .collect::<Vec<_>>();
+        let verify_at_index = |ctx: &mut Context<Fr>, index: u64| {
+            let index = ctx.load_witness(Fr::from(index));
+            *ssz_chip.verify_branch_at_index(ctx, &root, &field_roots[3], &branch, index).value()
+        };
+        assert_eq!(verify_at_index(ctx, 3), Fr::ONE);
+        assert_eq!(verify_at_index(ctx, 2), Fr::ZERO);
+    });
+}
+
+#[test]
+fn test_ssz_state_root_branch() {
+    let header = random_header();
+    let root = header.hash_tree_root();
+    // state_root is the node at generalized index 11 of the header
+    let gindex = chunk_gindex(BeaconBlockHeader::NUM_FIELDS, BeaconBlockHeader::STATE_ROOT_INDEX);
+    assert_eq!(gindex, 11);
+    let branch = header.field_branch(BeaconBlockHeader::STATE_ROOT_INDEX);
+    assert_eq!(native::branch_root(&header.state_root, &branch, gindex), root);
+    let preimages = branch_preimages(header.state_root, &branch, gindex);
+    // every 64 byte preimage takes 2 SHA-256 blocks
+    let capacity = 8;
+
+    base_test().k(18).lookup_bits(8).run(|ctx: &mut Context<Fr>, range: &RangeChip<Fr>| {
+        let mut hasher = create_hasher();
+        hasher.initialize_consts(ctx, range.gate());
+        let sha256_chip = Sha256PromiseChip::new(range, &hasher);
+        let ssz_chip = SszChip::new(&sha256_chip);
+
+        let root_assigned = ssz_chip.load_chunk(ctx, root);
+        let state_root = ssz_chip.load_chunk(ctx, header.state_root);
+        let branch = branch.iter().map(|node| ssz_chip.load_chunk(ctx, *node)).collect::<Vec<_>>();
+        let computed_root = ssz_chip.branch_root(ctx, &state_root, &branch, gindex);
+        assert_eq!(chunk_value(&computed_root), root);
+        let is_valid = ssz_chip.is_equal_chunk(ctx, &computed_root, &root_assigned);
+        assert_eq!(*is_valid.value(), Fr::ONE);
+
+        let expected_commit = calculate_circuit_outputs_commit(&multi_inputs_to_circuit_outputs::<
+            Fr,
+        >(&preimages, capacity));
+        let commit = sha256_chip.promise_commit(ctx, capacity);
+        assert_eq!(*commit.value(), expected_commit);
+
+        // the parent root is not at the generalized index of the state root
+        let parent_root = ssz_chip.load_chunk(ctx, header.parent_root);
+        let is_valid = ssz_chip.verify_branch(ctx, &root_assigned, &parent_root, &branch, gindex);
+        assert_eq!(*is_valid.value(), Fr::ZERO);
+    });
+}
+
+#[test]
+fn test_ssz_basic_vector() {
+    // a BLS12-381 public key is a Vector[uint8, 48]
+    let pubkey = (0..48).map(|_| rand::random::<u8>()).collect::<Vec<_>>();
+    let expected = native::merkleize(&native::pack(&pubkey), 2);
+
+    base_test().k(17).lookup_bits(8).run(|ctx: &mut Context<Fr>, range: &RangeChip<Fr>| {
+        let mut hasher = create_hasher();
+        hasher.initialize_consts(ctx, range.gate());
+        let sha256_chip = Sha256PromiseChip::new(range, &hasher);
+        let ssz_chip = SszChip::new(&sha256_chip);
+        let bytes = ctx.assign_witnesses(pubkey.iter().map(|b| Fr::from(*b as u64)));
+        let bytes = SafeTypeChip::new(range).raw_to_fix_len_bytes_vec(ctx, bytes, 48);
+        let root = ssz_chip.basic_vector_root(ctx, bytes.bytes());
+        assert_eq!(chunk_value(&root), expected);
+    });
+}